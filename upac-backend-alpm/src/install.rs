@@ -0,0 +1,142 @@
+// Imports
+use stabby::option::Option as StabOption;
+use stabby::string::String as StabString;
+
+// Package scripts built from the functions of an .INSTALL file
+pub struct InstallScripts {
+    pub pre_install: StabOption<StabString>,
+    pub post_install: StabOption<StabString>,
+    pub pre_remove: StabOption<StabString>,
+    pub post_remove: StabOption<StabString>,
+}
+
+impl InstallScripts {
+    // Function to wrap .INSTALL into one script per phase; upac passes the new version
+    // as $1 and the old one as $2, removals get the old version as $1 and, during an
+    // upgrade, the new one as $2
+    pub fn parse(content: &str) -> Self {
+        let defined = |function: &str| Self::defines(content, function);
+
+        let install_phase = |install: &str, upgrade: &str| {
+            let script = match (defined(install), defined(upgrade)) {
+                (false, false) => return StabOption::None(),
+                (true, false) => format!("if [ -z \"$2\" ]; then\n    {install} \"$1\"\nfi"),
+                (false, true) => format!("if [ -n \"$2\" ]; then\n    {upgrade} \"$1\" \"$2\"\nfi"),
+                (true, true) => format!(
+                    "if [ -n \"$2\" ]; then\n    {upgrade} \"$1\" \"$2\"\nelse\n    {install} \"$1\"\nfi"
+                ),
+            };
+            StabOption::Some(Self::wrap(content, &script))
+        };
+
+        // pacman runs no removal functions when a package is upgraded
+        let remove_phase = |remove: &str| {
+            if defined(remove) {
+                let script = format!("if [ -z \"$2\" ]; then\n    {remove} \"$1\"\nfi");
+                StabOption::Some(Self::wrap(content, &script))
+            } else {
+                StabOption::None()
+            }
+        };
+
+        Self {
+            pre_install: install_phase("pre_install", "pre_upgrade"),
+            post_install: install_phase("post_install", "post_upgrade"),
+            pre_remove: remove_phase("pre_remove"),
+            post_remove: remove_phase("post_remove"),
+        }
+    }
+
+    // Scripts of a package without .INSTALL
+    pub fn empty() -> Self {
+        Self {
+            pre_install: StabOption::None(),
+            post_install: StabOption::None(),
+            pre_remove: StabOption::None(),
+            post_remove: StabOption::None(),
+        }
+    }
+
+    fn wrap(content: &str, call: &str) -> StabString {
+        format!("{}\n\n{call}\n", content.trim_end())
+            .as_str()
+            .into()
+    }
+
+    // Function to check whether the script defines a shell function, as `name()` or `function name`
+    fn defines(content: &str, function: &str) -> bool {
+        content.lines().any(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix("function ").unwrap_or(line).trim_start();
+
+            line.strip_prefix(function).is_some_and(|rest| {
+                let rest = rest.trim_start();
+                rest.starts_with('(') || rest.starts_with('{')
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::Command;
+
+    const INSTALL: &str = "post_install() {\n\
+                           \x20   echo \"installed $1\"\n\
+                           }\n\
+                           \n\
+                           post_upgrade() {\n\
+                           \x20   echo \"upgraded $1 from $2\"\n\
+                           }\n\
+                           \n\
+                           pre_remove () {\n\
+                           \x20   echo \"removing $1\"\n\
+                           }\n";
+
+    // Function to run a script the way upac does, with the versions as arguments
+    fn run(script: &StabOption<StabString>, versions: &[&str]) -> String {
+        let script = script.as_ref().expect("script is missing");
+        let output = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(script.as_str())
+            .arg("sh")
+            .args(versions)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn calls_the_function_of_each_phase() {
+        let scripts = InstallScripts::parse(INSTALL);
+
+        assert!(scripts.pre_install.is_none());
+        assert!(scripts.post_remove.is_none());
+
+        assert_eq!(run(&scripts.post_install, &["1.0-1"]), "installed 1.0-1\n");
+        assert_eq!(
+            run(&scripts.post_install, &["2.0-1", "1.0-1"]),
+            "upgraded 2.0-1 from 1.0-1\n"
+        );
+        assert_eq!(run(&scripts.pre_remove, &["1.0-1"]), "removing 1.0-1\n");
+
+        // The old version goes away in an upgrade, pacman runs no removal function then
+        assert_eq!(run(&scripts.pre_remove, &["1.0-1", "2.0-1"]), "");
+    }
+
+    #[test]
+    fn finds_functions_by_definition_only() {
+        let scripts = InstallScripts::parse(
+            "function pre_upgrade {\n    true\n}\n\
+             post_install_helper() {\n    true\n}\n\
+             echo post_remove\n",
+        );
+
+        assert!(scripts.pre_install.is_some());
+        assert!(scripts.post_install.is_none());
+        assert!(scripts.post_remove.is_none());
+    }
+}
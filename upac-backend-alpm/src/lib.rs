@@ -0,0 +1,143 @@
+// Imports
+use upac_lib::archive::{self, Compression};
+use upac_lib::{Backend, VersionScheme};
+
+use upac_types::{BackendError, BackendResult, ExtractedPackage, PackageMetadata};
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Mods
+pub mod install;
+pub mod mtree;
+pub mod pkginfo;
+
+pub use install::InstallScripts;
+pub use mtree::MtreeEntry;
+pub use pkginfo::PkgInfo;
+
+const PKGINFO: &str = ".PKGINFO";
+const INSTALL: &str = ".INSTALL";
+const MTREE: &str = ".MTREE";
+
+// Offset of the "ustar" magic in a tar header
+const USTAR_OFFSET: usize = 257;
+
+// Backend for Arch Linux packages (.pkg.tar.zst, .pkg.tar.xz, ...)
+pub struct AlpmBackend;
+
+impl Backend for AlpmBackend {
+    fn name(&self) -> &str {
+        "alpm"
+    }
+
+    fn version_scheme(&self) -> VersionScheme {
+        VersionScheme::Alpm
+    }
+
+    fn detect(&self, path: &Path, header: &[u8]) -> bool {
+        let named = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().contains(".pkg.tar"));
+
+        named
+            && (Compression::detect(header) != Compression::None
+                || header.get(USTAR_OFFSET..USTAR_OFFSET + 5) == Some(b"ustar"))
+    }
+
+    fn read_metadata(&self, path: &Path) -> BackendResult<PackageMetadata> {
+        let content =
+            archive::read_tar_member(archive::decompress(File::open(path)?)?, Path::new(PKGINFO))?
+                .ok_or_else(|| missing(PKGINFO))?;
+
+        let info = PkgInfo::parse(&String::from_utf8_lossy(&content))?;
+
+        Ok(PackageMetadata {
+            name: info.name.as_str().into(),
+            version: info.version.as_str().into(),
+            format: self.name().into(),
+            dependencies: to_stab_vec(&info.depends),
+            provides: to_stab_vec(&info.provides),
+            conflicts: to_stab_vec(&info.conflicts),
+        })
+    }
+
+    fn extract(&self, path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+        let mut pkginfo = None;
+        let mut install = None;
+        let mut mtree = None;
+
+        // Metadata lives in top-level dot files, none of them is installed
+        let file_list = archive::unpack_tar(
+            archive::decompress(File::open(path)?)?,
+            temp_dir,
+            |member, reader| {
+                let Some(name) = member
+                    .to_str()
+                    .filter(|name| name.starts_with('.') && !name.contains('/'))
+                else {
+                    return Ok(false);
+                };
+
+                match name {
+                    PKGINFO => pkginfo = Some(read_text(reader)?),
+                    INSTALL => install = Some(read_text(reader)?),
+                    // .MTREE is a gzip compressed mtree listing
+                    MTREE => mtree = Some(read_text(&mut archive::decompress(reader)?)?),
+                    _ => {}
+                }
+                Ok(true)
+            },
+        )?;
+
+        let info = PkgInfo::parse(&pkginfo.ok_or_else(|| missing(PKGINFO))?)?;
+
+        if let Some(mtree) = mtree {
+            mtree::verify(&mtree::parse(&mtree)?, temp_dir)?;
+        }
+
+        let scripts = install
+            .as_deref()
+            .map(InstallScripts::parse)
+            .unwrap_or_else(InstallScripts::empty);
+
+        Ok(ExtractedPackage {
+            name: info.name.as_str().into(),
+            version: info.version.as_str().into(),
+            format: self.name().into(),
+            file_list,
+            dependencies: to_stab_vec(&info.depends),
+            provides: to_stab_vec(&info.provides),
+            conflicts: to_stab_vec(&info.conflicts),
+            config_files: to_stab_vec(&info.backup),
+            pre_install: scripts.pre_install,
+            post_install: scripts.post_install,
+            pre_remove: scripts.pre_remove,
+            post_remove: scripts.post_remove,
+        })
+    }
+}
+
+fn read_text(reader: &mut dyn Read) -> BackendResult<String> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+
+    String::from_utf8(content).map_err(|err| {
+        BackendError::Format(format!("Invalid UTF-8 in package metadata: {err}").into())
+    })
+}
+
+fn missing(member: &str) -> BackendError {
+    BackendError::Format(format!("Package has no {member}").into())
+}
+
+fn to_stab_vec(values: &[String]) -> StabVec<StabString> {
+    values
+        .iter()
+        .map(|value| StabString::from(value.as_str()))
+        .collect()
+}
@@ -0,0 +1,243 @@
+// Imports
+use upac_lib::archive::sha256_file;
+
+use upac_types::{BackendError, BackendResult};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Entry of .MTREE with the keywords upac checks
+#[derive(Debug)]
+pub struct MtreeEntry {
+    pub path: PathBuf,
+    pub entry_type: String,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    pub link: Option<PathBuf>,
+}
+
+// Function to parse the (already decompressed) mtree listing of a package
+pub fn parse(content: &str) -> BackendResult<Vec<MtreeEntry>> {
+    let mut defaults: HashMap<String, String> = HashMap::new();
+    let mut entries = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let first = words.next().unwrap_or_default();
+
+        match first {
+            "/set" => {
+                for (key, value) in words.map(keyword) {
+                    defaults.insert(key.to_string(), value.to_string());
+                }
+            }
+            "/unset" => {
+                for (key, _) in words.map(keyword) {
+                    defaults.remove(key);
+                }
+            }
+            path => {
+                let mut keywords = defaults.clone();
+                for (key, value) in words.map(keyword) {
+                    keywords.insert(key.to_string(), value.to_string());
+                }
+
+                let size = keywords
+                    .get("size")
+                    .map(|size| size.parse::<u64>())
+                    .transpose()
+                    .map_err(|err| {
+                        BackendError::Format(format!("Invalid .MTREE size: {err}").into())
+                    })?;
+
+                entries.push(MtreeEntry {
+                    path: PathBuf::from(unescape(path.trim_start_matches("./"))),
+                    entry_type: keywords
+                        .get("type")
+                        .cloned()
+                        .unwrap_or_else(|| "file".into()),
+                    size,
+                    sha256: keywords.get("sha256digest").cloned(),
+                    link: keywords
+                        .get("link")
+                        .map(|link| PathBuf::from(unescape(link))),
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+// Function to check extracted files against their .MTREE entries
+pub fn verify(entries: &[MtreeEntry], temp_dir: &Path) -> BackendResult<()> {
+    for entry in entries {
+        // Package metadata files are not extracted
+        if entry.path.to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = temp_dir.join(&entry.path);
+        let mismatch = |what: &str| {
+            BackendError::Format(
+                format!("{} does not match .MTREE: {what}", entry.path.display()).into(),
+            )
+        };
+
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(mismatch("missing")),
+            Err(err) => return Err(err.into()),
+        };
+
+        match entry.entry_type.as_str() {
+            "dir" if !metadata.is_dir() => return Err(mismatch("not a directory")),
+            "link" => {
+                if !metadata.file_type().is_symlink() {
+                    return Err(mismatch("not a symlink"));
+                }
+                if entry
+                    .link
+                    .as_ref()
+                    .is_some_and(|link| fs::read_link(&path).ok().as_ref() != Some(link))
+                {
+                    return Err(mismatch("link target"));
+                }
+            }
+            "file" => {
+                if !metadata.is_file() {
+                    return Err(mismatch("not a regular file"));
+                }
+                if entry.size.is_some_and(|size| size != metadata.len()) {
+                    return Err(mismatch("size"));
+                }
+                if let Some(sha256) = &entry.sha256 {
+                    if &sha256_file(&path)? != sha256 {
+                        return Err(mismatch("checksum"));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn keyword(word: &str) -> (&str, &str) {
+    word.split_once('=').unwrap_or((word, ""))
+}
+
+// Function to decode the octal escapes (`\040`) mtree uses for special characters
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escape = bytes.get(index + 1..index + 4).filter(|digits| {
+            bytes[index] == b'\\' && digits.iter().all(|digit| (b'0'..=b'7').contains(digit))
+        });
+
+        match escape {
+            Some(digits) => {
+                let value = digits
+                    .iter()
+                    .fold(0u32, |value, digit| value * 8 + u32::from(digit - b'0'));
+                decoded.push(value as u8);
+                index += 4;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::fs::symlink;
+
+    // sha256 of "hello\n"
+    const HELLO_SHA256: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+    fn listing() -> String {
+        format!(
+            "#mtree\n\
+             /set type=file uid=0 gid=0 mode=644\n\
+             ./.PKGINFO time=1.0 size=120\n\
+             ./usr time=1.0 type=dir mode=755\n\
+             ./usr/bin time=1.0 type=dir mode=755\n\
+             ./usr/bin/hello time=1.0 mode=755 size=6 sha256digest={HELLO_SHA256}\n\
+             ./usr/bin/hi\\040there time=1.0 mode=777 type=link link=hello\n\
+             /unset mode\n\
+             ./usr/share time=1.0 type=dir\n"
+        )
+    }
+
+    #[test]
+    fn applies_defaults_and_escapes() {
+        let entries = parse(&listing()).unwrap();
+        let paths: Vec<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+
+        assert_eq!(
+            paths,
+            [
+                ".PKGINFO",
+                "usr",
+                "usr/bin",
+                "usr/bin/hello",
+                "usr/bin/hi there",
+                "usr/share"
+            ]
+            .map(Path::new)
+        );
+        assert_eq!(entries[0].entry_type, "file");
+        assert_eq!(entries[1].entry_type, "dir");
+        assert_eq!(entries[3].size, Some(6));
+        assert_eq!(entries[3].sha256.as_deref(), Some(HELLO_SHA256));
+        assert_eq!(entries[4].entry_type, "link");
+        assert_eq!(entries[4].link.as_deref(), Some(Path::new("hello")));
+        assert!(parse("./usr/bin/hello size=big\n").is_err());
+    }
+
+    #[test]
+    fn checks_extracted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("usr/bin")).unwrap();
+        fs::create_dir_all(dir.path().join("usr/share")).unwrap();
+        fs::write(dir.path().join("usr/bin/hello"), "hello\n").unwrap();
+        symlink("hello", dir.path().join("usr/bin/hi there")).unwrap();
+
+        let entries = parse(&listing()).unwrap();
+        verify(&entries, dir.path()).unwrap();
+
+        let error = |entries: &[MtreeEntry]| verify(entries, dir.path()).unwrap_err().to_string();
+
+        fs::write(dir.path().join("usr/bin/hello"), "hullo\n").unwrap();
+        assert!(error(&entries).contains("usr/bin/hello does not match .MTREE: checksum"));
+
+        fs::write(dir.path().join("usr/bin/hello"), "hello!\n").unwrap();
+        assert!(error(&entries).contains("usr/bin/hello does not match .MTREE: size"));
+
+        fs::write(dir.path().join("usr/bin/hello"), "hello\n").unwrap();
+        fs::remove_file(dir.path().join("usr/bin/hi there")).unwrap();
+        symlink("elsewhere", dir.path().join("usr/bin/hi there")).unwrap();
+        assert!(error(&entries).contains("usr/bin/hi there does not match .MTREE: link target"));
+
+        fs::remove_file(dir.path().join("usr/bin/hi there")).unwrap();
+        assert!(error(&entries).contains("usr/bin/hi there does not match .MTREE: missing"));
+    }
+}
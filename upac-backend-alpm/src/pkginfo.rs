@@ -0,0 +1,90 @@
+// Imports
+use upac_types::{BackendError, BackendResult};
+
+// Fields of .PKGINFO used by upac; repeated keys accumulate
+#[derive(Debug, Default)]
+pub struct PkgInfo {
+    pub name: String,
+    pub version: String,
+    pub depends: Vec<String>,
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+    // Configuration files, relative to the root
+    pub backup: Vec<String>,
+}
+
+impl PkgInfo {
+    // Function to parse the `key = value` lines written by makepkg
+    pub fn parse(content: &str) -> BackendResult<Self> {
+        let mut info = Self::default();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(BackendError::Format(
+                    format!("Invalid .PKGINFO line: {line}").into(),
+                ));
+            };
+            let value = value.trim().to_string();
+
+            match key.trim() {
+                "pkgname" => info.name = value,
+                "pkgver" => info.version = value,
+                "depend" => info.depends.push(value),
+                "provides" => info.provides.push(value),
+                "conflict" => info.conflicts.push(value),
+                "backup" => info.backup.push(value),
+                _ => {}
+            }
+        }
+
+        if info.name.is_empty() || info.version.is_empty() {
+            return Err(BackendError::Format(
+                ".PKGINFO lacks pkgname or pkgver".into(),
+            ));
+        }
+
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_makepkg_fields() {
+        let info = PkgInfo::parse(
+            "# Generated by makepkg 6.1.0\n\
+             pkgname = hello\n\
+             pkgbase = hello\n\
+             pkgver = 1:2.12-3\n\
+             pkgdesc = Say hello = greet\n\
+             depend = glibc\n\
+             depend = bash>=5.0\n\
+             provides = greeter=2.12\n\
+             conflict = hello-git\n\
+             backup = etc/hello.conf\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            (info.name.as_str(), info.version.as_str()),
+            ("hello", "1:2.12-3")
+        );
+        assert_eq!(info.depends, ["glibc", "bash>=5.0"]);
+        assert_eq!(info.provides, ["greeter=2.12"]);
+        assert_eq!(info.conflicts, ["hello-git"]);
+        assert_eq!(info.backup, ["etc/hello.conf"]);
+    }
+
+    #[test]
+    fn rejects_incomplete_pkginfo() {
+        assert!(PkgInfo::parse("pkgname = hello\n").is_err());
+        assert!(PkgInfo::parse("pkgname = hello\npkgver = 1.0-1\nnonsense\n").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+// Imports
+use upac_types::{BackendError, BackendResult};
+
+// Fields of the `control` file used by upac, with relations already in upac syntax (`name>=version`)
+#[derive(Debug, Default)]
+pub struct Control {
+    pub package: String,
+    pub version: String,
+    pub depends: Vec<String>,
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+impl Control {
+    // Function to parse the deb822 paragraph of a binary package
+    pub fn parse(content: &str) -> BackendResult<Self> {
+        let mut control = Self::default();
+        let mut fields: Vec<(String, String)> = Vec::new();
+
+        for line in content.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Continuation lines start with whitespace
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(BackendError::Format(
+                    format!("Invalid control line: {line}").into(),
+                ));
+            };
+            fields.push((key.trim().to_string(), value.trim().to_string()));
+        }
+
+        for (key, value) in fields {
+            match key.to_ascii_lowercase().as_str() {
+                "package" => control.package = value,
+                "version" => control.version = value,
+                "depends" | "pre-depends" => control.depends.extend(relations(&value)),
+                "provides" => control.provides.extend(relations(&value)),
+                "conflicts" => control.conflicts.extend(relations(&value)),
+                _ => {}
+            }
+        }
+
+        if control.package.is_empty() || control.version.is_empty() {
+            return Err(BackendError::Format(
+                "control lacks Package or Version".into(),
+            ));
+        }
+
+        Ok(control)
+    }
+}
+
+// Function to convert a relation field like `libc6 (>= 2.34), foo | bar` into upac dependencies;
+// upac has no alternatives, so only the first one of a group is kept
+fn relations(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter_map(|group| group.split('|').next())
+        .map(str::trim)
+        .filter(|relation| !relation.is_empty())
+        .map(relation)
+        .collect()
+}
+
+fn relation(relation: &str) -> String {
+    let (name, constraint) = match relation.split_once('(') {
+        Some((name, constraint)) => (name, Some(constraint.trim_end_matches(')'))),
+        None => (relation, None),
+    };
+    // Architecture qualifiers (`foo:any`) and restrictions (`foo [amd64]`) do not apply here
+    let name = name
+        .split([':', '[', '<'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+
+    let Some(constraint) = constraint else {
+        return name.to_string();
+    };
+
+    let constraint = constraint.trim();
+    let operator_len = constraint
+        .find(|char: char| !matches!(char, '<' | '>' | '='))
+        .unwrap_or(constraint.len());
+    let (operator, version) = constraint.split_at(operator_len);
+
+    // dpkg still reads the obsolete single `<` and `>` as `<=` and `>=`
+    let operator = match operator {
+        "<<" => "<",
+        ">>" => ">",
+        "<" => "<=",
+        ">" => ">=",
+        other => other,
+    };
+
+    format!("{name}{operator}{}", version.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_relations_to_upac_syntax() {
+        let control = Control::parse(
+            "Package: tool\n\
+             Version: 1.4-2\n\
+             Pre-Depends: dpkg (>= 1.19)\n\
+             Depends: libc6 (>= 2.34), libssl3 (<< 4) | libssl1.1,\n\
+             \x20python3:any, old (< 2), zlib1g [amd64]\n\
+             Provides: tool-api (= 1.4)\n\
+             Conflicts: tool-legacy (>> 0.9)\n\
+             Description: a tool\n",
+        )
+        .unwrap();
+
+        assert_eq!(control.package, "tool");
+        assert_eq!(control.version, "1.4-2");
+        assert_eq!(
+            control.depends,
+            [
+                "dpkg>=1.19",
+                "libc6>=2.34",
+                "libssl3<4",
+                "python3",
+                "old<=2",
+                "zlib1g"
+            ]
+        );
+        assert_eq!(control.provides, ["tool-api=1.4"]);
+        assert_eq!(control.conflicts, ["tool-legacy>0.9"]);
+    }
+
+    #[test]
+    fn rejects_incomplete_control() {
+        assert!(Control::parse("Package: tool\n").is_err());
+        assert!(Control::parse("Package: tool\nnot a field\n").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+// Imports
+use upac_lib::archive;
+use upac_lib::{Backend, VersionScheme};
+
+use upac_types::{BackendError, BackendResult, ExtractedPackage, PackageMetadata};
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Mods
+pub mod control;
+pub mod scripts;
+
+pub use control::Control;
+pub use scripts::MaintainerScripts;
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const CONTROL_MEMBER: &str = "control.tar";
+const DATA_MEMBER: &str = "data.tar";
+
+// Backend for Debian binary packages (.deb)
+pub struct DebBackend;
+
+impl Backend for DebBackend {
+    fn name(&self) -> &str {
+        "deb"
+    }
+
+    fn version_scheme(&self) -> VersionScheme {
+        VersionScheme::Dpkg
+    }
+
+    fn detect(&self, path: &Path, header: &[u8]) -> bool {
+        path.extension().is_some_and(|extension| extension == "deb") && header.starts_with(AR_MAGIC)
+    }
+
+    fn read_metadata(&self, path: &Path) -> BackendResult<PackageMetadata> {
+        let mut archive = ar::Archive::new(File::open(path)?);
+
+        while let Some(entry) = archive.next_entry() {
+            let entry = entry?;
+
+            if member_kind(&entry) == Some(CONTROL_MEMBER) {
+                let control = ControlArchive::read(entry)?;
+
+                return Ok(PackageMetadata {
+                    name: control.control.package.as_str().into(),
+                    version: control.control.version.as_str().into(),
+                    format: self.name().into(),
+                    dependencies: to_stab_vec(&control.control.depends),
+                    provides: to_stab_vec(&control.control.provides),
+                    conflicts: to_stab_vec(&control.control.conflicts),
+                });
+            }
+        }
+
+        Err(missing(CONTROL_MEMBER))
+    }
+
+    fn extract(&self, path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+        let mut archive = ar::Archive::new(File::open(path)?);
+        let mut control = None;
+        let mut file_list = None;
+
+        while let Some(entry) = archive.next_entry() {
+            let entry = entry?;
+
+            match member_kind(&entry) {
+                Some(CONTROL_MEMBER) => control = Some(ControlArchive::read(entry)?),
+                Some(DATA_MEMBER) => {
+                    file_list = Some(archive::unpack_tar(
+                        archive::decompress(entry)?,
+                        temp_dir,
+                        |_, _| Ok(false),
+                    )?)
+                }
+                _ => {}
+            }
+        }
+
+        let control = control.ok_or_else(|| missing(CONTROL_MEMBER))?;
+        let file_list = file_list.ok_or_else(|| missing(DATA_MEMBER))?;
+        let info = control.control;
+        let scripts = control.scripts;
+
+        Ok(ExtractedPackage {
+            name: info.package.as_str().into(),
+            version: info.version.as_str().into(),
+            format: self.name().into(),
+            file_list,
+            dependencies: to_stab_vec(&info.depends),
+            provides: to_stab_vec(&info.provides),
+            conflicts: to_stab_vec(&info.conflicts),
+            config_files: to_stab_vec(&control.conffiles),
+            pre_install: scripts.pre_install,
+            post_install: scripts.post_install,
+            pre_remove: scripts.pre_remove,
+            post_remove: scripts.post_remove,
+        })
+    }
+}
+
+// Contents of control.tar.*
+struct ControlArchive {
+    control: Control,
+    scripts: MaintainerScripts,
+    // Configuration files, relative to the root
+    conffiles: Vec<String>,
+}
+
+impl ControlArchive {
+    fn read(reader: impl Read) -> BackendResult<Self> {
+        let mut control = None;
+        let mut scripts = MaintainerScripts::empty();
+        let mut conffiles = Vec::new();
+
+        for (path, content) in archive::read_tar_files(archive::decompress(reader)?)? {
+            let content = String::from_utf8(content).map_err(|err| {
+                BackendError::Format(format!("Invalid UTF-8 in {}: {err}", path.display()).into())
+            })?;
+
+            match path.to_str().unwrap_or_default() {
+                "control" => control = Some(Control::parse(&content)?),
+                // Newer dpkg prefixes entries with flags such as `remove-on-upgrade`
+                "conffiles" => conffiles.extend(content.lines().filter_map(|line| {
+                    line.split_whitespace()
+                        .last()
+                        .map(|path| path.trim_start_matches('/').to_string())
+                })),
+                member => scripts.set(member, &content),
+            }
+        }
+
+        Ok(Self {
+            control: control.ok_or_else(|| missing("control"))?,
+            scripts,
+            conffiles,
+        })
+    }
+}
+
+// Function to tell which tarball an ar member is, whatever its compression suffix
+fn member_kind<R: Read>(entry: &ar::Entry<R>) -> Option<&'static str> {
+    // GNU ar terminates member names with a slash
+    let identifier = String::from_utf8_lossy(entry.header().identifier());
+    let identifier = identifier.trim_end_matches('/');
+
+    [CONTROL_MEMBER, DATA_MEMBER].into_iter().find(|member| {
+        identifier
+            .strip_prefix(member)
+            .is_some_and(|suffix| suffix.is_empty() || suffix.starts_with('.'))
+    })
+}
+
+fn missing(member: &str) -> BackendError {
+    BackendError::Format(format!("Package has no {member}").into())
+}
+
+fn to_stab_vec(values: &[String]) -> StabVec<StabString> {
+    values
+        .iter()
+        .map(|value| StabString::from(value.as_str()))
+        .collect()
+}
@@ -0,0 +1,93 @@
+// Imports
+use upac_lib::embed_script;
+
+use stabby::option::Option as StabOption;
+use stabby::string::String as StabString;
+
+// Package scripts built from the maintainer scripts of a .deb
+pub struct MaintainerScripts {
+    pub pre_install: StabOption<StabString>,
+    pub post_install: StabOption<StabString>,
+    pub pre_remove: StabOption<StabString>,
+    pub post_remove: StabOption<StabString>,
+}
+
+impl MaintainerScripts {
+    pub fn empty() -> Self {
+        Self {
+            pre_install: StabOption::None(),
+            post_install: StabOption::None(),
+            pre_remove: StabOption::None(),
+            post_remove: StabOption::None(),
+        }
+    }
+
+    // Function to map a control member onto its phase; other members are ignored
+    pub fn set(&mut self, member: &str, content: &str) {
+        // upac passes the new version as $1 and the old one as $2 (the reverse for removal scripts),
+        // dpkg expects an action first
+        let (phase, arguments) = match member {
+            "preinst" => (
+                &mut self.pre_install,
+                r#"if [ -n "$2" ]; then set -- upgrade "$2"; else set -- install; fi"#,
+            ),
+            "postinst" => (
+                &mut self.post_install,
+                r#"if [ -n "$2" ]; then set -- configure "$2"; else set -- configure; fi"#,
+            ),
+            "prerm" => (
+                &mut self.pre_remove,
+                r#"if [ -n "$2" ]; then set -- upgrade "$2"; else set -- remove; fi"#,
+            ),
+            "postrm" => (
+                &mut self.post_remove,
+                r#"if [ -n "$2" ]; then set -- upgrade "$2"; else set -- remove; fi"#,
+            ),
+            _ => return,
+        };
+
+        // Maintainer scripts run on their own, so any interpreter in their shebang works
+        *phase = StabOption::Some(embed_script(content, None, arguments));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::Command;
+
+    // Function to run a script the way upac does, with the versions as arguments
+    fn run(script: &StabOption<StabString>, versions: &[&str]) -> String {
+        let script = script.as_ref().expect("script is missing");
+        let output = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(script.as_str())
+            .arg("sh")
+            .args(versions)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn passes_dpkg_actions() {
+        let mut scripts = MaintainerScripts::empty();
+        for member in ["preinst", "postinst", "prerm", "postrm", "md5sums"] {
+            scripts.set(member, "#!/bin/sh\necho \"$@\"\n");
+        }
+
+        assert_eq!(run(&scripts.pre_install, &["1.0"]), "install\n");
+        assert_eq!(run(&scripts.pre_install, &["2.0", "1.0"]), "upgrade 1.0\n");
+        assert_eq!(run(&scripts.post_install, &["1.0"]), "configure\n");
+        assert_eq!(
+            run(&scripts.post_install, &["2.0", "1.0"]),
+            "configure 1.0\n"
+        );
+        assert_eq!(run(&scripts.pre_remove, &["1.0"]), "remove\n");
+        assert_eq!(run(&scripts.pre_remove, &["1.0", "2.0"]), "upgrade 2.0\n");
+        assert_eq!(run(&scripts.post_remove, &["1.0"]), "remove\n");
+        assert_eq!(run(&scripts.post_remove, &["1.0", "2.0"]), "upgrade 2.0\n");
+    }
+}
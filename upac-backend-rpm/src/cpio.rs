@@ -0,0 +1,266 @@
+// Imports
+use upac_lib::archive::{normalize_path, path_to_stab};
+
+use upac_types::{BackendError, BackendResult};
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+const NEWC_MAGIC: &[u8] = b"070701";
+const CRC_MAGIC: &[u8] = b"070702";
+const HEADER_LEN: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+// Longest member name accepted, to keep a corrupt archive from allocating gigabytes
+const MAX_NAME_LEN: usize = 64 * 1024;
+
+const MODE_TYPE: u32 = 0o170000;
+const MODE_DIR: u32 = 0o040000;
+const MODE_FILE: u32 = 0o100000;
+const MODE_SYMLINK: u32 = 0o120000;
+
+// Fields of a newc header upac uses
+struct CpioHeader {
+    inode: u32,
+    mode: u32,
+    links: u32,
+    size: u64,
+    name_len: usize,
+}
+
+impl CpioHeader {
+    fn parse(header: &[u8; HEADER_LEN]) -> BackendResult<Self> {
+        if !header.starts_with(NEWC_MAGIC) && !header.starts_with(CRC_MAGIC) {
+            return Err(BackendError::Unsupported(
+                "Only newc cpio payloads are supported".into(),
+            ));
+        }
+
+        // Thirteen 8 digit hex fields follow the magic
+        let field = |index: usize| {
+            let start = NEWC_MAGIC.len() + index * 8;
+            std::str::from_utf8(&header[start..start + 8])
+                .ok()
+                .and_then(|field| u32::from_str_radix(field, 16).ok())
+                .ok_or_else(|| BackendError::Format("Invalid cpio header".into()))
+        };
+
+        Ok(Self {
+            inode: field(0)?,
+            mode: field(1)?,
+            links: field(4)?,
+            size: u64::from(field(6)?),
+            name_len: field(11)? as usize,
+        })
+    }
+}
+
+// Function to unpack a newc cpio stream into temp_dir; returns the unpacked paths in archive order
+pub fn unpack_cpio(mut reader: impl Read, temp_dir: &Path) -> BackendResult<StabVec<StabString>> {
+    fs::create_dir_all(temp_dir)?;
+
+    let mut file_list = StabVec::new();
+    // Hard links of a file carry no data, it comes with the last one of the same inode
+    let mut pending_links: HashMap<u32, Vec<PathBuf>> = HashMap::new();
+    let mut offset = 0usize;
+
+    loop {
+        let mut raw_header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut raw_header)?;
+        let header = CpioHeader::parse(&raw_header)?;
+
+        if header.name_len == 0 || header.name_len > MAX_NAME_LEN {
+            return Err(BackendError::Format("Invalid cpio member name".into()));
+        }
+
+        let mut name = vec![0u8; header.name_len];
+        reader.read_exact(&mut name)?;
+        offset += HEADER_LEN + header.name_len;
+        skip(&mut reader, padding(offset))?;
+        offset += padding(offset);
+
+        let name = String::from_utf8_lossy(&name[..header.name_len - 1]).into_owned();
+        if name == TRAILER {
+            break;
+        }
+
+        let mut data = (&mut reader).take(header.size);
+
+        // The payload may list the root itself as `.`
+        let relative_path = normalize_path(Path::new(&name))?;
+        if !relative_path.as_os_str().is_empty() {
+            unpack_member(
+                &header,
+                &relative_path,
+                &mut data,
+                temp_dir,
+                &mut pending_links,
+            )?;
+            file_list.push(path_to_stab(&relative_path)?);
+        }
+
+        // Whatever the member type did not read still has to be skipped
+        io::copy(&mut data, &mut io::sink())?;
+        offset += header.size as usize;
+        skip(&mut reader, padding(offset))?;
+        offset += padding(offset);
+    }
+
+    // Links whose data never came are empty files
+    for path in pending_links.into_values().flatten() {
+        fs::File::create(path)?;
+    }
+
+    Ok(file_list)
+}
+
+fn unpack_member(
+    header: &CpioHeader,
+    relative_path: &Path,
+    data: &mut impl Read,
+    temp_dir: &Path,
+    pending_links: &mut HashMap<u32, Vec<PathBuf>>,
+) -> BackendResult<()> {
+    check_parents(temp_dir, relative_path)?;
+
+    let path = temp_dir.join(relative_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match header.mode & MODE_TYPE {
+        MODE_DIR => {
+            fs::create_dir_all(&path)?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(header.mode & 0o7777))?;
+        }
+        MODE_SYMLINK => {
+            let mut target = String::new();
+            data.read_to_string(&mut target)?;
+            remove_existing(&path)?;
+            symlink(target, &path)?;
+        }
+        MODE_FILE if header.size == 0 && header.links > 1 => {
+            pending_links.entry(header.inode).or_default().push(path);
+        }
+        MODE_FILE => {
+            remove_existing(&path)?;
+            io::copy(data, &mut fs::File::create(&path)?)?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(header.mode & 0o7777))?;
+
+            for link in pending_links.remove(&header.inode).unwrap_or_default() {
+                remove_existing(&link)?;
+                fs::hard_link(&path, &link)?;
+            }
+        }
+        _ => {
+            return Err(BackendError::Unsupported(
+                format!("Special file in payload: {}", relative_path.display()).into(),
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+fn padding(offset: usize) -> usize {
+    (4 - offset % 4) % 4
+}
+
+fn skip(reader: &mut impl Read, len: usize) -> BackendResult<()> {
+    let mut buffer = [0u8; 4];
+    reader.read_exact(&mut buffer[..len])?;
+    Ok(())
+}
+
+// Function to refuse members that would be written through a symlink unpacked earlier
+fn check_parents(temp_dir: &Path, relative_path: &Path) -> BackendResult<()> {
+    for parent in relative_path.ancestors().skip(1) {
+        let is_symlink = fs::symlink_metadata(temp_dir.join(parent))
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+
+        if is_symlink {
+            return Err(BackendError::Format(
+                format!("Unsafe path in archive: {}", relative_path.display()).into(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_existing(path: &Path) -> BackendResult<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => Ok(fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::cpio;
+
+    #[test]
+    fn unpacks_newc_members() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive = cpio(&[
+            (".", 0o040755, 1, 2, b""),
+            ("./usr/bin", 0o040755, 2, 2, b""),
+            ("./usr/bin/tool", 0o100755, 3, 1, b"tool"),
+            ("./usr/bin/alias", 0o120777, 4, 1, b"tool"),
+            ("./usr/lib/a", 0o100644, 5, 2, b""),
+            ("./usr/lib/b", 0o100644, 5, 2, b"shared"),
+        ]);
+
+        let file_list = unpack_cpio(archive.as_slice(), temp_dir.path()).unwrap();
+        let file_list: Vec<&str> = file_list.iter().map(|file| file.as_str()).collect();
+        assert_eq!(
+            file_list,
+            [
+                "usr/bin",
+                "usr/bin/tool",
+                "usr/bin/alias",
+                "usr/lib/a",
+                "usr/lib/b"
+            ]
+        );
+
+        let tool = temp_dir.path().join("usr/bin/tool");
+        assert_eq!(fs::read_to_string(&tool).unwrap(), "tool");
+        assert_eq!(
+            fs::metadata(&tool).unwrap().permissions().mode() & 0o7777,
+            0o755
+        );
+        assert_eq!(
+            fs::read_link(temp_dir.path().join("usr/bin/alias")).unwrap(),
+            Path::new("tool")
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("usr/lib/a")).unwrap(),
+            "shared"
+        );
+    }
+
+    #[test]
+    fn refuses_paths_through_symlinks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let target = outside.path().to_string_lossy().into_owned();
+        let archive = cpio(&[
+            ("etc", 0o120777, 1, 1, target.as_bytes()),
+            ("etc/passwd", 0o100644, 2, 1, b"root"),
+        ]);
+
+        assert!(unpack_cpio(archive.as_slice(), temp_dir.path()).is_err());
+        assert!(!outside.path().join("passwd").exists());
+
+        let archive = cpio(&[("../escape", 0o100644, 1, 1, b"")]);
+        assert!(unpack_cpio(archive.as_slice(), temp_dir.path()).is_err());
+    }
+}
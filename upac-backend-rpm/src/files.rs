@@ -0,0 +1,186 @@
+// Imports
+use crate::header::{Header, TAG_BASENAMES, TAG_DIRINDEXES, TAG_DIRNAMES, TAG_OLDFILENAMES};
+use crate::header::{TAG_FILEFLAGS, TAG_FILEGROUPNAME, TAG_FILEMODES, TAG_FILEUSERNAME};
+
+use upac_types::{BackendError, BackendResult};
+
+use nix::errno::Errno;
+use nix::unistd::{geteuid, Group, User};
+
+use std::fs;
+use std::os::unix::fs::{lchown, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+const RPMFILE_CONFIG: u32 = 1 << 0;
+// Ghost files are owned by the package but not shipped in the payload
+const RPMFILE_GHOST: u32 = 1 << 6;
+
+// Attributes of one file as the header records them
+#[derive(Debug)]
+pub struct FileInfo {
+    // Relative to the root
+    pub path: PathBuf,
+    pub mode: u32,
+    pub user: String,
+    pub group: String,
+    pub flags: u32,
+}
+
+impl FileInfo {
+    // Function to read the file table, in the compressed (dirnames) or the old layout
+    pub fn from_header(header: &Header) -> BackendResult<Vec<Self>> {
+        let paths: Vec<String> = match header.strings(TAG_OLDFILENAMES)? {
+            paths if !paths.is_empty() => paths,
+            _ => {
+                let dir_names = header.strings(TAG_DIRNAMES)?;
+                let dir_indexes = header.integers(TAG_DIRINDEXES)?;
+
+                header
+                    .strings(TAG_BASENAMES)?
+                    .into_iter()
+                    .zip(dir_indexes)
+                    .map(|(base_name, index)| {
+                        dir_names
+                            .get(index as usize)
+                            .map(|dir_name| format!("{dir_name}{base_name}"))
+                            .ok_or_else(|| BackendError::Format("Invalid RPM file table".into()))
+                    })
+                    .collect::<BackendResult<_>>()?
+            }
+        };
+
+        let modes = header.integers(TAG_FILEMODES)?;
+        let flags = header.integers(TAG_FILEFLAGS)?;
+        let users = header.strings(TAG_FILEUSERNAME)?;
+        let groups = header.strings(TAG_FILEGROUPNAME)?;
+
+        Ok(paths
+            .into_iter()
+            .enumerate()
+            .map(|(index, path)| Self {
+                path: PathBuf::from(path.trim_start_matches('/')),
+                mode: modes.get(index).copied().unwrap_or_default(),
+                user: users.get(index).cloned().unwrap_or_default(),
+                group: groups.get(index).cloned().unwrap_or_default(),
+                flags: flags.get(index).copied().unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    pub fn is_config(&self) -> bool {
+        self.flags & RPMFILE_CONFIG != 0 && self.flags & RPMFILE_GHOST == 0
+    }
+
+    // Function to give an unpacked file the mode and owner from the header; like rpm,
+    // owners unknown on this system fall back to root. Only root can hand files to other owners,
+    // so an unprivileged extract keeps its own and a refused chown is not an error
+    pub fn apply(&self, temp_dir: &Path) -> BackendResult<()> {
+        let path = temp_dir.join(&self.path);
+
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            return Ok(());
+        };
+
+        if !metadata.file_type().is_symlink() && self.mode != 0 {
+            fs::set_permissions(&path, fs::Permissions::from_mode(self.mode & 0o7777))?;
+        }
+
+        if !geteuid().is_root() {
+            return Ok(());
+        }
+
+        let uid = User::from_name(&self.user)
+            .ok()
+            .flatten()
+            .map_or(0, |user| user.uid.as_raw());
+        let gid = Group::from_name(&self.group)
+            .ok()
+            .flatten()
+            .map_or(0, |group| group.gid.as_raw());
+        match lchown(&path, Some(uid), Some(gid)) {
+            Err(err) if err.raw_os_error() == Some(Errno::EPERM as i32) => Ok(()),
+            result => result.map_err(BackendError::from),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{header, Value};
+
+    use std::os::unix::fs::MetadataExt;
+
+    fn file_table() -> Vec<FileInfo> {
+        let header = header(&[
+            (TAG_FILEMODES, Value::Int16(&[0o100755, 0o100600, 0o100644])),
+            (
+                TAG_FILEFLAGS,
+                Value::Int32(&[0, 1 << 0, (1 << 0) | (1 << 6)]),
+            ),
+            (
+                TAG_FILEUSERNAME,
+                Value::Strings(&["root", "upac-nobody", "root"]),
+            ),
+            (
+                TAG_FILEGROUPNAME,
+                Value::Strings(&["root", "upac-nobody", "root"]),
+            ),
+            (TAG_DIRINDEXES, Value::Int32(&[0, 1, 1])),
+            (
+                TAG_BASENAMES,
+                Value::Strings(&["tool", "tool.conf", "tool.state"]),
+            ),
+            (TAG_DIRNAMES, Value::Strings(&["/usr/bin/", "/etc/"])),
+        ]);
+        FileInfo::from_header(&Header::read(&mut header.as_slice()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_compressed_file_table() {
+        let files = file_table();
+
+        let paths: Vec<&Path> = files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            ["usr/bin/tool", "etc/tool.conf", "etc/tool.state"].map(Path::new)
+        );
+        assert_eq!(files[1].mode, 0o100600);
+        assert_eq!(files[1].user, "upac-nobody");
+
+        // Ghost config files are not in the payload, so there is nothing to keep
+        let config: Vec<bool> = files.iter().map(FileInfo::is_config).collect();
+        assert_eq!(config, [false, true, false]);
+    }
+
+    #[test]
+    fn applies_modes_and_owners() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("etc")).unwrap();
+        fs::write(temp_dir.path().join("etc/tool.conf"), "").unwrap();
+        if geteuid().is_root() {
+            lchown(
+                temp_dir.path().join("etc/tool.conf"),
+                Some(1000),
+                Some(1000),
+            )
+            .unwrap();
+        }
+        let files = file_table();
+
+        // Files missing from the payload are skipped
+        for file in &files {
+            file.apply(temp_dir.path()).unwrap();
+        }
+
+        let metadata = fs::metadata(temp_dir.path().join("etc/tool.conf")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o600);
+
+        // Root hands files of unknown owners to root, anyone else keeps them
+        if geteuid().is_root() {
+            assert_eq!((metadata.uid(), metadata.gid()), (0, 0));
+        } else {
+            assert_eq!(metadata.uid(), geteuid().as_raw());
+        }
+    }
+}
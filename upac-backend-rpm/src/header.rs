@@ -0,0 +1,259 @@
+// Imports
+use upac_types::{BackendError, BackendResult};
+
+use std::io::Read;
+
+pub const LEAD_MAGIC: &[u8] = &[0xed, 0xab, 0xee, 0xdb];
+const LEAD_LEN: usize = 96;
+const HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8, 0x01];
+const INDEX_ENTRY_LEN: usize = 16;
+
+// Upper bounds that keep a corrupt header from allocating gigabytes
+const MAX_INDEX_ENTRIES: usize = 0x10000;
+const MAX_STORE_LEN: usize = 256 * 1024 * 1024;
+
+// Header tags read by upac
+pub const TAG_NAME: u32 = 1000;
+pub const TAG_VERSION: u32 = 1001;
+pub const TAG_RELEASE: u32 = 1002;
+pub const TAG_EPOCH: u32 = 1003;
+pub const TAG_PREIN: u32 = 1023;
+pub const TAG_POSTIN: u32 = 1024;
+pub const TAG_PREUN: u32 = 1025;
+pub const TAG_POSTUN: u32 = 1026;
+pub const TAG_OLDFILENAMES: u32 = 1027;
+pub const TAG_FILEMODES: u32 = 1030;
+pub const TAG_FILEFLAGS: u32 = 1037;
+pub const TAG_FILEUSERNAME: u32 = 1039;
+pub const TAG_FILEGROUPNAME: u32 = 1040;
+pub const TAG_PROVIDENAME: u32 = 1047;
+pub const TAG_REQUIREFLAGS: u32 = 1048;
+pub const TAG_REQUIRENAME: u32 = 1049;
+pub const TAG_REQUIREVERSION: u32 = 1050;
+pub const TAG_CONFLICTFLAGS: u32 = 1053;
+pub const TAG_CONFLICTNAME: u32 = 1054;
+pub const TAG_CONFLICTVERSION: u32 = 1055;
+pub const TAG_PREINPROG: u32 = 1085;
+pub const TAG_POSTINPROG: u32 = 1086;
+pub const TAG_PREUNPROG: u32 = 1087;
+pub const TAG_POSTUNPROG: u32 = 1088;
+pub const TAG_PROVIDEFLAGS: u32 = 1112;
+pub const TAG_PROVIDEVERSION: u32 = 1113;
+pub const TAG_DIRINDEXES: u32 = 1116;
+pub const TAG_BASENAMES: u32 = 1117;
+pub const TAG_DIRNAMES: u32 = 1118;
+pub const TAG_PAYLOADFORMAT: u32 = 1124;
+pub const TAG_PAYLOADCOMPRESSOR: u32 = 1125;
+
+// Data types of index entries
+const TYPE_INT16: u32 = 3;
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    tag: u32,
+    data_type: u32,
+    offset: usize,
+    count: usize,
+}
+
+// One header structure: an index of tagged entries over a data store
+#[derive(Debug)]
+pub struct Header {
+    entries: Vec<IndexEntry>,
+    store: Vec<u8>,
+}
+
+impl Header {
+    // Function to read the lead and the signature header, leaving the stream at the main header
+    pub fn skip_lead_and_signature(reader: &mut impl Read) -> BackendResult<()> {
+        let mut lead = [0u8; LEAD_LEN];
+        reader.read_exact(&mut lead)?;
+
+        if !lead.starts_with(LEAD_MAGIC) {
+            return Err(BackendError::Format("Not an RPM package".into()));
+        }
+
+        let signature = Self::read(reader)?;
+
+        // The signature header is padded to a multiple of 8 bytes, the main header is not
+        let signature_len = 16 + signature.entries.len() * INDEX_ENTRY_LEN + signature.store.len();
+        let mut padding = vec![0u8; (8 - signature_len % 8) % 8];
+        reader.read_exact(&mut padding)?;
+
+        Ok(())
+    }
+
+    // Function to read one header structure from the stream
+    pub fn read(reader: &mut impl Read) -> BackendResult<Self> {
+        let mut intro = [0u8; 16];
+        reader.read_exact(&mut intro)?;
+
+        if !intro.starts_with(HEADER_MAGIC) {
+            return Err(BackendError::Format("Invalid RPM header magic".into()));
+        }
+
+        let index_len = be_u32(&intro[8..]) as usize;
+        let store_len = be_u32(&intro[12..]) as usize;
+
+        if index_len > MAX_INDEX_ENTRIES || store_len > MAX_STORE_LEN {
+            return Err(BackendError::Format("RPM header is too large".into()));
+        }
+
+        let mut index = vec![0u8; index_len * INDEX_ENTRY_LEN];
+        reader.read_exact(&mut index)?;
+
+        let mut store = vec![0u8; store_len];
+        reader.read_exact(&mut store)?;
+
+        let entries = index
+            .chunks_exact(INDEX_ENTRY_LEN)
+            .map(|entry| IndexEntry {
+                tag: be_u32(entry),
+                data_type: be_u32(&entry[4..]),
+                offset: be_u32(&entry[8..]) as usize,
+                count: be_u32(&entry[12..]) as usize,
+            })
+            .collect();
+
+        Ok(Self { entries, store })
+    }
+
+    fn entry(&self, tag: u32) -> Option<IndexEntry> {
+        self.entries.iter().find(|entry| entry.tag == tag).copied()
+    }
+
+    fn store_from(&self, entry: IndexEntry) -> BackendResult<&[u8]> {
+        self.store
+            .get(entry.offset..)
+            .ok_or_else(|| invalid(entry.tag))
+    }
+
+    // Function to read a string tag; for i18n strings the first (untranslated) one is used
+    pub fn string(&self, tag: u32) -> BackendResult<Option<String>> {
+        let Some(entry) = self.entry(tag) else {
+            return Ok(None);
+        };
+
+        match entry.data_type {
+            TYPE_STRING | TYPE_STRING_ARRAY | TYPE_I18NSTRING => {
+                Ok(self.strings_in(entry)?.into_iter().next())
+            }
+            _ => Err(invalid(tag)),
+        }
+    }
+
+    // Function to read a string array tag; a missing tag is an empty array
+    pub fn strings(&self, tag: u32) -> BackendResult<Vec<String>> {
+        match self.entry(tag) {
+            None => Ok(Vec::new()),
+            Some(entry) if matches!(entry.data_type, TYPE_STRING | TYPE_STRING_ARRAY) => {
+                self.strings_in(entry)
+            }
+            Some(_) => Err(invalid(tag)),
+        }
+    }
+
+    fn strings_in(&self, entry: IndexEntry) -> BackendResult<Vec<String>> {
+        let count = if entry.data_type == TYPE_STRING {
+            1
+        } else {
+            entry.count
+        };
+        let mut data = self.store_from(entry)?;
+        let mut strings = Vec::with_capacity(count.min(MAX_INDEX_ENTRIES));
+
+        for _ in 0..count {
+            let end = data
+                .iter()
+                .position(|byte| *byte == 0)
+                .ok_or_else(|| invalid(entry.tag))?;
+            strings.push(String::from_utf8_lossy(&data[..end]).into_owned());
+            data = &data[end + 1..];
+        }
+
+        Ok(strings)
+    }
+
+    // Function to read an integer array tag (int16 or int32) as u32 values
+    pub fn integers(&self, tag: u32) -> BackendResult<Vec<u32>> {
+        let Some(entry) = self.entry(tag) else {
+            return Ok(Vec::new());
+        };
+
+        let width = match entry.data_type {
+            TYPE_INT16 => 2,
+            TYPE_INT32 => 4,
+            _ => return Err(invalid(tag)),
+        };
+
+        let data = self
+            .store_from(entry)?
+            .get(..entry.count * width)
+            .ok_or_else(|| invalid(tag))?;
+
+        Ok(data
+            .chunks_exact(width)
+            .map(|value| match width {
+                2 => u32::from(u16::from_be_bytes([value[0], value[1]])),
+                _ => be_u32(value),
+            })
+            .collect())
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn invalid(tag: u32) -> BackendError {
+    BackendError::Format(format!("Invalid RPM header tag {tag}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{header, lead, Value};
+
+    #[test]
+    fn reads_tags_after_lead_and_signature() {
+        let mut package = lead();
+        // Five bytes of store leave the signature header three bytes short of alignment
+        package.extend(header(&[(1000, Value::String("sign"))]));
+        package.extend([0; 3]);
+        package.extend(header(&[
+            (TAG_NAME, Value::String("tool")),
+            (TAG_EPOCH, Value::Int32(&[2])),
+            (TAG_FILEMODES, Value::Int16(&[0o100755, 0o040755])),
+            (TAG_BASENAMES, Value::Strings(&["tool", "share"])),
+        ]));
+
+        let mut reader = package.as_slice();
+        Header::skip_lead_and_signature(&mut reader).unwrap();
+        let main = Header::read(&mut reader).unwrap();
+
+        assert!(reader.is_empty());
+        assert_eq!(main.string(TAG_NAME).unwrap().as_deref(), Some("tool"));
+        assert_eq!(main.integers(TAG_EPOCH).unwrap(), [2]);
+        assert_eq!(main.integers(TAG_FILEMODES).unwrap(), [0o100755, 0o040755]);
+        assert_eq!(main.strings(TAG_BASENAMES).unwrap(), ["tool", "share"]);
+        assert_eq!(main.string(TAG_BASENAMES).unwrap().as_deref(), Some("tool"));
+
+        assert_eq!(main.string(TAG_VERSION).unwrap(), None);
+        assert!(main.strings(TAG_DIRNAMES).unwrap().is_empty());
+        assert!(main.integers(TAG_NAME).is_err());
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_rpms() {
+        let mut reader = [0u8; 128].as_slice();
+        assert!(Header::skip_lead_and_signature(&mut reader).is_err());
+
+        let mut package = lead();
+        package.extend([0; 16]);
+        assert!(Header::skip_lead_and_signature(&mut package.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+// Imports
+use upac_lib::archive;
+use upac_lib::{Backend, VersionScheme};
+
+use upac_types::{BackendError, BackendResult, ExtractedPackage, PackageMetadata};
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Mods
+pub mod cpio;
+pub mod files;
+pub mod header;
+pub mod scripts;
+
+#[cfg(test)]
+mod testing;
+
+pub use files::FileInfo;
+pub use header::Header;
+pub use scripts::Scriptlets;
+
+use header::{LEAD_MAGIC, TAG_CONFLICTFLAGS, TAG_CONFLICTNAME, TAG_CONFLICTVERSION, TAG_EPOCH};
+use header::{TAG_NAME, TAG_PAYLOADCOMPRESSOR, TAG_PAYLOADFORMAT, TAG_PROVIDEFLAGS};
+use header::{TAG_PROVIDENAME, TAG_PROVIDEVERSION, TAG_RELEASE, TAG_REQUIREFLAGS};
+use header::{TAG_REQUIRENAME, TAG_REQUIREVERSION, TAG_VERSION};
+
+// Dependency sense flags
+const RPMSENSE_LESS: u32 = 1 << 1;
+const RPMSENSE_GREATER: u32 = 1 << 2;
+const RPMSENSE_EQUAL: u32 = 1 << 3;
+// Requirements on features of rpm itself, like `rpmlib(PayloadIsZstd)`
+const RPMSENSE_RPMLIB: u32 = 1 << 24;
+
+// Backend for RPM packages, read without rpm on the host
+pub struct RpmBackend;
+
+// Package description from the main header
+struct RpmInfo {
+    name: String,
+    version: String,
+    requires: Vec<String>,
+    provides: Vec<String>,
+    conflicts: Vec<String>,
+}
+
+impl RpmInfo {
+    fn from_header(header: &Header) -> BackendResult<Self> {
+        let field = |tag| {
+            header
+                .string(tag)?
+                .ok_or_else(|| BackendError::Format(format!("RPM header lacks tag {tag}").into()))
+        };
+
+        let name = field(TAG_NAME)?;
+        let version = format!("{}-{}", field(TAG_VERSION)?, field(TAG_RELEASE)?);
+        let version = match header.integers(TAG_EPOCH)?.first() {
+            Some(epoch) => format!("{epoch}:{version}"),
+            None => version,
+        };
+
+        // File requirements (`/bin/sh`) name paths, not packages upac could look up
+        let requires = relations(
+            header,
+            TAG_REQUIRENAME,
+            TAG_REQUIREFLAGS,
+            TAG_REQUIREVERSION,
+        )?
+        .into_iter()
+        .filter(|requirement| !requirement.starts_with('/'))
+        .collect();
+
+        Ok(Self {
+            name,
+            version,
+            requires,
+            provides: relations(
+                header,
+                TAG_PROVIDENAME,
+                TAG_PROVIDEFLAGS,
+                TAG_PROVIDEVERSION,
+            )?,
+            conflicts: relations(
+                header,
+                TAG_CONFLICTNAME,
+                TAG_CONFLICTFLAGS,
+                TAG_CONFLICTVERSION,
+            )?,
+        })
+    }
+}
+
+impl RpmBackend {
+    // Function to read up to the end of the main header, leaving the stream at the payload
+    fn read_header(path: &Path) -> BackendResult<(Header, BufReader<File>)> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Header::skip_lead_and_signature(&mut reader)?;
+        let header = Header::read(&mut reader)?;
+
+        Ok((header, reader))
+    }
+}
+
+impl Backend for RpmBackend {
+    fn name(&self) -> &str {
+        "rpm"
+    }
+
+    fn version_scheme(&self) -> VersionScheme {
+        VersionScheme::Rpm
+    }
+
+    fn detect(&self, path: &Path, header: &[u8]) -> bool {
+        path.extension().is_some_and(|extension| extension == "rpm")
+            && header.starts_with(LEAD_MAGIC)
+    }
+
+    fn read_metadata(&self, path: &Path) -> BackendResult<PackageMetadata> {
+        let (header, _) = Self::read_header(path)?;
+        let info = RpmInfo::from_header(&header)?;
+
+        Ok(PackageMetadata {
+            name: info.name.as_str().into(),
+            version: info.version.as_str().into(),
+            format: self.name().into(),
+            dependencies: to_stab_vec(&info.requires),
+            provides: to_stab_vec(&info.provides),
+            conflicts: to_stab_vec(&info.conflicts),
+        })
+    }
+
+    fn extract(&self, path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+        let (header, reader) = Self::read_header(path)?;
+        let info = RpmInfo::from_header(&header)?;
+        let scripts = Scriptlets::from_header(&header)?;
+        let files = FileInfo::from_header(&header)?;
+
+        check_payload(&header)?;
+        let file_list = cpio::unpack_cpio(archive::decompress(reader)?, temp_dir)?;
+
+        for file in &files {
+            file.apply(temp_dir)?;
+        }
+
+        let config_files: Vec<String> = files
+            .iter()
+            .filter(|file| file.is_config())
+            .map(|file| file.path.to_string_lossy().into_owned())
+            .collect();
+
+        Ok(ExtractedPackage {
+            name: info.name.as_str().into(),
+            version: info.version.as_str().into(),
+            format: self.name().into(),
+            file_list,
+            dependencies: to_stab_vec(&info.requires),
+            provides: to_stab_vec(&info.provides),
+            conflicts: to_stab_vec(&info.conflicts),
+            config_files: to_stab_vec(&config_files),
+            pre_install: scripts.pre_install,
+            post_install: scripts.post_install,
+            pre_remove: scripts.pre_remove,
+            post_remove: scripts.post_remove,
+        })
+    }
+}
+
+// Function to refuse payloads archive::decompress cannot read before anything is unpacked
+fn check_payload(header: &Header) -> BackendResult<()> {
+    if let Some(format) = header.string(TAG_PAYLOADFORMAT)? {
+        if format != "cpio" {
+            return Err(BackendError::Unsupported(
+                format!("RPM payload format {format}").into(),
+            ));
+        }
+    }
+
+    // Packages older than the tag are gzip compressed
+    let compressor = header
+        .string(TAG_PAYLOADCOMPRESSOR)?
+        .unwrap_or_else(|| "gzip".into());
+
+    if !matches!(compressor.as_str(), "gzip" | "xz" | "zstd") {
+        return Err(BackendError::Unsupported(
+            format!("RPM payload compression {compressor}").into(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Function to zip the name, flags and version arrays of a dependency tag into upac relations
+fn relations(
+    header: &Header,
+    name_tag: u32,
+    flags_tag: u32,
+    version_tag: u32,
+) -> BackendResult<Vec<String>> {
+    let flags = header.integers(flags_tag)?;
+    let versions = header.strings(version_tag)?;
+
+    Ok(header
+        .strings(name_tag)?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let flags = flags.get(index).copied().unwrap_or_default();
+            let version = versions.get(index).map(String::as_str).unwrap_or_default();
+
+            if flags & RPMSENSE_RPMLIB != 0 || name.starts_with("rpmlib(") {
+                return None;
+            }
+
+            let operator = match flags & (RPMSENSE_LESS | RPMSENSE_GREATER | RPMSENSE_EQUAL) {
+                sense if sense == RPMSENSE_LESS | RPMSENSE_EQUAL => "<=",
+                sense if sense == RPMSENSE_GREATER | RPMSENSE_EQUAL => ">=",
+                RPMSENSE_LESS => "<",
+                RPMSENSE_GREATER => ">",
+                RPMSENSE_EQUAL => "=",
+                _ => "",
+            };
+
+            Some(match (operator, version) {
+                ("", _) | (_, "") => name,
+                (operator, version) => format!("{name}{operator}{version}"),
+            })
+        })
+        .collect())
+}
+
+fn to_stab_vec(values: &[String]) -> StabVec<StabString> {
+    values
+        .iter()
+        .map(|value| StabString::from(value.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{TAG_BASENAMES, TAG_DIRINDEXES, TAG_DIRNAMES, TAG_FILEFLAGS};
+    use crate::header::{TAG_FILEMODES, TAG_POSTIN, TAG_PREUNPROG};
+    use crate::testing::{cpio, header, lead, Value};
+
+    use flate2::write::GzEncoder;
+
+    use std::fs;
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+
+    const RPMSENSE_PREREQ: u32 = 1 << 6;
+
+    // Function to write a small package: one program, one config file and a scriptlet of each kind
+    fn write_package(path: &Path) {
+        let mut package = lead();
+        package.extend(header(&[]));
+        package.extend(header(&[
+            (TAG_NAME, Value::String("tool")),
+            (TAG_VERSION, Value::String("1.4")),
+            (TAG_RELEASE, Value::String("2.fc40")),
+            (TAG_EPOCH, Value::Int32(&[1])),
+            (
+                TAG_REQUIRENAME,
+                Value::Strings(&[
+                    "libc.so.6",
+                    "/bin/sh",
+                    "rpmlib(PayloadIsZstd)",
+                    "libfoo",
+                    "bar",
+                ]),
+            ),
+            (
+                TAG_REQUIREFLAGS,
+                Value::Int32(&[
+                    0,
+                    RPMSENSE_PREREQ,
+                    RPMSENSE_RPMLIB | RPMSENSE_LESS | RPMSENSE_EQUAL,
+                    RPMSENSE_GREATER | RPMSENSE_EQUAL,
+                    RPMSENSE_LESS,
+                ]),
+            ),
+            (
+                TAG_REQUIREVERSION,
+                Value::Strings(&["", "", "5.4.18-1", "2.0", "3"]),
+            ),
+            (TAG_PROVIDENAME, Value::Strings(&["tool"])),
+            (TAG_PROVIDEFLAGS, Value::Int32(&[RPMSENSE_EQUAL])),
+            (TAG_PROVIDEVERSION, Value::Strings(&["1:1.4-2.fc40"])),
+            (TAG_CONFLICTNAME, Value::Strings(&["tool-legacy"])),
+            (TAG_CONFLICTFLAGS, Value::Int32(&[RPMSENSE_GREATER])),
+            (TAG_CONFLICTVERSION, Value::Strings(&["0.9"])),
+            (TAG_POSTIN, Value::String("echo \"post $1\"")),
+            (TAG_PREUNPROG, Value::Strings(&["/bin/echo"])),
+            (TAG_FILEMODES, Value::Int16(&[0o100755, 0o100644])),
+            (TAG_FILEFLAGS, Value::Int32(&[0, 1])),
+            (TAG_DIRINDEXES, Value::Int32(&[0, 1])),
+            (TAG_BASENAMES, Value::Strings(&["tool", "tool.conf"])),
+            (TAG_DIRNAMES, Value::Strings(&["/usr/bin/", "/etc/"])),
+            (TAG_PAYLOADFORMAT, Value::String("cpio")),
+            (TAG_PAYLOADCOMPRESSOR, Value::String("gzip")),
+        ]));
+
+        let mut payload = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        payload
+            .write_all(&cpio(&[
+                ("./usr/bin/tool", 0o100700, 1, 1, b"tool"),
+                ("./etc/tool.conf", 0o100644, 2, 1, b"key = value"),
+            ]))
+            .unwrap();
+        package.extend(payload.finish().unwrap());
+
+        fs::write(path, package).unwrap();
+    }
+
+    fn strings(values: &StabVec<StabString>) -> Vec<&str> {
+        values.iter().map(|value| value.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_packages_without_rpm() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("tool-1.4-2.fc40.x86_64.rpm");
+        write_package(&path);
+
+        assert!(RpmBackend.detect(&path, &lead()));
+        assert!(!RpmBackend.detect(&temp_dir.path().join("tool.deb"), &lead()));
+
+        let metadata = RpmBackend.read_metadata(&path).unwrap();
+        assert_eq!(metadata.version.as_str(), "1:1.4-2.fc40");
+        assert_eq!(
+            strings(&metadata.dependencies),
+            ["libc.so.6", "libfoo>=2.0", "bar<3"]
+        );
+        assert_eq!(strings(&metadata.provides), ["tool=1:1.4-2.fc40"]);
+        assert_eq!(strings(&metadata.conflicts), ["tool-legacy>0.9"]);
+
+        let root = temp_dir.path().join("root");
+        let package = RpmBackend.extract(&path, &root).unwrap();
+        assert_eq!(
+            strings(&package.file_list),
+            ["usr/bin/tool", "etc/tool.conf"]
+        );
+        assert_eq!(strings(&package.config_files), ["etc/tool.conf"]);
+        assert!(package.pre_install.is_none());
+        assert!(package.post_install.is_some());
+        assert!(package.pre_remove.is_some());
+
+        // The header mode wins over the one in the payload
+        let metadata = fs::metadata(root.join("usr/bin/tool")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o755);
+    }
+}
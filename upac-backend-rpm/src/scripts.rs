@@ -0,0 +1,119 @@
+// Imports
+use crate::header::{Header, TAG_POSTIN, TAG_POSTINPROG, TAG_POSTUN, TAG_POSTUNPROG};
+use crate::header::{TAG_PREIN, TAG_PREINPROG, TAG_PREUN, TAG_PREUNPROG};
+
+use upac_lib::embed_script;
+
+use upac_types::{BackendError, BackendResult};
+
+use stabby::option::Option as StabOption;
+use stabby::string::String as StabString;
+
+const DEFAULT_INTERPRETER: &str = "/bin/sh";
+// Interpreter rpm runs inside its own process; there is nothing to run it with here
+const LUA_INTERPRETER: &str = "<lua>";
+
+// upac passes the new version as $1 and the old one as $2 (the reverse for removal scripts),
+// rpm scriptlets get the number of instances left after the transaction
+const INSTALL_ARGUMENTS: &str = r#"if [ -n "$2" ]; then set -- 2; else set -- 1; fi"#;
+const REMOVE_ARGUMENTS: &str = r#"if [ -n "$2" ]; then set -- 1; else set -- 0; fi"#;
+
+// Package scripts built from the scriptlets in the header
+pub struct Scriptlets {
+    pub pre_install: StabOption<StabString>,
+    pub post_install: StabOption<StabString>,
+    pub pre_remove: StabOption<StabString>,
+    pub post_remove: StabOption<StabString>,
+}
+
+impl Scriptlets {
+    pub fn from_header(header: &Header) -> BackendResult<Self> {
+        Ok(Self {
+            pre_install: scriptlet(header, TAG_PREIN, TAG_PREINPROG, INSTALL_ARGUMENTS)?,
+            post_install: scriptlet(header, TAG_POSTIN, TAG_POSTINPROG, INSTALL_ARGUMENTS)?,
+            pre_remove: scriptlet(header, TAG_PREUN, TAG_PREUNPROG, REMOVE_ARGUMENTS)?,
+            post_remove: scriptlet(header, TAG_POSTUN, TAG_POSTUNPROG, REMOVE_ARGUMENTS)?,
+        })
+    }
+}
+
+// Function to build one phase; a scriptlet may be a body, a program (`%post -p /sbin/ldconfig`) or both
+fn scriptlet(
+    header: &Header,
+    body_tag: u32,
+    program_tag: u32,
+    arguments: &str,
+) -> BackendResult<StabOption<StabString>> {
+    let body = header
+        .string(body_tag)?
+        .filter(|body| !body.trim().is_empty());
+    let program = header.strings(program_tag)?.join(" ");
+
+    if program == LUA_INTERPRETER {
+        return Err(BackendError::Unsupported(
+            "Lua scriptlets need rpm itself".into(),
+        ));
+    }
+
+    Ok(match (body, program.is_empty()) {
+        (Some(body), true) => {
+            StabOption::Some(embed_script(&body, Some(DEFAULT_INTERPRETER), arguments))
+        }
+        (Some(body), false) => StabOption::Some(embed_script(&body, Some(&program), arguments)),
+        (None, false) => StabOption::Some(
+            format!("{arguments}\nexec {program} \"$@\"\n")
+                .as_str()
+                .into(),
+        ),
+        (None, true) => StabOption::None(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{header, Value};
+
+    use std::process::Command;
+
+    // Function to run a script the way upac does, with the versions as arguments
+    fn run(script: &StabOption<StabString>, versions: &[&str]) -> String {
+        let script = script.as_ref().expect("script is missing");
+        let output = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(script.as_str())
+            .arg("sh")
+            .args(versions)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn passes_instance_counts() {
+        let header = header(&[
+            (TAG_PREIN, Value::String("echo \"prein $1\"")),
+            (TAG_POSTIN, Value::String("echo \"postin $1\"")),
+            (TAG_POSTINPROG, Value::Strings(&["/bin/sh"])),
+            (TAG_PREUNPROG, Value::Strings(&["/bin/echo", "preun"])),
+        ]);
+        let scripts =
+            Scriptlets::from_header(&Header::read(&mut header.as_slice()).unwrap()).unwrap();
+
+        assert_eq!(run(&scripts.pre_install, &["1.0"]), "prein 1\n");
+        assert_eq!(run(&scripts.post_install, &["2.0", "1.0"]), "postin 2\n");
+        assert_eq!(run(&scripts.pre_remove, &["1.0"]), "preun 0\n");
+        assert_eq!(run(&scripts.pre_remove, &["1.0", "2.0"]), "preun 1\n");
+        assert!(scripts.post_remove.is_none());
+    }
+
+    #[test]
+    fn refuses_lua_scriptlets() {
+        let header = header(&[
+            (TAG_POSTIN, Value::String("print(1)")),
+            (TAG_POSTINPROG, Value::Strings(&["<lua>"])),
+        ]);
+        assert!(Scriptlets::from_header(&Header::read(&mut header.as_slice()).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+// Imports
+use crate::header::LEAD_MAGIC;
+
+// Values of the header index entries tests write
+pub(crate) enum Value<'a> {
+    Int16(&'a [u16]),
+    Int32(&'a [u32]),
+    String(&'a str),
+    Strings(&'a [&'a str]),
+}
+
+// Member of a newc archive: name, mode, inode, link count and data
+pub(crate) type Member<'a> = (&'a str, u32, u32, u32, &'a [u8]);
+
+// Function to write a lead as rpm does; only its magic is read
+pub(crate) fn lead() -> Vec<u8> {
+    let mut lead = LEAD_MAGIC.to_vec();
+    lead.resize(96, 0);
+    lead
+}
+
+// Function to encode one header structure, aligning values the way rpm does
+pub(crate) fn header(entries: &[(u32, Value)]) -> Vec<u8> {
+    let mut index = Vec::new();
+    let mut store = Vec::new();
+
+    for (tag, value) in entries {
+        let (data_type, alignment, count) = match value {
+            Value::Int16(values) => (3u32, 2, values.len()),
+            Value::Int32(values) => (4, 4, values.len()),
+            Value::String(_) => (6, 1, 1),
+            Value::Strings(values) => (8, 1, values.len()),
+        };
+        store.resize(store.len().next_multiple_of(alignment), 0);
+
+        index.extend(tag.to_be_bytes());
+        index.extend(data_type.to_be_bytes());
+        index.extend((store.len() as u32).to_be_bytes());
+        index.extend((count as u32).to_be_bytes());
+
+        match value {
+            Value::Int16(values) => values
+                .iter()
+                .for_each(|value| store.extend(value.to_be_bytes())),
+            Value::Int32(values) => values
+                .iter()
+                .for_each(|value| store.extend(value.to_be_bytes())),
+            Value::String(value) => store.extend(value.bytes().chain([0])),
+            Value::Strings(values) => values
+                .iter()
+                .for_each(|value| store.extend(value.bytes().chain([0]))),
+        }
+    }
+
+    let mut header = vec![0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+    header.extend((entries.len() as u32).to_be_bytes());
+    header.extend((store.len() as u32).to_be_bytes());
+    header.extend(index);
+    header.extend(store);
+    header
+}
+
+// Function to write a newc archive with its trailer
+pub(crate) fn cpio(members: &[Member]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let trailer: Member = ("TRAILER!!!", 0, 0, 1, &[]);
+
+    for (name, mode, inode, links, data) in members.iter().chain([&trailer]) {
+        let fields = [
+            *inode,
+            *mode,
+            0,
+            0,
+            *links,
+            0,
+            data.len() as u32,
+            0,
+            0,
+            0,
+            0,
+        ];
+
+        archive.extend(b"070701");
+        for field in fields {
+            archive.extend(format!("{field:08x}").bytes());
+        }
+        archive.extend(format!("{:08x}{:08x}", name.len() + 1, 0).bytes());
+        archive.extend(name.bytes().chain([0]));
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend(*data);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+    }
+
+    archive
+}
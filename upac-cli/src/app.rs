@@ -1,12 +1,11 @@
-use upac_core_lib::{
-    Backend, Config, Database, InstallEvent, Installer, OStreeError, OStreeRepo, PackageDatabase,
-    UpacConfig,
+use upac_lib::{
+    open_database, BackendRegistry, Config, Database, Keyring, OSTreeManager, PackageInstaller,
+    SignatureVerifier, UpacConfig,
 };
+use upac_types::{BackendError, ConfigError, DatabaseError, InstallerError, OSTreeError, RepositoryError};
 
 use std::io;
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::thread;
-use std::thread::JoinHandle;
+use std::path::{Path, PathBuf};
 
 pub type AppResult<T> = std::result::Result<T, AppError>;
 
@@ -22,8 +21,26 @@ impl From<io::Error> for AppError {
     }
 }
 
-impl From<OStreeError> for AppError {
-    fn from(err: OStreeError) -> Self {
+impl From<OSTreeError> for AppError {
+    fn from(err: OSTreeError) -> Self {
+        AppError::CommandError(err.to_string())
+    }
+}
+
+impl From<BackendError> for AppError {
+    fn from(err: BackendError) -> Self {
+        AppError::CommandError(err.to_string())
+    }
+}
+
+impl From<RepositoryError> for AppError {
+    fn from(err: RepositoryError) -> Self {
+        AppError::CommandError(err.to_string())
+    }
+}
+
+impl From<ConfigError> for AppError {
+    fn from(err: ConfigError) -> Self {
         AppError::CommandError(err.to_string())
     }
 }
@@ -34,6 +51,11 @@ impl From<InstallerError> for AppError {
     }
 }
 
+impl From<DatabaseError> for AppError {
+    fn from(err: DatabaseError) -> Self {
+        AppError::CommandError(err.to_string())
+    }
+}
 
 impl std::fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -44,51 +66,54 @@ impl std::fmt::Display for AppError {
     }
 }
 
-pub enum ErrorMessage {
-    Error(String),
-    Shutdown,
+// Плагины с несовместимым ABI пропускаются с предупреждением
+pub fn load_plugins(backends: &mut BackendRegistry, config: &UpacConfig) -> AppResult<()> {
+    let rejected_plugins = backends.load_plugins(Path::new(config.plugin_dir.as_str())).map_err(|err| AppError::InitError(err.to_string()))?;
+    for err in rejected_plugins {
+        eprintln!("Warning: {err}");
+    }
+
+    Ok(())
 }
 
 pub struct App {
     config: UpacConfig,
-    installer: Installer,
-    ostree: Option<OStreeRepo>,
-    database: Database,
-    error_tx: Sender<ErrorMessage>,
-    backends: Vec<Box<dyn Backend>>,
+    installer: PackageInstaller,
+    ostree: Option<OSTreeManager>,
+    database: Box<dyn Database>,
+    backends: BackendRegistry,
 }
 
 impl App {
-    pub fn init(backends: Vec<Box<dyn Backend>>) -> AppResult<Self> {
-        let (install_tx, install_rx) = channel();
-        let (error_tx, error_rx) = channel::<ErrorMessage>();
-
+    pub fn init(mut backends: BackendRegistry) -> AppResult<Self> {
         let config = UpacConfig::load().map_err(|err| AppError::InitError(err.to_string()))?;
+        load_plugins(&mut backends, &config)?;
 
-        let database = match Database::new(config.database_path.clone()) {
-            Ok(database) => database,
-            Err(err) => return Err(AppError::InitError(err.to_string())),
-        };
+        // Подписи пакетов проверяются библиотекой перед распаковкой
+        let keyring = Keyring::load(Path::new(config.keyring_dir.as_str())).map_err(|err| AppError::InitError(err.to_string()))?;
+        backends.set_verifier(SignatureVerifier::new(config.signature_policy, keyring));
 
-        let _ = Self::spawn_error_thread(error_rx)?;
-        let _ = Self::spawn_installer_message_thread(install_rx)?;
+        // Установщик пишет в свою копию базы, команды читают из второй
+        let open = || {
+            open_database(config.database_backend, PathBuf::from(config.database_path.as_str()))
+                .map_err(|err| AppError::InitError(err.to_string()))
+        };
+        let database = open()?;
 
-        let boxed_database: Box<dyn PackageDatabase> = Box::new(database.clone());
+        let mut installer = PackageInstaller::new(
+            config.root_dir.to_string(),
+            config.package_dir.to_string(),
+            config.temp_dir.to_string(),
+            open()?,
+        )
+        .map_err(|err| AppError::InitError(err.to_string()))?;
 
-        let installer = Installer::new(
-            boxed_database,
-            config.ostree.enabled,
-            config.root_dir.clone(),
-            config.package_dir.clone(),
-            config.temp_dir.clone(),
-            install_tx,
-        );
+        let config_files: Vec<&str> = config.config_files.iter().map(|pattern| pattern.as_str()).collect();
+        installer.set_config_files(&config_files).map_err(|err| AppError::InitError(err.to_string()))?;
+        installer.set_version_schemes(backends.version_schemes());
 
         let ostree = if config.ostree.enabled {
-            Some(
-                OStreeRepo::open(config.ostree.repo_path.clone())
-                    .map_err(|err| AppError::InitError(err.to_string()))?,
-            )
+            Some(OSTreeManager::new(PathBuf::from(config.ostree.repo_path.as_str())))
         } else {
             None
         };
@@ -98,83 +123,26 @@ impl App {
             ostree,
             config,
             database,
-            error_tx,
             backends,
         })
     }
 
-    fn spawn_installer_message_thread(
-        install_rx: Receiver<InstallEvent>,
-    ) -> AppResult<JoinHandle<()>> {
-        let installer_thread = thread::Builder::new()
-            .name(String::from("installer"))
-            .spawn(move || {
-                for message in install_rx {
-                    match message {
-                        InstallEvent::InstallStarted {
-                            package,
-                            total_files,
-                        } => println!("Installing {} ({} files)...", package, total_files),
-                        InstallEvent::FileInstalled { current, total, .. } => {
-                            print!("\rProgress: {}/{}", current, total)
-                        }
-                        InstallEvent::InstallFinished { package } => {
-                            println!("\nDone: {} installed", package)
-                        }
-                        InstallEvent::RemoveStarted { package } => {
-                            println!("Removing {}...", package)
-                        }
-                        InstallEvent::RemoveFinished { package } => {
-                            println!("Done: {} removed", package)
-                        }
-                        InstallEvent::Failed { package, reason } => {
-                            eprintln!("Failed {}: {}", package, reason)
-                        }
-                        _ => {}
-                    }
-                }
-            })?;
-
-        Ok(installer_thread)
-    }
-
-    fn spawn_error_thread(error_rx: Receiver<ErrorMessage>) -> AppResult<JoinHandle<()>> {
-        let error_thread = thread::Builder::new()
-            .name(String::from("error"))
-            .spawn(move || {
-                for message in error_rx {
-                    match message {
-                        ErrorMessage::Error(msg) => eprintln!("Error: {msg}"),
-                        ErrorMessage::Shutdown => break,
-                    }
-                }
-            })?;
-
-        Ok(error_thread)
-    }
-
     pub fn run<F>(&mut self, command: F) -> AppResult<()>
     where
         F: FnOnce(
-            &mut Installer,
-            Option<&OStreeRepo>,
+            &mut PackageInstaller,
+            Option<&OSTreeManager>,
             &UpacConfig,
-            &Database,
-            &[Box<dyn Backend>],
+            &dyn Database,
+            &BackendRegistry,
         ) -> AppResult<()>,
     {
         command(
             &mut self.installer,
             self.ostree.as_ref(),
             &self.config,
-            &self.database,
+            self.database.as_ref(),
             &self.backends,
         )
     }
 }
-
-impl Drop for App {
-    fn drop(&mut self) {
-        let _ = self.error_tx.send(ErrorMessage::Shutdown);
-    }
-}
@@ -0,0 +1,18 @@
+use crate::app::AppResult;
+use crate::BuildOptions;
+
+use upac_lib::{build_package, BUILD_MANIFEST_NAME};
+
+use std::path::PathBuf;
+
+// Сборка не трогает систему, поэтому работает без App и без root
+pub(crate) fn build(options: BuildOptions) -> AppResult<()> {
+    // Манифест по умолчанию лежит в корне staging-каталога и в пакет не попадает
+    let manifest = options.manifest.unwrap_or_else(|| options.dir.join(BUILD_MANIFEST_NAME));
+    let output = options.output.unwrap_or_else(|| PathBuf::from("."));
+
+    let package = build_package(&options.dir, &manifest, &output)?;
+    println!("Built {}", package.display());
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use crate::app::{AppError, AppResult};
+
+use upac_lib::{BackendRegistry, Database, DatabaseBackend, JournalState, OSTreeManager, PackageDatabase, PackageInstaller, UpacConfig};
+
+use std::path::PathBuf;
+
+pub(crate) fn recover() -> impl FnOnce(
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
+    &UpacConfig,
+    &dyn Database,
+    &BackendRegistry,
+) -> AppResult<()> {
+    move |_, _, config, _, _| {
+        // SQLite сам откатывает прерванные транзакции, журнал есть только у TOML базы
+        if config.database_backend == DatabaseBackend::Sqlite {
+            println!("SQLite database needs no recovery");
+            return Ok(());
+        }
+
+        let report = PackageDatabase::recover(PathBuf::from(config.database_path.as_str()))
+            .map_err(|err| AppError::CommandError(format!("Database recovery failed: {err}")))?;
+
+        match report.journal {
+            JournalState::Clean     => println!("Journal: clean"),
+            JournalState::Replayed  => println!("Journal: interrupted operation replayed"),
+            JournalState::Discarded => println!("Journal: uncommitted operation discarded"),
+        }
+
+        for package in report.orphaned_packages.iter() {
+            println!("Removed orphaned package record: {package}");
+        }
+
+        for package in report.missing_packages.iter() {
+            println!("Dropped package without file list: {package}");
+        }
+
+        if report.orphaned_packages.is_empty() && report.missing_packages.is_empty() {
+            println!("Database is consistent");
+        }
+
+        Ok(())
+    }
+}
@@ -1,2 +1,4 @@
+pub mod build;
+pub mod db;
 pub mod package;
 pub mod repo;
@@ -1,49 +1,102 @@
 use crate::app::{AppResult, AppError};
-use crate::{InstallOptions, RemoveOptions, SearchOptions, UpdateOptions, UpgradeOptions};
+use crate::{InstallOptions, RemoveOptions, SearchOptions, UpdateOptions, UpgradeOptions, VerifyOptions};
 
-use upac_core_lib::{Backend, Database, Install, Installer, InstallerError, OStreeRepo, PackageDiff, PackageRegistry, PackageRepo, UpacConfig};
+use upac_lib::{compare_versions, BackendRegistry, Constraint, Database, InstallPolicy, Installer, NameMatch, OSTree, OSTreeManager, PackageFilter, PackageInstaller, RemovePolicy, RepositorySet, ScriptOutput, UpacConfig, VerifyReport};
+use upac_types::{DatabaseError, InstallReason, OSTreeOperation, Package};
 
-pub(crate) fn install(
-    options: InstallOptions,
-) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
-    &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
-) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| {
-    	// Проверка существования пути пакета
-        if !&options.package.exists() {
-            return Err(AppError::CommandError(format!("File not found: {}", &options.package.display())));
+use regex::Regex;
+
+use serde_json::json;
+
+use time::OffsetDateTime;
+
+use std::cmp::Ordering;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+fn print_script_outputs(outputs: &[ScriptOutput]) {
+    for output in outputs {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+
+        if output.exit_code != 0 {
+            eprintln!("Warning: {} script exited with code {}", output.phase.as_str(), output.exit_code);
         }
+    }
+}
 
-        // Выбор бекенда для пакета
-        let backend = backends.iter().find(|backend| backend.detect(&options.package)).ok_or_else(|| AppError::CommandError(format!("Unsupported package format: {}", &options.package.display())))?;
+// Снимок системы после операции, если ostree включён
+fn commit_snapshot(ostree: Option<&OSTreeManager>, config: &UpacConfig, operation: OSTreeOperation, packages: &[String]) -> AppResult<()> {
+    if !config.ostree.enabled {
+        return Ok(());
+    }
 
-        // Извлекаем пакет во временную директорию
-        let extracted_package = backend.extract(&options.package, &config.temp_dir)?;
+    let ostree = ostree.ok_or_else(|| AppError::CommandError(String::from("OSTree not available")))?;
+    let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+    ostree.commit(Path::new(config.ostree.repo_path.as_str()), None, operation, &packages)?;
 
-        // Устанавливаем
-        installer.install(&extracted_package)?;
+    Ok(())
+}
 
-        // Если ostree включён — делаем коммит
-        if config.ostree.enabled {
-            let packages = installer.list_packages()?;
+// Отсутствующая запись в базе означает, что пакет не установлен
+fn installed_package(database: &dyn Database, name: &str) -> AppResult<Option<Package>> {
+    match database.get_package(name) {
+        Ok(package)                  => Ok(Some(package)),
+        Err(DatabaseError::NotFound) => Ok(None),
+        Err(err)                     => Err(err.into()),
+    }
+}
 
-            let mut packages_files = Vec::new();
-            for package in &packages {
-                packages_files.extend(installer.list_files(&package.name)?);
+pub(crate) fn install(
+    options: InstallOptions,
+) -> impl FnOnce(
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
+    &UpacConfig,
+    &dyn Database,
+    &BackendRegistry,
+) -> AppResult<()> {
+    move |installer, ostree, config, database, backends| {
+        let reason = if options.as_deps { InstallReason::Dependency } else { InstallReason::Explicit };
+
+        // Файл с диска ставится как есть, иначе пакет ищется по имени в репозиториях
+        let packages = if options.package.exists() {
+            vec![(options.package.clone(), reason)]
+        } else {
+            let name = options.package.to_str().filter(|name| !name.contains('/')).ok_or_else(|| AppError::CommandError(format!("File not found: {}", options.package.display())))?;
+
+            let mut repositories = RepositorySet::from_config(config)?;
+            repositories.set_version_schemes(backends.version_schemes());
+            let remote_packages = repositories.resolve(&[name], database)?;
+
+            // Сначала скачиваем всё, чтобы не оставить систему с половиной зависимостей
+            let mut packages = Vec::new();
+            for remote_package in &remote_packages {
+                println!("Downloading {} {}...", remote_package.entry.name, remote_package.entry.version);
+                let package_reason = if remote_package.entry.name == name { reason } else { InstallReason::Dependency };
+                packages.push((repositories.download(remote_package)?, package_reason));
             }
-
-            let diff = PackageDiff {
-                added:   vec![extracted_package.name.clone()],
-                removed: vec![],
-                updated: vec![],
+            packages
+        };
+
+        // Весь набор ставится одной транзакцией: ошибка любого пакета откатывает и его зависимости
+        let mut added = Vec::new();
+        let temp_dir = Path::new(config.temp_dir.as_str());
+        installer.install_all(packages.iter().map(|(package_path, reason)| {
+            // Реестр проверяет подпись, выбирает бэкенд и извлекает пакет во временную директорию
+            let extracted_package = backends.extract(package_path, temp_dir)?;
+            added.push(extracted_package.name.to_string());
+
+            let policy = InstallPolicy {
+                overwrite: options.overwrite.iter().map(|pattern| pattern.as_str().into()).collect(),
+                reason:    *reason,
             };
+            Ok((extracted_package, policy))
+        }))?;
+        print_script_outputs(installer.script_outputs());
 
-            ostree.ok_or_else(|| AppError::CommandError(String::from("OSTree not available")))?.commit(packages_files, &diff)?;
-        }
+        // Если ostree включён — делаем коммит
+        commit_snapshot(ostree, config, OSTreeOperation::Install, &added)?;
 
         Ok(())
     }
@@ -52,33 +105,19 @@ pub(crate) fn install(
 pub(crate) fn remove(
     options: RemoveOptions,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| {
-        let package = database.get_package(&options.package).map_err(|err| AppError::CommandError(err.to_string()))?.ok_or_else(|| AppError::CommandError(format!("Package not found: {}", options.package)))?;
+    move |installer, ostree, config, database, _| {
+        let package = installed_package(database, &options.package)?.ok_or_else(|| AppError::CommandError(format!("Package not found: {}", options.package)))?;
 
-        installer.remove(&package.name)?;
+        installer.remove(&package.name, RemovePolicy { force: options.force, purge: options.purge })?;
+        print_script_outputs(installer.script_outputs());
 
-        if config.ostree.enabled {
-            let packages = installer.list_packages()?;
-
-            let mut packages_files = Vec::new();
-            for package in &packages {
-                packages_files.extend(installer.list_files(&package.name)?);
-            }
-
-            let diff = PackageDiff {
-                added:   vec![],
-                removed: vec![options.package.clone()],
-                updated: vec![],
-            };
-
-            ostree.ok_or_else(|| AppError::CommandError(String::from("OSTree not available")))?.commit(packages_files, &diff)?;
-        }
+        commit_snapshot(ostree, config, OSTreeOperation::Remove, std::slice::from_ref(&options.package))?;
 
         Ok(())
     }
@@ -87,11 +126,11 @@ pub(crate) fn remove(
 pub(crate) fn update(
     options: UpdateOptions,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
     move |installer, ostree, config, database, backends| {
         // Проверяем что файл существует
@@ -99,41 +138,42 @@ pub(crate) fn update(
             return Err(AppError::CommandError(format!("File not found: {}", options.package.display())));
         }
 
-        // Ищем подходящий бэкенд
-        let backend = backends.iter().find(|backend| backend.detect(&options.package)).ok_or_else(|| AppError::CommandError(format!("Unsupported package format: {}", &options.package.display())))?;
-
-        // Читаем метаданные нового пакета
-        let extracted_package = backend.extract(&options.package, &config.temp_dir)?;
+        // Реестр выбирает бэкенд и извлекает новый пакет
+        let extracted_package = backends.extract(&options.package, Path::new(config.temp_dir.as_str()))?;
 
         // Проверяем что пакет вообще установлен
-        let current_package = database.get_package(&extracted_package.name)?.ok_or_else(|| AppError::CommandError(format!("Package not installed: {}", extracted_package.name)))?;
+        let current_package = installed_package(database, &extracted_package.name)?.ok_or_else(|| AppError::CommandError(format!("Package not installed: {}", extracted_package.name)))?;
 
-        // Проверяем что новая версия отличается от текущей
-        if current_package.version == extracted_package.version && !options.force {
-            println!("Package {} is already at version {}", extracted_package.name, extracted_package.version);
-            return Ok(());
+        // Сравниваем версии по правилам формата пакета: откат на старую версию только с --force
+        let scheme = backends.version_scheme(extracted_package.format.as_str());
+        match compare_versions(scheme, extracted_package.version.as_str(), &current_package.version) {
+            Ordering::Equal if !options.force => {
+                println!("Package {} is already at version {}", extracted_package.name, extracted_package.version);
+                return Ok(());
+            }
+            Ordering::Less if !options.force => {
+                return Err(AppError::CommandError(format!(
+                    "{} {} is older than the installed {}, use --force to downgrade",
+                    extracted_package.name, extracted_package.version, current_package.version
+                )));
+            }
+            Ordering::Less => println!("Downgrading {}: {} -> {}", extracted_package.name, current_package.version, extracted_package.version),
+            Ordering::Equal => println!("Reinstalling {} {}", extracted_package.name, extracted_package.version),
+            Ordering::Greater => println!("Upgrading {}: {} -> {}", extracted_package.name, current_package.version, extracted_package.version),
         }
 
-        installer.remove(&extracted_package.name)?;
-
-        installer.install(&extracted_package)?;
+        // Обновление заменяет общие файлы на месте, сохраняет изменённые конфиги и удаляет только исчезнувшие файлы
+        // Причина установки сохраняется при обновлении
+        let policy = InstallPolicy {
+            overwrite: options.overwrite.iter().map(|pattern| pattern.as_str().into()).collect(),
+            reason:    current_package.reason,
+        };
 
-        if config.ostree.enabled {
-            let packages = installer.list_packages()?;
+        let name = extracted_package.name.to_string();
+        installer.upgrade(&current_package.name, extracted_package, policy)?;
+        print_script_outputs(installer.script_outputs());
 
-            let mut packages_files = Vec::new();
-            for package in &packages {
-                packages_files.extend(installer.list_files(&package.name)?);
-            }
-
-            let diff = PackageDiff {
-                added:   vec![],
-                removed: vec![],
-                updated: vec![extracted.name.clone()],
-            };
-
-            ostree.ok_or_else(|| AppError::CommandError(String::from("OSTree not available")))?.commit(packages_files, &diff)?;
-        }
+        commit_snapshot(ostree, config, OSTreeOperation::Update, &[name])?;
 
         Ok(())
     }
@@ -142,54 +182,122 @@ pub(crate) fn update(
 pub(crate) fn upgrade(
     options: UpgradeOptions,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, _, _, _| {
-        if options.check_only {
-            let packages = installer.list_packages()?;
+    move |installer, ostree, config, database, backends| {
+        // Сравниваем установленные пакеты с индексами, полученными последним `upac repo update`
+        let mut repositories = RepositorySet::from_config(config)?;
+        repositories.set_version_schemes(backends.version_schemes());
+        let upgrades = repositories.upgrades(database)?;
 
-            if packages.is_empty() {
-                println!("No packages installed.");
-                return Ok(());
+        if upgrades.is_empty() {
+            println!("All packages are up to date.");
+            return Ok(());
+        }
+
+        // Набор показывается в порядке установки: зависимости раньше зависящих от них пакетов
+        println!("Pending upgrades:");
+        for upgrade in &upgrades {
+            let entry = &upgrade.package.entry;
+            match &upgrade.installed {
+                Some(installed) => println!("  {} {installed} → {}", entry.name, entry.version),
+                None            => println!("  {} {} (new dependency)", entry.name, entry.version),
             }
+        }
+
+        if options.check_only {
+            return Ok(());
+        }
+
+        if !options.yes {
+            print!("Proceed with upgrade? [y/N] ");
+            io::stdout().flush()?;
 
-            println!("Installed packages:");
-            for package in &packages {
-                println!("  {} ({})", package.name, package.version);
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                println!("Upgrade cancelled.");
+                return Ok(());
             }
+        }
 
-            println!("\nRepository support is not yet implemented.");
-            println!("Cannot check for updates without a configured repository.");
+        // Сначала скачиваем всё, система не трогается, пока не получен каждый пакет
+        let mut packages = Vec::new();
+        for upgrade in &upgrades {
+            let entry = &upgrade.package.entry;
+            println!("Downloading {} {}...", entry.name, entry.version);
 
-            return Ok(());
+            // Обновление сохраняет причину установки, новые зависимости ставятся как зависимости
+            let reason = match installed_package(database, &entry.name)? {
+                Some(installed) => installed.reason,
+                None            => InstallReason::Dependency,
+            };
+            packages.push((repositories.download(&upgrade.package)?, reason));
         }
 
-        Err(AppError::CommandError(
-            "Upgrade requires repository support which is not yet implemented.".to_string()
-        ))
+        // Каждый пакет извлекается прямо перед применением; ошибка любого откатывает весь набор
+        let temp_dir = Path::new(config.temp_dir.as_str());
+        installer.install_all(packages.iter().map(|(package_path, reason)| {
+            let extracted_package = backends.extract(package_path, temp_dir)?;
+            let policy = InstallPolicy {
+                overwrite: Default::default(),
+                reason:    *reason,
+            };
+            Ok((extracted_package, policy))
+        }))?;
+        print_script_outputs(installer.script_outputs());
+
+        let updated: Vec<_> = upgrades.iter().map(|upgrade| upgrade.package.entry.name.clone()).collect();
+        commit_snapshot(ostree, config, OSTreeOperation::Update, &updated)?;
+
+        Ok(())
     }
 }
 
+// Даты принимаются в RFC 3339 или как YYYY-MM-DD
+fn parse_date(date: &str) -> AppResult<OffsetDateTime> {
+    PackageFilter::parse_install_date(date)
+        .or_else(|| PackageFilter::parse_install_date(&format!("{date}T00:00:00Z")))
+        .ok_or_else(|| AppError::CommandError(format!("Invalid date: {date}")))
+}
+
 pub(crate) fn search(
     options: SearchOptions,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
     move |_, _, _, database, _| {
-        let mut results: Vec<_> = database.list_all_packages().iter().filter(|package_info| {
-            if options.exact {
-                package_info.name == options.query
-            } else {
-            	package_info.name.to_lowercase().contains(&options.query.to_lowercase())
-            }
-        }).collect();
+        let name = if options.exact {
+            NameMatch::Exact(options.query.clone())
+        } else if options.regex {
+            NameMatch::Regex(Regex::new(&options.query).map_err(|err| AppError::CommandError(format!("Invalid regex: {err}")))?)
+        } else {
+            NameMatch::Substring(options.query.clone())
+        };
+
+        let reason = match (options.explicit, options.deps) {
+            (true, false) => Some(InstallReason::Explicit),
+            (false, true) => Some(InstallReason::Dependency),
+            _ => None,
+        };
+
+        let filter = PackageFilter {
+            name: Some(name),
+            format: options.format.clone(),
+            installed_after: options.after.as_deref().map(parse_date).transpose()?,
+            installed_before: options.before.as_deref().map(parse_date).transpose()?,
+            reason,
+        };
+
+        let mut results = database.filter_packages(&filter).map_err(|err| AppError::CommandError(err.to_string()))?;
 
         if results.is_empty() {
             println!("No packages found for: {}", options.query);
@@ -201,7 +309,7 @@ pub(crate) fn search(
         }
 
         for pkg in results {
-            println!("{} ({})", pkg.name, pkg.version);
+            println!("{} ({}) [{}]", pkg.name, pkg.version, pkg.reason.as_str());
         }
 
         Ok(())
@@ -209,43 +317,167 @@ pub(crate) fn search(
 }
 
 pub(crate) fn show(
-	package: &str,
+	package: String,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| {
-        todo!()
+    move |_, _, _, database, _| {
+        let package = installed_package(database, &package)?.ok_or_else(|| AppError::CommandError(format!("Package not installed: {package}")))?;
+
+        let list = |values: &[String]| if values.is_empty() { String::from("None") } else { values.join(" ") };
+
+        println!("Name         : {}", package.name);
+        println!("Version      : {}", package.version);
+        println!("Format       : {}", package.format);
+        println!("Install Date : {}", package.install_date);
+        println!("Reason       : {}", package.reason.as_str());
+        println!("Depends On   : {}", list(&package.dependencies));
+        println!("Provides     : {}", list(&package.provides));
+        println!("Conflicts    : {}", list(&package.conflicts));
+
+        Ok(())
     }
 }
 
 pub(crate) fn files(
-	package: &str,
+	package: String,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| {
-        todo!()
+    move |_, _, config, database, _| {
+        if installed_package(database, &package)?.is_none() {
+            return Err(AppError::CommandError(format!("Package not installed: {package}")));
+        }
+
+        // Пути в базе хранятся относительно корня
+        let root = Path::new(config.root_dir.as_str());
+        for entry in database.get_file_entries(&package)? {
+            match &entry.link_target {
+                Some(target) => println!("{} -> {}", root.join(&entry.path).display(), target.display()),
+                None         => println!("{}", root.join(&entry.path).display()),
+            }
+        }
+
+        Ok(())
     }
 }
 
 pub(crate) fn deps(
-	package: &str,
+	package: String,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| {
-        todo!()
+    move |_, _, _, database, backends| {
+        let package = installed_package(database, &package)?.ok_or_else(|| AppError::CommandError(format!("Package not installed: {package}")))?;
+        let installed = database.list_packages()?;
+
+        // Зависимость удовлетворяется по имени и версии или через provides другого пакета
+        let satisfies = |constraint: &Constraint, candidate: &Package| {
+            let scheme = backends.version_scheme(&candidate.format);
+            constraint.satisfied_by(&candidate.name, &candidate.version, candidate.provides.iter().map(String::as_str), scheme)
+        };
+
+        println!("{} {} depends on:", package.name, package.version);
+        if package.dependencies.is_empty() {
+            println!("  nothing");
+        }
+        for dependency in &package.dependencies {
+            let constraint = Constraint::parse(dependency);
+            match installed.iter().find(|candidate| satisfies(&constraint, candidate)) {
+                Some(provider) => println!("  {dependency} ({} {})", provider.name, provider.version),
+                None           => println!("  {dependency} (missing)"),
+            }
+        }
+
+        let dependents: Vec<&str> = installed
+            .iter()
+            .filter(|candidate| candidate.name != package.name)
+            .filter(|candidate| candidate.dependencies.iter().any(|dependency| satisfies(&Constraint::parse(dependency), &package)))
+            .map(|candidate| candidate.name.as_str())
+            .collect();
+
+        println!("Required by: {}", if dependents.is_empty() { String::from("nothing") } else { dependents.join(", ") });
+
+        Ok(())
+    }
+}
+
+pub(crate) fn owns(
+	path: PathBuf,
+) -> impl FnOnce(
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
+    &UpacConfig,
+    &dyn Database,
+    &BackendRegistry,
+) -> AppResult<()> {
+    move |_, _, config, database, _| {
+        // Пути в базе хранятся относительно корня
+        let relative_path = path.strip_prefix(config.root_dir.as_str()).unwrap_or(&path);
+
+        let owners = database.owner_of(relative_path).map_err(|_| AppError::CommandError(format!("No package owns {}", path.display())))?;
+
+        println!("{} is owned by {}", path.display(), owners.join(", "));
+
+        Ok(())
+    }
+}
+
+fn print_verify_report(report: &VerifyReport, as_json: bool) {
+    if as_json {
+        let issues: Vec<_> = report.issues.iter().map(|issue| json!({
+            "package":  issue.package.as_str(),
+            "path":     issue.path.as_str(),
+            "kind":     issue.kind.as_str(),
+            "expected": issue.expected.as_str(),
+            "actual":   issue.actual.as_str(),
+        })).collect();
+
+        println!("{}", json!({
+            "packages_checked": report.packages_checked,
+            "files_checked":    report.files_checked,
+            "issues":           issues,
+        }));
+        return;
+    }
+
+    for issue in report.issues.iter() {
+        println!("{}: /{}: {} (expected: {}, actual: {})", issue.package, issue.path, issue.kind.as_str(), issue.expected, issue.actual);
+    }
+
+    println!("Checked {} files in {} packages, {} problems found", report.files_checked, report.packages_checked, report.issues.len());
+}
+
+pub(crate) fn verify(
+	options: VerifyOptions,
+) -> impl FnOnce(
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
+    &UpacConfig,
+    &dyn Database,
+    &BackendRegistry,
+) -> AppResult<()> {
+    move |installer, _, _, _, _| {
+        let report = installer.verify(options.package.as_deref())?;
+        print_verify_report(&report, options.json);
+
+        // Ненулевой код выхода для мониторинга
+        if !report.issues.is_empty() {
+            return Err(AppError::CommandError(format!("{} integrity problems found", report.issues.len())));
+        }
+
+        Ok(())
     }
 }
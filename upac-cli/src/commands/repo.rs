@@ -1,37 +1,139 @@
-use crate::app::{AppResult};
+use crate::app::{AppError, AppResult};
+use crate::RepoIndexOptions;
 
-use upac_core_lib::{Backend, Installer, Database, OStreeRepo, UpacConfig};
+use upac_lib::{generate_index, BackendRegistry, Database, OSTreeManager, PackageInstaller, RepositorySet, SecretKey, UpacConfig};
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+// Пароль ключа берётся из окружения, чтобы repo-index работал в CI
+const KEY_PASSWORD_ENV: &str = "UPAC_KEY_PASSWORD";
 
 pub(crate) fn add(
     url: String,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| todo!()
+    move |_, _, config, _, _| {
+        // Поддерживаются только локальные каталоги и обычный HTTP
+        if !(url.starts_with("file://") || url.starts_with("http://") || url.starts_with('/')) {
+            return Err(AppError::CommandError(format!("Unsupported repository URL: {url}")));
+        }
+
+        if config.repositories.iter().any(|repository| repository.as_str() == url) {
+            println!("Repository {url} is already configured");
+            return Ok(());
+        }
+
+        let mut config = config.clone();
+        config.repositories.push(url.as_str().into());
+        config.save_repositories()?;
+
+        println!("Added repository {url}, run `upac repo update` to fetch its index");
+        Ok(())
+    }
 }
 
 pub(crate) fn remove(
     url: String,
 ) -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| todo!()
+    move |_, _, config, _, _| {
+        let mut config = config.clone();
+        let configured = config.repositories.len();
+        config.repositories = config.repositories.iter().filter(|repository| repository.as_str() != url).cloned().collect();
+
+        if config.repositories.len() == configured {
+            return Err(AppError::CommandError(format!("Repository not configured: {url}")));
+        }
+
+        config.save_repositories()?;
+
+        // Кэш индекса удаляется при следующем `repo update`
+        println!("Removed repository {url}");
+        Ok(())
+    }
 }
 
 pub(crate) fn update() -> impl FnOnce(
-    &mut Installer,
-    Option<&OStreeRepo>,
+    &mut PackageInstaller,
+    Option<&OSTreeManager>,
     &UpacConfig,
-    &Database,
-    &[Box<dyn Backend>],
+    &dyn Database,
+    &BackendRegistry,
 ) -> AppResult<()> {
-    move |installer, ostree, config, database, backends| todo!()
+    move |_, _, config, _, backends| {
+        if config.repositories.is_empty() {
+            println!("No repositories configured, add one with `upac repo add <url>`");
+            return Ok(());
+        }
+
+        // Индексы проверяются той же политикой подписи, что и пакеты
+        let verifier = backends.verifier().cloned().unwrap_or_default();
+
+        let mut repositories = RepositorySet::from_config(config)?;
+        repositories.update(&verifier)?;
+
+        for repository in repositories.repositories() {
+            println!("{}: {} packages", repository.url, repository.index.packages.len());
+        }
+
+        Ok(())
+    }
+}
+
+// Зашифрованный ключ без пароля в окружении запрашивает пароль у пользователя
+fn load_secret_key(path: &Path) -> AppResult<SecretKey> {
+    if let Ok(password) = env::var(KEY_PASSWORD_ENV) {
+        return SecretKey::load(path, Some(&password)).map_err(|err| AppError::CommandError(err.to_string()));
+    }
+
+    if let Ok(key) = SecretKey::load(path, None) {
+        return Ok(key);
+    }
+
+    let prompt = format!("Password for {}: ", path.display());
+
+    // С терминала пароль читается без эха, из пайпа построчно
+    let password = if io::stdin().is_terminal() {
+        rpassword::prompt_password(prompt)?
+    } else {
+        eprint!("{prompt}");
+        let mut password = String::new();
+        io::stdin().read_line(&mut password)?;
+        password
+    };
+
+    SecretKey::load(path, Some(password.trim_end_matches(['\r', '\n']))).map_err(|err| AppError::CommandError(err.to_string()))
+}
+
+// Индекс собирается без App: нужны только бэкенды для чтения метаданных
+pub(crate) fn index(options: RepoIndexOptions, backends: &BackendRegistry) -> AppResult<()> {
+    let key = options.key.as_deref().map(load_secret_key).transpose()?;
+
+    // Неизменённые файлы берутся из старого индекса без повторного хеширования
+    let report = generate_index(&options.dir, backends, key.as_ref())?;
+
+    for file in &report.updated {
+        println!("Indexed {file}");
+    }
+    for file in &report.removed {
+        println!("Dropped {file}");
+    }
+    for file in &report.skipped {
+        eprintln!("Warning: {file} is not a package, skipped");
+    }
+
+    println!("{} packages indexed, {} unchanged{}", report.updated.len(), report.reused, if key.is_some() { ", index signed" } else { "" });
+    Ok(())
 }
@@ -1,11 +1,16 @@
 use clap::{Parser, Subcommand, Args};
 
 use upac_backend_alpm::AlpmBackend;
+use upac_backend_deb::DebBackend;
+use upac_backend_rpm::RpmBackend;
+use upac_lib::{BackendRegistry, Config, NativeBackend, UpacConfig};
 
+use commands::build;
+use commands::db;
 use commands::package;
 use commands::repo;
 
-use app::App;
+use app::{App, AppError, AppResult};
 
 use std::path::PathBuf;
 
@@ -29,8 +34,14 @@ enum Command {
     Show     { package: String },
     Files    { package: String },
     Deps     { package: String },
+    Owns     { path: PathBuf },
+    Verify(VerifyOptions),
+    Build(BuildOptions),
+    RepoIndex(RepoIndexOptions),
     #[command(subcommand)]
     Repo(RepoCommand),
+    #[command(subcommand)]
+    Db(DbCommand),
 }
 
 #[derive(Subcommand)]
@@ -40,12 +51,19 @@ enum RepoCommand {
     Update,
 }
 
+#[derive(Subcommand)]
+enum DbCommand {
+    Recover,
+}
+
 #[derive(Args, Default)]
 pub struct InstallOptions {
     pub package: PathBuf,
     #[arg(short, long)] pub yes:      bool,
     #[arg(short, long)] pub force:    bool,
     #[arg(short, long)] pub download: bool,
+    #[arg(long)]        pub as_deps:  bool,
+    #[arg(long, value_name = "GLOB")] pub overwrite: Vec<String>,
 }
 
 #[derive(Args, Default)]
@@ -64,6 +82,7 @@ pub struct UpdateOptions {
     #[arg(short, long)] pub yes:     bool,
     #[arg(short, long)] pub force:   bool,
     #[arg(short, long)] pub no_deps: bool,
+    #[arg(long, value_name = "GLOB")] pub overwrite: Vec<String>,
 }
 
 #[derive(Args, Default)]
@@ -80,39 +99,80 @@ pub struct SearchOptions {
     #[arg(short, long)] pub description:    bool,
     #[arg(short, long)] pub installed_only: bool,
     #[arg(short, long)] pub limit:          Option<u64>,
+    #[arg(long)]        pub regex:          bool,
+    #[arg(long)]        pub format:         Option<String>,
+    #[arg(long)]        pub after:          Option<String>,
+    #[arg(long)]        pub before:         Option<String>,
+    #[arg(long)]        pub explicit:       bool,
+    #[arg(long)]        pub deps:           bool,
+}
+
+#[derive(Args, Default)]
+pub struct VerifyOptions {
+    pub package: Option<String>,
+    #[arg(long)] pub json: bool,
+}
+
+#[derive(Args, Default)]
+pub struct BuildOptions {
+    pub dir: PathBuf,
+    #[arg(short, long)] pub manifest: Option<PathBuf>,
+    #[arg(short, long)] pub output:   Option<PathBuf>,
+}
+
+#[derive(Args, Default)]
+pub struct RepoIndexOptions {
+    pub dir: PathBuf,
+    #[arg(short, long)] pub key: Option<PathBuf>,
 }
 
 fn main() {
 	let cli = Cli::parse();
 
-    let mut app = match App::init(vec![
-        Box::new(AlpmBackend),
-    ]) {
-        Ok(app)  => app,
-        Err(err) => {
-            eprintln!("Error: {err}");
-            std::process::exit(1);
-        }
-    };
+    let mut backends = BackendRegistry::new();
+    backends.register(Box::new(NativeBackend));
+    backends.register(Box::new(AlpmBackend));
+    backends.register(Box::new(DebBackend));
+    backends.register(Box::new(RpmBackend));
 
+    // Сборка пакета и индекса не трогают систему: им не нужны ни база, ни хранилище, ни root
     let result = match cli.command {
+        Command::Build(opts)     => build::build(opts),
+        Command::RepoIndex(opts) => UpacConfig::load()
+            .map_err(AppError::from)
+            .and_then(|config| app::load_plugins(&mut backends, &config))
+            .and_then(|()| repo::index(opts, &backends)),
+        command => run(command, backends),
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run(command: Command, backends: BackendRegistry) -> AppResult<()> {
+    let mut app = App::init(backends)?;
+
+    match command {
         Command::Install(opts) => app.run(package::install(opts)),
         Command::Remove(opts)  => app.run(package::remove(opts)),
         Command::Update(opts)  => app.run(package::update(opts)),
         Command::Upgrade(opts) => app.run(package::upgrade(opts)),
         Command::Search(opts)  => app.run(package::search(opts)),
-        Command::Show  { package } => app.run(package::show(&package)),
-        Command::Files { package } => app.run(package::files(&package)),
-        Command::Deps  { package } => app.run(package::deps(&package)),
+        Command::Show  { package } => app.run(package::show(package)),
+        Command::Files { package } => app.run(package::files(package)),
+        Command::Deps  { package } => app.run(package::deps(package)),
+        Command::Owns  { path }    => app.run(package::owns(path)),
+        Command::Verify(opts) => app.run(package::verify(opts)),
         Command::Repo(cmd) => match cmd {
             RepoCommand::Add    { url } => app.run(repo::add(url)),
             RepoCommand::Remove { url } => app.run(repo::remove(url)),
             RepoCommand::Update        => app.run(repo::update()),
         },
-    };
-
-    if let Err(err) = result {
-        eprintln!("Error: {err}");
-        std::process::exit(1);
+        Command::Db(cmd) => match cmd {
+            DbCommand::Recover => app.run(db::recover()),
+        },
+        Command::Build(_) | Command::RepoIndex(_) => unreachable!("dispatched before App::init"),
     }
 }
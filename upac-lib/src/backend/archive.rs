@@ -0,0 +1,172 @@
+// Imports
+use super::{BackendError, BackendResult};
+
+pub use crate::checksum::sha256_file;
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use tar::{Archive, EntryType};
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+// Compression of a stream, recognised by its first bytes
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else if header.starts_with(XZ_MAGIC) {
+            Self::Xz
+        } else if header.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        } else {
+            Self::None
+        }
+    }
+}
+
+// Function to wrap a stream in the decoder matching its first bytes
+pub fn decompress<'a, R: Read + 'a>(reader: R) -> BackendResult<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let compression = Compression::detect(reader.fill_buf()?);
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+    })
+}
+
+// Function to turn an archive member name into a path relative to the package root
+pub fn normalize_path(path: &Path) -> BackendResult<PathBuf> {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => {
+                return Err(BackendError::Format(
+                    format!("Unsafe path in archive: {}", path.display()).into(),
+                ))
+            }
+        }
+    }
+
+    Ok(normalized)
+}
+
+// Function to unpack a tar stream into temp_dir; returns the unpacked paths in archive order.
+// Every member is offered to `take` first, members it consumes (returns true for) are not unpacked
+pub fn unpack_tar<R: Read>(
+    reader: R,
+    temp_dir: &Path,
+    take: impl FnMut(&Path, &mut dyn Read) -> BackendResult<bool>,
+) -> BackendResult<StabVec<StabString>> {
+    unpack_tar_with(reader, temp_dir, true, take)
+}
+
+// Function to unpack a tar stream like unpack_tar; the owners the archive records are applied only
+// with `preserve_ownerships`, otherwise the files belong to whoever extracts them
+pub fn unpack_tar_with<R: Read>(
+    reader: R,
+    temp_dir: &Path,
+    preserve_ownerships: bool,
+    mut take: impl FnMut(&Path, &mut dyn Read) -> BackendResult<bool>,
+) -> BackendResult<StabVec<StabString>> {
+    fs::create_dir_all(temp_dir)?;
+
+    let mut archive = Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_ownerships(preserve_ownerships);
+    archive.set_preserve_mtime(true);
+    archive.set_overwrite(true);
+
+    let mut file_list = StabVec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if matches!(
+            entry.header().entry_type(),
+            EntryType::XGlobalHeader | EntryType::XHeader
+        ) {
+            continue;
+        }
+
+        let path = normalize_path(&entry.path()?)?;
+        if path.as_os_str().is_empty() || take(&path, &mut entry)? {
+            continue;
+        }
+
+        if !entry.unpack_in(temp_dir)? {
+            return Err(BackendError::Format(
+                format!("Unsafe path in archive: {}", path.display()).into(),
+            ));
+        }
+
+        file_list.push(path_to_stab(&path)?);
+    }
+
+    Ok(file_list)
+}
+
+// Function to read one member of a tar stream without unpacking anything
+pub fn read_tar_member<R: Read>(reader: R, member: &Path) -> BackendResult<Option<Vec<u8>>> {
+    let mut archive = Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if normalize_path(&entry.path()?)? == member {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            return Ok(Some(content));
+        }
+    }
+
+    Ok(None)
+}
+
+// Function to read every regular file of a small tar stream (control archives and the like) into memory
+pub fn read_tar_files<R: Read>(reader: R) -> BackendResult<Vec<(PathBuf, Vec<u8>)>> {
+    let mut archive = Archive::new(reader);
+    let mut files = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = normalize_path(&entry.path()?)?;
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.push((path, content));
+    }
+
+    Ok(files)
+}
+
+// Function to turn a relative path into an entry of a package file list
+pub fn path_to_stab(path: &Path) -> BackendResult<StabString> {
+    path.to_str()
+        .map(StabString::from)
+        .ok_or_else(|| BackendError::Format(format!("Non UTF-8 path: {}", path.display()).into()))
+}
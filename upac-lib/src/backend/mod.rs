@@ -0,0 +1,39 @@
+// Imports
+use upac_types::{BackendError, BackendResult, ExtractedPackage, PackageMetadata};
+
+use crate::version::VersionScheme;
+
+use std::path::Path;
+
+// Mods
+pub mod archive;
+pub mod native;
+pub mod plugin;
+pub mod registry;
+pub mod script;
+
+pub use plugin::{BackendPlugin, PluginBackend, PLUGIN_SYMBOL};
+pub use registry::BackendRegistry;
+
+// Number of leading bytes of a package file handed to `Backend::detect`
+pub const HEADER_LEN: usize = 512;
+
+// Trait every package format implements; the installer only sees the extracted result
+pub trait Backend: Send + Sync {
+    // Format name stored in ExtractedPackage.format
+    fn name(&self) -> &str;
+
+    // Version ordering of the format; plugins get the one matching their name
+    fn version_scheme(&self) -> VersionScheme {
+        VersionScheme::for_format(self.name())
+    }
+
+    // Function to recognise a package by its path and first HEADER_LEN bytes (fewer for short files)
+    fn detect(&self, path: &Path, header: &[u8]) -> bool;
+
+    // Function to read the package description without unpacking any files
+    fn read_metadata(&self, path: &Path) -> BackendResult<PackageMetadata>;
+
+    // Function to unpack package files into temp_dir; file_list is relative to temp_dir
+    fn extract(&self, path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage>;
+}
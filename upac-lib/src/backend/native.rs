@@ -0,0 +1,575 @@
+// Imports
+use super::archive::{self, path_to_stab, Compression};
+use super::{Backend, BackendError, BackendResult, ExtractedPackage, PackageMetadata};
+
+use crate::version::VersionScheme;
+
+use serde::{Deserialize, Serialize};
+
+use stabby::option::Option as StabOption;
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use tar::{Builder, Header, HeaderMode};
+
+use toml::{from_str, to_string_pretty};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const NATIVE_EXTENSION: &str = "upac";
+// First member of every .upac archive
+pub const MANIFEST_NAME: &str = ".MANIFEST.toml";
+// Manifest read from the staging directory when `upac build` gets none
+pub const BUILD_MANIFEST_NAME: &str = "upac.toml";
+
+// Version of the archive layout written by build_package
+const FORMAT_VERSION: u32 = 1;
+const ZSTD_LEVEL: i32 = 19;
+
+// Scripts of a native package, run like any other package scripts
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ManifestScripts {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_install: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_install: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_remove: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_remove: Option<String>,
+}
+
+// Manifest of a native package; checksums are filled in by build_package
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PackageManifest {
+    #[serde(default)]
+    pub format_version: u32,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub provides: Vec<String>,
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    // Configuration files, relative to the root
+    #[serde(default)]
+    pub config_files: Vec<String>,
+    #[serde(default)]
+    pub scripts: ManifestScripts,
+    // SHA-256 of every regular file, by path relative to the root
+    #[serde(default)]
+    pub checksums: BTreeMap<String, String>,
+}
+
+impl PackageManifest {
+    pub fn load(path: &Path) -> BackendResult<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> BackendResult<Self> {
+        let manifest: Self = from_str(content).map_err(|err| {
+            BackendError::Format(format!("Invalid package manifest: {err}").into())
+        })?;
+
+        if manifest.format_version > FORMAT_VERSION {
+            return Err(BackendError::Unsupported(
+                format!(
+                    "Package format version {} is newer than this upac",
+                    manifest.format_version
+                )
+                .into(),
+            ));
+        }
+
+        let valid_name = !manifest.name.is_empty()
+            && !manifest
+                .name
+                .contains(|char: char| char == '/' || char.is_whitespace());
+        if !valid_name || manifest.version.is_empty() {
+            return Err(BackendError::Format(
+                "Package manifest needs a name without slashes or spaces and a version".into(),
+            ));
+        }
+
+        Ok(manifest)
+    }
+
+    fn metadata(&self, format: &str) -> PackageMetadata {
+        PackageMetadata {
+            name: self.name.as_str().into(),
+            version: self.version.as_str().into(),
+            format: format.into(),
+            dependencies: to_stab_vec(&self.dependencies),
+            provides: to_stab_vec(&self.provides),
+            conflicts: to_stab_vec(&self.conflicts),
+        }
+    }
+}
+
+// Backend for upac's own format: a zstd compressed tar whose first member is the manifest
+pub struct NativeBackend;
+
+impl Backend for NativeBackend {
+    fn name(&self) -> &str {
+        NATIVE_EXTENSION
+    }
+
+    fn version_scheme(&self) -> VersionScheme {
+        VersionScheme::Alpm
+    }
+
+    fn detect(&self, path: &Path, header: &[u8]) -> bool {
+        path.extension()
+            .is_some_and(|extension| extension == NATIVE_EXTENSION)
+            && Compression::detect(header) == Compression::Zstd
+    }
+
+    fn read_metadata(&self, path: &Path) -> BackendResult<PackageMetadata> {
+        let content = archive::read_tar_member(
+            archive::decompress(File::open(path)?)?,
+            Path::new(MANIFEST_NAME),
+        )?
+        .ok_or_else(missing_manifest)?;
+
+        Ok(PackageManifest::parse(&String::from_utf8_lossy(&content))?.metadata(self.name()))
+    }
+
+    fn extract(&self, path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+        let mut manifest = None;
+
+        // Owners in the archive are not trusted, the files belong to whoever installs them
+        let file_list = archive::unpack_tar_with(
+            archive::decompress(File::open(path)?)?,
+            temp_dir,
+            false,
+            |member, reader| {
+                if member != Path::new(MANIFEST_NAME) {
+                    return Ok(false);
+                }
+
+                let mut content = String::new();
+                reader.read_to_string(&mut content)?;
+                manifest = Some(PackageManifest::parse(&content)?);
+                Ok(true)
+            },
+        )?;
+
+        let manifest = manifest.ok_or_else(missing_manifest)?;
+        verify_checksums(&manifest, &file_list, temp_dir)?;
+
+        let script = |script: &Option<String>| match script {
+            Some(script) => StabOption::Some(StabString::from(script.as_str())),
+            None => StabOption::None(),
+        };
+
+        let metadata = manifest.metadata(self.name());
+
+        Ok(ExtractedPackage {
+            name: metadata.name,
+            version: metadata.version,
+            format: metadata.format,
+            file_list,
+            dependencies: metadata.dependencies,
+            provides: metadata.provides,
+            conflicts: metadata.conflicts,
+            config_files: to_stab_vec(&manifest.config_files),
+            pre_install: script(&manifest.scripts.pre_install),
+            post_install: script(&manifest.scripts.post_install),
+            pre_remove: script(&manifest.scripts.pre_remove),
+            post_remove: script(&manifest.scripts.post_remove),
+        })
+    }
+}
+
+// Function to check that every unpacked regular file is listed in the manifest with its checksum
+fn verify_checksums(
+    manifest: &PackageManifest,
+    file_list: &StabVec<StabString>,
+    temp_dir: &Path,
+) -> BackendResult<()> {
+    let mut unchecked: BTreeSet<&str> = manifest.checksums.keys().map(String::as_str).collect();
+
+    for file in file_list.iter() {
+        let path = temp_dir.join(file.as_str());
+        if !fs::symlink_metadata(&path)?.is_file() {
+            continue;
+        }
+
+        let expected = manifest.checksums.get(file.as_str()).ok_or_else(|| {
+            BackendError::Format(format!("{file} is not listed in the manifest").into())
+        })?;
+
+        if &archive::sha256_file(&path)? != expected {
+            return Err(BackendError::Format(
+                format!("{file} does not match its manifest checksum").into(),
+            ));
+        }
+
+        unchecked.remove(file.as_str());
+    }
+
+    match unchecked.first() {
+        Some(file) => Err(BackendError::Format(
+            format!("{file} is listed in the manifest but missing").into(),
+        )),
+        None => Ok(()),
+    }
+}
+
+// Function to pack a staging directory into `<name>-<version>.upac` inside output_dir.
+// The manifest may live inside the staging directory, it is never packed as a file
+pub fn build_package(
+    staging_dir: &Path,
+    manifest_path: &Path,
+    output_dir: &Path,
+) -> BackendResult<PathBuf> {
+    let mut manifest = PackageManifest::load(manifest_path)?;
+    manifest.format_version = FORMAT_VERSION;
+
+    let skipped = fs::canonicalize(manifest_path)?;
+    let mut paths = Vec::new();
+    collect_paths(staging_dir, Path::new(""), &skipped, &mut paths)?;
+
+    manifest.checksums.clear();
+    for path in &paths {
+        let full_path = staging_dir.join(path);
+        if fs::symlink_metadata(&full_path)?.is_file() {
+            manifest.checksums.insert(
+                path_to_stab(path)?.to_string(),
+                archive::sha256_file(&full_path)?,
+            );
+        }
+    }
+
+    for config_file in &manifest.config_files {
+        if !manifest
+            .checksums
+            .contains_key(config_file.trim_start_matches('/'))
+        {
+            return Err(BackendError::Format(
+                format!("Config file {config_file} is not a regular file of the package").into(),
+            ));
+        }
+    }
+
+    let content = to_string_pretty(&manifest)
+        .map_err(|err| BackendError::Format(format!("Invalid package manifest: {err}").into()))?;
+
+    fs::create_dir_all(output_dir)?;
+    let package_path = output_dir.join(format!(
+        "{}-{}.{NATIVE_EXTENSION}",
+        manifest.name, manifest.version
+    ));
+    let partial_path = package_path.with_extension("part");
+
+    let result = write_archive(&partial_path, staging_dir, &content, &paths);
+    if let Err(err) = result {
+        let _ = fs::remove_file(&partial_path);
+        return Err(err);
+    }
+
+    fs::rename(&partial_path, &package_path)?;
+    Ok(package_path)
+}
+
+fn write_archive(
+    archive_path: &Path,
+    staging_dir: &Path,
+    manifest: &str,
+    paths: &[PathBuf],
+) -> BackendResult<()> {
+    let encoder = zstd::stream::write::Encoder::new(File::create(archive_path)?, ZSTD_LEVEL)?;
+    let mut builder = Builder::new(encoder);
+
+    let mut header = root_header();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, MANIFEST_NAME, manifest.as_bytes())?;
+
+    // Every entry belongs to root whoever builds the package, only the mode bits are kept
+    for path in paths {
+        let full_path = staging_dir.join(path);
+        let metadata = fs::symlink_metadata(&full_path)?;
+
+        let mut header = root_header();
+        header.set_metadata_in_mode(&metadata, HeaderMode::Complete);
+        header.set_uid(0);
+        header.set_gid(0);
+
+        if metadata.file_type().is_symlink() {
+            builder.append_link(&mut header, path, fs::read_link(&full_path)?)?;
+        } else if metadata.is_file() {
+            builder.append_data(&mut header, path, File::open(&full_path)?)?;
+        } else {
+            builder.append_data(&mut header, path, io::empty())?;
+        }
+    }
+
+    let file = builder.into_inner()?.finish()?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn root_header() -> Header {
+    let mut header = Header::new_gnu();
+    header.set_uid(0);
+    header.set_gid(0);
+    // Both names fit the header field, setting them cannot fail
+    let _ = header.set_username("root");
+    let _ = header.set_groupname("root");
+    header
+}
+
+// Function to list a staging directory depth first, in a stable order
+fn collect_paths(
+    staging_dir: &Path,
+    relative_dir: &Path,
+    skipped: &Path,
+    paths: &mut Vec<PathBuf>,
+) -> BackendResult<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(staging_dir.join(relative_dir))?
+        .map(|entry| entry.map(|entry| relative_dir.join(entry.file_name())))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        let full_path = staging_dir.join(&path);
+        let file_type = fs::symlink_metadata(&full_path)?.file_type();
+
+        if fs::canonicalize(&full_path).is_ok_and(|full_path| full_path == skipped)
+            && file_type.is_file()
+        {
+            continue;
+        }
+
+        if !(file_type.is_file() || file_type.is_dir() || file_type.is_symlink()) {
+            return Err(BackendError::Unsupported(
+                format!("Special file in staging directory: {}", path.display()).into(),
+            ));
+        }
+
+        paths.push(path.clone());
+
+        if file_type.is_dir() {
+            collect_paths(staging_dir, &path, skipped, paths)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn missing_manifest() -> BackendError {
+    BackendError::Format(format!("Package has no {MANIFEST_NAME}").into())
+}
+
+fn to_stab_vec(values: &[String]) -> StabVec<StabString> {
+    values
+        .iter()
+        .map(|value| StabString::from(value.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database::PackageDatabase;
+    use crate::installer::{InstallPolicy, Installer, PackageInstaller};
+
+    use nix::unistd::{Gid, Uid};
+
+    use tar::Archive;
+
+    use std::io::Read;
+    use std::os::unix::fs::{lchown, symlink, MetadataExt, PermissionsExt};
+
+    // Owner of the staged files, any user but the one running the tests
+    const BUILDER_ID: u32 = 1000;
+
+    // Function to stage `tool` 1.0 with a program, a link to it and a configuration file
+    fn stage(dir: &Path) -> PathBuf {
+        let staging_dir = dir.join("staging");
+        fs::create_dir_all(staging_dir.join("usr/bin")).unwrap();
+        fs::create_dir_all(staging_dir.join("etc")).unwrap();
+        fs::write(staging_dir.join("usr/bin/tool"), "tool").unwrap();
+        fs::set_permissions(
+            staging_dir.join("usr/bin/tool"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        symlink("tool", staging_dir.join("usr/bin/t")).unwrap();
+        fs::write(staging_dir.join("etc/tool.conf"), "conf").unwrap();
+        fs::write(
+            staging_dir.join(BUILD_MANIFEST_NAME),
+            "name = \"tool\"\n\
+             version = \"1.0-1\"\n\
+             dependencies = [\"libfoo>=1.0\"]\n\
+             config_files = [\"/etc/tool.conf\"]\n\
+             [scripts]\n\
+             post_install = \"echo installed\"\n",
+        )
+        .unwrap();
+        staging_dir
+    }
+
+    fn build(dir: &Path) -> PathBuf {
+        let staging_dir = stage(dir);
+        build_package(
+            &staging_dir,
+            &staging_dir.join(BUILD_MANIFEST_NAME),
+            &dir.join("out"),
+        )
+        .unwrap()
+    }
+
+    // Function to rewrite every member of a package archive
+    fn rewrite(package_path: &Path, mut edit: impl FnMut(&Path, &mut Header, &mut Vec<u8>)) {
+        let mut archive = Archive::new(archive::decompress(File::open(package_path).unwrap()).unwrap());
+        let mut builder = Builder::new(Vec::new());
+
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            let mut header = entry.header().clone();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+
+            edit(&path, &mut header, &mut content);
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append(&header, content.as_slice()).unwrap();
+        }
+
+        let data = builder.into_inner().unwrap();
+        fs::write(package_path, zstd::encode_all(data.as_slice(), 3).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn extracts_what_was_built() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_path = build(dir.path());
+        assert_eq!(package_path, dir.path().join("out/tool-1.0-1.upac"));
+
+        let metadata = NativeBackend.read_metadata(&package_path).unwrap();
+        assert_eq!(
+            (metadata.name.as_str(), metadata.version.as_str()),
+            ("tool", "1.0-1")
+        );
+        assert_eq!(metadata.dependencies[0].as_str(), "libfoo>=1.0");
+
+        let temp_dir = dir.path().join("temp");
+        let package = NativeBackend.extract(&package_path, &temp_dir).unwrap();
+
+        let files: Vec<&str> = package.file_list.iter().map(|file| file.as_str()).collect();
+        assert_eq!(
+            files,
+            ["etc", "etc/tool.conf", "usr", "usr/bin", "usr/bin/t", "usr/bin/tool"]
+        );
+        assert_eq!(package.config_files[0].as_str(), "/etc/tool.conf");
+        assert_eq!(
+            package.post_install.as_ref().map(|script| script.as_str()),
+            Some("echo installed")
+        );
+        assert_eq!(
+            fs::read_link(temp_dir.join("usr/bin/t")).unwrap(),
+            Path::new("tool")
+        );
+        assert_eq!(
+            fs::metadata(temp_dir.join("usr/bin/tool")).unwrap().mode() & 0o7777,
+            0o755
+        );
+    }
+
+    #[test]
+    fn rejects_files_not_matching_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_path = build(dir.path());
+
+        rewrite(&package_path, |path, _, content| {
+            if path == Path::new("usr/bin/tool") {
+                content.extend_from_slice(b"!");
+            }
+        });
+
+        let Err(err) = NativeBackend.extract(&package_path, &dir.path().join("temp")) else {
+            panic!("a changed file was extracted");
+        };
+        assert!(
+            err.to_string().contains("usr/bin/tool does not match its manifest checksum"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn built_archive_belongs_to_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let staging_dir = stage(dir.path());
+
+        // Run as root, the staged files are given to another user first
+        if Uid::effective().is_root() {
+            for path in ["usr", "usr/bin", "usr/bin/tool", "usr/bin/t", "etc", "etc/tool.conf"] {
+                lchown(staging_dir.join(path), Some(BUILDER_ID), Some(BUILDER_ID)).unwrap();
+            }
+        }
+
+        let package_path = build_package(
+            &staging_dir,
+            &staging_dir.join(BUILD_MANIFEST_NAME),
+            &dir.path().join("out"),
+        )
+        .unwrap();
+
+        let mut archive = Archive::new(archive::decompress(File::open(&package_path).unwrap()).unwrap());
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let header = entry.header();
+            let path = entry.path().unwrap().display().to_string();
+
+            assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0), "{path}");
+            assert_eq!(header.username().unwrap(), Some("root"), "{path}");
+            assert_eq!(header.groupname().unwrap(), Some("root"), "{path}");
+            if path == "usr/bin/tool" {
+                assert_eq!(header.mode().unwrap() & 0o7777, 0o755);
+            }
+        }
+    }
+
+    #[test]
+    fn installed_files_do_not_take_archive_owners() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_path = build(dir.path());
+
+        // An archive made by another tool may still name its builder
+        rewrite(&package_path, |_, header, _| {
+            header.set_uid(BUILDER_ID.into());
+            header.set_gid(BUILDER_ID.into());
+        });
+
+        for subdir in ["root", "repo", "temp", "db"] {
+            fs::create_dir(dir.path().join(subdir)).unwrap();
+        }
+        let path = |subdir: &str| dir.path().join(subdir).display().to_string();
+        let database = PackageDatabase::new(dir.path().join("db")).unwrap();
+        let mut installer =
+            PackageInstaller::with_repo_dir(path("root"), path("repo"), path("temp"), Box::new(database));
+
+        let package = NativeBackend
+            .extract(&package_path, &dir.path().join("temp"))
+            .unwrap();
+        let mut libfoo = crate::testing::package("libfoo", "1.0", &[]);
+        libfoo.format = NATIVE_EXTENSION.into();
+        installer.install(libfoo, InstallPolicy::default()).unwrap();
+        installer.install(package, InstallPolicy::default()).unwrap();
+
+        // Files belong to whoever installs them, root on a real system
+        let owner = (Uid::effective().as_raw(), Gid::effective().as_raw());
+        for file in ["usr/bin/tool", "usr/bin/t", "etc/tool.conf"] {
+            let metadata = fs::symlink_metadata(dir.path().join("root").join(file)).unwrap();
+            assert_eq!((metadata.uid(), metadata.gid()), owner, "{file}");
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// Imports
+use super::{Backend, BackendError, BackendRegistry, BackendResult};
+use super::{ExtractedPackage, PackageMetadata};
+
+use upac_types::BackendStabbyResult;
+
+use stabby::boxed::Box as StabBox;
+use stabby::libloading::StabbyLibrary;
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use libloading::Library;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Symbol every plugin exports through `export_backend!`
+pub const PLUGIN_SYMBOL: &[u8] = b"upac_backend_plugin";
+// Type report stabby exports next to the entry point
+const PLUGIN_REPORT_SYMBOL: &[u8] = b"upac_backend_plugin_stabbied_v3_report";
+
+type PluginEntry = extern "C" fn() -> BackendPlugin;
+
+// Table of functions a plugin hands over; its stabby report covers every argument and result type
+#[stabby::stabby]
+pub struct BackendPlugin {
+    pub name: extern "C" fn() -> StabString,
+    pub detect: extern "C" fn(StabString, StabVec<u8>) -> bool,
+    // Results are boxed so their layout stays simple for stabby to compute
+    pub read_metadata: extern "C" fn(StabString) -> BackendStabbyResult<StabBox<PackageMetadata>>,
+    pub extract:
+        extern "C" fn(StabString, StabString) -> BackendStabbyResult<StabBox<ExtractedPackage>>,
+}
+
+// Backend implemented by a shared object; the library stays loaded as long as the backend lives
+pub struct PluginBackend {
+    name: String,
+    plugin: BackendPlugin,
+    path: PathBuf,
+    _library: Library,
+}
+
+impl PluginBackend {
+    // Function to load a plugin, rejecting it when its types do not match the ones of this build
+    pub fn load(path: &Path) -> BackendResult<Self> {
+        let plugin_error = |err: &dyn std::fmt::Display| {
+            BackendError::Plugin(format!("{}: {err}", path.display()).into())
+        };
+
+        let library = unsafe { Library::new(path) }.map_err(|err| plugin_error(&err))?;
+
+        let plugin = match unsafe { library.get_stabbied::<PluginEntry>(PLUGIN_SYMBOL) } {
+            Ok(entry) => entry(),
+            // A plugin exporting the entry point with other types still exports its type report
+            Err(_) if unsafe { library.get::<*const ()>(PLUGIN_REPORT_SYMBOL) }.is_ok() => {
+                return Err(plugin_error(
+                    &"built against incompatible upac types, rebuild it for this version",
+                ))
+            }
+            Err(err) => return Err(plugin_error(&err)),
+        };
+
+        Ok(Self {
+            name: (plugin.name)().to_string(),
+            plugin,
+            path: path.to_path_buf(),
+            _library: library,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Backend for PluginBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, path: &Path, header: &[u8]) -> bool {
+        (self.plugin.detect)(path_to_stab(path), header.iter().copied().collect())
+    }
+
+    fn read_metadata(&self, path: &Path) -> BackendResult<PackageMetadata> {
+        BackendResult::from((self.plugin.read_metadata)(path_to_stab(path)))
+            .map(StabBox::into_inner)
+    }
+
+    fn extract(&self, path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+        BackendResult::from((self.plugin.extract)(
+            path_to_stab(path),
+            path_to_stab(temp_dir),
+        ))
+        .map(StabBox::into_inner)
+    }
+}
+
+impl BackendRegistry {
+    // Function to register every plugin in a directory; rejected plugins are returned, not fatal
+    pub fn load_plugins(&mut self, plugin_dir: &Path) -> BackendResult<Vec<BackendError>> {
+        let mut rejected = Vec::new();
+
+        if !plugin_dir.exists() {
+            return Ok(rejected);
+        }
+
+        let mut plugin_paths: Vec<PathBuf> = fs::read_dir(plugin_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        plugin_paths.retain(|path| path.extension().is_some_and(|extension| extension == "so"));
+        plugin_paths.sort();
+
+        for plugin_path in plugin_paths {
+            match PluginBackend::load(&plugin_path) {
+                Ok(backend) => self.register(Box::new(backend)),
+                Err(err) => rejected.push(err),
+            }
+        }
+
+        Ok(rejected)
+    }
+}
+
+fn path_to_stab(path: &Path) -> StabString {
+    path.to_string_lossy().as_ref().into()
+}
+
+// Helpers called by the code `export_backend!` generates inside a plugin
+#[doc(hidden)]
+pub fn plugin_detect(backend: &dyn Backend, path: StabString, header: StabVec<u8>) -> bool {
+    backend.detect(Path::new(path.as_str()), &header)
+}
+
+#[doc(hidden)]
+pub fn plugin_read_metadata(
+    backend: &dyn Backend,
+    path: StabString,
+) -> BackendStabbyResult<StabBox<PackageMetadata>> {
+    backend
+        .read_metadata(Path::new(path.as_str()))
+        .map(StabBox::new)
+        .into()
+}
+
+#[doc(hidden)]
+pub fn plugin_extract(
+    backend: &dyn Backend,
+    path: StabString,
+    temp_dir: StabString,
+) -> BackendStabbyResult<StabBox<ExtractedPackage>> {
+    backend
+        .extract(Path::new(path.as_str()), Path::new(temp_dir.as_str()))
+        .map(StabBox::new)
+        .into()
+}
+
+// Macro turning a `Backend` into a loadable plugin; the plugin crate must be a cdylib depending on stabby
+#[macro_export]
+macro_rules! export_backend {
+    ($backend:expr) => {
+        fn __upac_backend() -> &'static dyn $crate::Backend {
+            static BACKEND: ::std::sync::OnceLock<::std::boxed::Box<dyn $crate::Backend>> =
+                ::std::sync::OnceLock::new();
+            BACKEND
+                .get_or_init(|| ::std::boxed::Box::new($backend))
+                .as_ref()
+        }
+
+        extern "C" fn __upac_backend_name() -> ::stabby::string::String {
+            __upac_backend().name().into()
+        }
+
+        extern "C" fn __upac_backend_detect(
+            path: ::stabby::string::String,
+            header: ::stabby::vec::Vec<u8>,
+        ) -> bool {
+            $crate::plugin_detect(__upac_backend(), path, header)
+        }
+
+        extern "C" fn __upac_backend_read_metadata(
+            path: ::stabby::string::String,
+        ) -> ::upac_types::BackendStabbyResult<::stabby::boxed::Box<::upac_types::PackageMetadata>>
+        {
+            $crate::plugin_read_metadata(__upac_backend(), path)
+        }
+
+        extern "C" fn __upac_backend_extract(
+            path: ::stabby::string::String,
+            temp_dir: ::stabby::string::String,
+        ) -> ::upac_types::BackendStabbyResult<::stabby::boxed::Box<::upac_types::ExtractedPackage>>
+        {
+            $crate::plugin_extract(__upac_backend(), path, temp_dir)
+        }
+
+        #[::stabby::export]
+        pub extern "C" fn upac_backend_plugin() -> $crate::BackendPlugin {
+            $crate::BackendPlugin {
+                name: __upac_backend_name,
+                detect: __upac_backend_detect,
+                read_metadata: __upac_backend_read_metadata,
+                extract: __upac_backend_extract,
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_files_that_are_not_plugins() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("broken.so"), "not a shared object").unwrap();
+        fs::write(dir.path().join("README"), "plugins go here").unwrap();
+
+        let mut backends = BackendRegistry::new();
+        let rejected = backends.load_plugins(dir.path()).unwrap();
+
+        assert_eq!(rejected.len(), 1);
+        assert!(rejected[0].to_string().contains("broken.so"), "{}", rejected[0]);
+        assert_eq!(backends.backends().count(), 0);
+
+        // A missing plugin directory is the same as an empty one
+        assert!(backends
+            .load_plugins(&dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+// Imports
+use super::{Backend, BackendError, BackendResult, HEADER_LEN};
+use super::{ExtractedPackage, PackageMetadata};
+
+use crate::signature::SignatureVerifier;
+use crate::version::{VersionScheme, VersionSchemes};
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+// Ordered set of backends; the first one recognising a file handles it
+#[derive(Default)]
+pub struct BackendRegistry {
+    backends: Vec<Box<dyn Backend>>,
+    // Signature check run before extraction; none means signatures are not looked at
+    verifier: Option<SignatureVerifier>,
+}
+
+impl BackendRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, backend: Box<dyn Backend>) {
+        self.backends.push(backend);
+    }
+
+    pub fn set_verifier(&mut self, verifier: SignatureVerifier) {
+        self.verifier = Some(verifier);
+    }
+
+    pub fn verifier(&self) -> Option<&SignatureVerifier> {
+        self.verifier.as_ref()
+    }
+
+    pub fn backends(&self) -> impl Iterator<Item = &dyn Backend> {
+        self.backends.iter().map(|backend| backend.as_ref())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Backend> {
+        self.backends().find(|backend| backend.name() == name)
+    }
+
+    // Function to get the version ordering of a format, guessed from its name when no backend has it
+    pub fn version_scheme(&self, format: &str) -> VersionScheme {
+        self.version_schemes().get(format)
+    }
+
+    // Function to collect the version ordering of every registered format, for code that has no
+    // registry at hand like the installer and the repositories
+    pub fn version_schemes(&self) -> VersionSchemes {
+        let mut schemes = VersionSchemes::new();
+        // Last to first, so the first backend of a name wins as in `get`
+        for backend in self.backends.iter().rev() {
+            schemes.insert(backend.name(), backend.version_scheme());
+        }
+        schemes
+    }
+
+    // Function to pick the backend for a package file
+    pub fn detect(&self, path: &Path) -> BackendResult<&dyn Backend> {
+        self.find(path)?
+            .ok_or_else(|| BackendError::Unsupported(path.display().to_string().into()))
+    }
+
+    // Function to pick the backend for a file that may not be a package at all
+    pub fn find(&self, path: &Path) -> BackendResult<Option<&dyn Backend>> {
+        let header = Self::read_header(path)?;
+
+        Ok(self
+            .backends()
+            .find(|backend| backend.detect(path, &header)))
+    }
+
+    pub fn read_metadata(&self, path: &Path) -> BackendResult<PackageMetadata> {
+        self.detect(path)?.read_metadata(path)
+    }
+
+    pub fn extract(&self, path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+        let backend = self.detect(path)?;
+
+        if let Some(verifier) = &self.verifier {
+            verifier.verify(path)?;
+        }
+
+        fs::create_dir_all(temp_dir)?;
+        backend.extract(path, temp_dir)
+    }
+
+    fn read_header(path: &Path) -> BackendResult<Vec<u8>> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        File::open(path)?
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::package;
+
+    // Backend for files starting with its own name
+    struct MagicBackend(&'static str);
+
+    impl Backend for MagicBackend {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn detect(&self, _path: &Path, header: &[u8]) -> bool {
+            header.starts_with(self.0.as_bytes())
+        }
+
+        fn read_metadata(&self, _path: &Path) -> BackendResult<PackageMetadata> {
+            Ok(package(self.0, "1.0", &[]).metadata())
+        }
+
+        fn extract(&self, _path: &Path, temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+            fs::write(temp_dir.join("file"), self.0)?;
+            Ok(package(self.0, "1.0", &["file"]))
+        }
+    }
+
+    // Backend recognising nothing, only there to state a version scheme
+    struct SchemeBackend(&'static str, VersionScheme);
+
+    impl Backend for SchemeBackend {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn version_scheme(&self) -> VersionScheme {
+            self.1
+        }
+
+        fn detect(&self, _path: &Path, _header: &[u8]) -> bool {
+            false
+        }
+
+        fn read_metadata(&self, _path: &Path) -> BackendResult<PackageMetadata> {
+            Err(BackendError::Unsupported(self.0.into()))
+        }
+
+        fn extract(&self, _path: &Path, _temp_dir: &Path) -> BackendResult<ExtractedPackage> {
+            Err(BackendError::Unsupported(self.0.into()))
+        }
+    }
+
+    #[test]
+    fn picks_the_backend_recognising_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut backends = BackendRegistry::new();
+        backends.register(Box::new(MagicBackend("first")));
+        backends.register(Box::new(MagicBackend("second")));
+
+        fs::write(dir.path().join("package"), "second package").unwrap();
+        fs::write(dir.path().join("other"), "unknown").unwrap();
+
+        let package_path = dir.path().join("package");
+        assert_eq!(backends.detect(&package_path).unwrap().name(), "second");
+        assert_eq!(
+            backends.read_metadata(&package_path).unwrap().name.as_str(),
+            "second"
+        );
+
+        // The temp directory is created for the backend
+        let temp_dir = dir.path().join("temp/package");
+        let extracted = backends.extract(&package_path, &temp_dir).unwrap();
+        assert_eq!(extracted.file_list.len(), 1);
+        assert_eq!(fs::read_to_string(temp_dir.join("file")).unwrap(), "second");
+
+        let other_path = dir.path().join("other");
+        assert!(backends.find(&other_path).unwrap().is_none());
+        assert!(backends.detect(&other_path).is_err());
+    }
+
+    #[test]
+    fn backends_state_the_version_scheme_of_their_format() {
+        let mut backends = BackendRegistry::new();
+        backends.register(Box::new(SchemeBackend("slack", VersionScheme::Rpm)));
+        backends.register(Box::new(SchemeBackend("slack", VersionScheme::Dpkg)));
+        backends.register(Box::new(SchemeBackend("deb", VersionScheme::Alpm)));
+
+        // The first backend of a name wins, as it does for extraction
+        let schemes = backends.version_schemes();
+        assert_eq!(schemes.get("slack"), VersionScheme::Rpm);
+        assert_eq!(backends.version_scheme("deb"), VersionScheme::Alpm);
+
+        // Formats no backend handles fall back to their name
+        assert_eq!(schemes.get("rpm"), VersionScheme::Rpm);
+        assert_eq!(schemes.get("upac"), VersionScheme::Alpm);
+    }
+}
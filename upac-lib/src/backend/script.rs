@@ -0,0 +1,30 @@
+// Imports
+use stabby::string::String as StabString;
+
+// Heredoc delimiter a foreign script is embedded with
+const SCRIPT_DELIMITER: &str = "UPAC_EMBEDDED_SCRIPT";
+
+// Function to turn a script of another package format into a upac script: `arguments` rewrites
+// the positional parameters (`set -- ...`), then the original runs with `interpreter`, or on its
+// own through its shebang when there is none
+pub fn embed_script(content: &str, interpreter: Option<&str>, arguments: &str) -> StabString {
+    let run = match interpreter {
+        Some(interpreter) => format!("{interpreter} \"$script\" \"$@\""),
+        None => "chmod 700 \"$script\"\n\"$script\" \"$@\"".to_string(),
+    };
+
+    format!(
+        "{arguments}\n\
+         script=$(mktemp) || exit 1\n\
+         cat > \"$script\" <<'{SCRIPT_DELIMITER}'\n\
+         {}\n\
+         {SCRIPT_DELIMITER}\n\
+         {run}\n\
+         status=$?\n\
+         rm -f \"$script\"\n\
+         exit $status\n",
+        content.trim_end()
+    )
+    .as_str()
+    .into()
+}
@@ -60,7 +60,7 @@ impl OSTree for OSTreeManager {
             Some(subject),
             Some(&body),
             None,
-            &root,
+            root,
             Cancellable::NONE,
         )?;
 
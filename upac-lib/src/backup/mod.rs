@@ -5,7 +5,7 @@ use std::path::Path;
 
 pub mod backup;
 
-pub(crate) trait OSTree {
+pub trait OSTree {
     fn commit(
         &self,
         repo_path: &Path,
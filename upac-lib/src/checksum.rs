@@ -0,0 +1,23 @@
+// Imports
+use sha2::{Digest, Sha256};
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+// Function to get the SHA-256 of a file as lowercase hex
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+// Function to get the SHA-256 of a byte string as lowercase hex
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
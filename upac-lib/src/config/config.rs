@@ -1,14 +1,21 @@
 // Imports
 use super::{ConfigError, ConfigResult, Config};
 
+use crate::database::DatabaseBackend;
+use crate::signature::{SignaturePolicy, DEFAULT_KEYRING_DIR};
+
 use toml::Value;
 
+use toml_edit::{value, Array, DocumentMut, TomlError};
+
 use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
 use stabby::result::Result as StabResult;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ffi::c_void;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
 
 // Default paths
 const DEFAULT_PACKAGE_DIR: &str = "/var/lib/upac/packages";
@@ -17,6 +24,8 @@ const DEFAULT_DATABASE_PATH: &str = "/var/lib/upac/db";
 const DEFAULT_REPO_PATH: &str = "/var/lib/upac/repo";
 const DEFAULT_TEMP_DIR: &str = "/tmp/upac";
 const DEFAULT_ROOT_DIR: &str = "/";
+const DEFAULT_PLUGIN_DIR: &str = "/usr/lib/upac/plugins";
+const DEFAULT_CACHE_DIR: &str = "/var/cache/upac";
 
 // Config for OStree
 #[stabby::stabby]
@@ -34,7 +43,19 @@ pub struct UpacConfig {
     pub package_dir:   StabString,
     pub temp_dir:      StabString,
     pub root_dir:      StabString,
+    // Directory of backend plugins (*.so) loaded at startup
+    pub plugin_dir:    StabString,
+    // Directory of trusted minisign public keys (*.pub)
+    pub keyring_dir:   StabString,
+    // Directory of synced repository indexes and downloaded packages
+    pub cache_dir:     StabString,
     pub ostree:        OStreeConfig,
+    // Globs of paths, relative to the root, treated as protected configuration files
+    pub config_files:  StabVec<StabString>,
+    // Repository URLs (file:// or http://), searched in order
+    pub repositories:  StabVec<StabString>,
+    pub database_backend: DatabaseBackend,
+    pub signature_policy: SignaturePolicy,
 }
 
 
@@ -56,7 +77,14 @@ impl Default for UpacConfig {
             package_dir:   StabString::from(DEFAULT_PACKAGE_DIR),
             temp_dir:      StabString::from(DEFAULT_TEMP_DIR),
             root_dir:      StabString::from(DEFAULT_ROOT_DIR),
+            plugin_dir:    StabString::from(DEFAULT_PLUGIN_DIR),
+            keyring_dir:   StabString::from(DEFAULT_KEYRING_DIR),
+            cache_dir:     StabString::from(DEFAULT_CACHE_DIR),
             ostree:        OStreeConfig::default(),
+            config_files:  StabVec::new(),
+            repositories:  StabVec::new(),
+            database_backend: DatabaseBackend::default(),
+            signature_policy: SignaturePolicy::default(),
         }
     }
 }
@@ -69,6 +97,77 @@ impl UpacConfig {
 	fn get_nested_str<'a>(value: &'a Value, section: &str, key: &str) -> ConfigResult<&'a str> {
     	value[section][key].as_str().ok_or_else(|| ConfigError::ParseError(format!("missing field: {section}.{key}").into()))
 	}
+
+	// Function to parse the optional database backend; TOML stays the default
+	fn get_database_backend(value: &Value) -> ConfigResult<DatabaseBackend> {
+		match value.get("database_backend").map(|backend| backend.as_str()) {
+			None                 => Ok(DatabaseBackend::Toml),
+			Some(Some("toml"))   => Ok(DatabaseBackend::Toml),
+			Some(Some("sqlite")) => Ok(DatabaseBackend::Sqlite),
+			Some(_)              => Err(ConfigError::ParseError("database_backend must be \"toml\" or \"sqlite\"".into())),
+		}
+	}
+
+	// Function to parse the optional signature policy; signatures are checked when present by default
+	fn get_signature_policy(value: &Value) -> ConfigResult<SignaturePolicy> {
+		match value.get("signature_policy").map(|policy| policy.as_str()) {
+			None                   => Ok(SignaturePolicy::Optional),
+			Some(Some("required")) => Ok(SignaturePolicy::Required),
+			Some(Some("optional")) => Ok(SignaturePolicy::Optional),
+			Some(Some("never"))    => Ok(SignaturePolicy::Never),
+			Some(_)                => Err(ConfigError::ParseError("signature_policy must be \"required\", \"optional\" or \"never\"".into())),
+		}
+	}
+
+	// Function to parse an optional array of strings, like the config file globs
+	fn get_string_array(value: &Value, key: &str) -> ConfigResult<StabVec<StabString>> {
+		let Some(strings) = value.get(key) else {
+			return Ok(StabVec::new());
+		};
+
+		let error = || ConfigError::ParseError(format!("{key} must be an array of strings").into());
+
+		strings
+			.as_array()
+			.ok_or_else(error)?
+			.iter()
+			.map(|string| string.as_str().map(StabString::from).ok_or_else(error))
+			.collect()
+	}
+
+	// Function to write the repository list back to the config file; only the `repositories` array
+	// changes, comments and every other key stay as they are
+	pub fn save_repositories(&self) -> ConfigResult<()> {
+		self.save_repositories_to(Path::new(DEFAULT_CONFIG_PATH))
+	}
+
+	pub(crate) fn save_repositories_to(&self, path: &Path) -> ConfigResult<()> {
+		let content = match fs::read_to_string(path) {
+			Ok(content) => content,
+			Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+			Err(err) => return Err(err.into()),
+		};
+		let mut document: DocumentMut = content.parse().map_err(|err: TomlError| ConfigError::ParseError(err.to_string().into()))?;
+
+		let repositories: Array = self.repositories.iter().map(|repository| repository.as_str()).collect();
+		document["repositories"] = value(repositories);
+
+		// Written aside and renamed, so a crash never leaves half a config behind
+		let partial_path = path.with_extension("toml.part");
+		let written = File::create(&partial_path)
+			.and_then(|mut file| {
+				file.write_all(document.to_string().as_bytes())?;
+				file.sync_all()
+			})
+			.and_then(|()| fs::rename(&partial_path, path));
+
+		if let Err(err) = written {
+			let _ = fs::remove_file(&partial_path);
+			return Err(err.into());
+		}
+
+		Ok(())
+	}
 }
 
 // Implementation Config for UpacConfig
@@ -88,10 +187,17 @@ impl Config for UpacConfig {
             package_dir:   Self::get_str(&value, "package_dir")?.into(),
             temp_dir:      Self::get_str(&value, "temp_dir")?.into(),
             root_dir:      Self::get_str(&value, "root_dir")?.into(),
+            plugin_dir:    value.get("plugin_dir").and_then(Value::as_str).unwrap_or(DEFAULT_PLUGIN_DIR).into(),
+            keyring_dir:   value.get("keyring_dir").and_then(Value::as_str).unwrap_or(DEFAULT_KEYRING_DIR).into(),
+            cache_dir:     value.get("cache_dir").and_then(Value::as_str).unwrap_or(DEFAULT_CACHE_DIR).into(),
             ostree: OStreeConfig {
                 enabled:   value["ostree"]["enabled"].as_bool().unwrap_or(false),
                 repo_path: Self::get_nested_str(&value, "ostree", "repo_path")?.into(),
             },
+            config_files:  Self::get_string_array(&value, "config_files")?,
+            repositories:  Self::get_string_array(&value, "repositories")?,
+            database_backend: Self::get_database_backend(&value)?,
+            signature_policy: Self::get_signature_policy(&value)?,
         })
     }
 
@@ -122,6 +228,21 @@ impl Config for UpacConfig {
         	return Err(ConfigError::PathError(self.temp_dir.clone()));
         }
 
+        let plugin_dir_path = PathBuf::from(self.plugin_dir.as_str());
+        if !plugin_dir_path.is_absolute() {
+            return Err(ConfigError::PathError(self.plugin_dir.clone()));
+        }
+
+        let keyring_dir_path = PathBuf::from(self.keyring_dir.as_str());
+        if !keyring_dir_path.is_absolute() {
+            return Err(ConfigError::PathError(self.keyring_dir.clone()));
+        }
+
+        let cache_dir_path = PathBuf::from(self.cache_dir.as_str());
+        if !cache_dir_path.is_absolute() {
+            return Err(ConfigError::PathError(self.cache_dir.clone()));
+        }
+
         let ostree_repo_path = PathBuf::from(self.ostree.repo_path.as_str());
         if self.ostree.enabled && !ostree_repo_path.is_absolute() {
             return Err(ConfigError::PathError(self.ostree.repo_path.clone()));
@@ -150,3 +271,50 @@ pub extern "C" fn upac_config_free(config: *mut c_void) {
         unsafe { drop(Box::from_raw(config as *mut UpacConfig)) };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_repositories_keeps_the_rest_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "# Managed by hand\n\
+             root_dir = \"/\"  # the live system\n\
+             repositories = [\"file:///old\"]\n\
+             \n\
+             [ostree]\n\
+             enabled = false\n",
+        )
+        .unwrap();
+
+        let config = UpacConfig {
+            repositories: ["file:///srv/repo", "https://example.org/repo"]
+                .into_iter()
+                .map(StabString::from)
+                .collect(),
+            ..UpacConfig::default()
+        };
+        config.save_repositories_to(&path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Managed by hand\n\
+             root_dir = \"/\"  # the live system\n\
+             repositories = [\"file:///srv/repo\", \"https://example.org/repo\"]\n\
+             \n\
+             [ostree]\n\
+             enabled = false\n"
+        );
+        assert!(!path.with_extension("toml.part").exists());
+
+        // A missing file gets just the list
+        let path = dir.path().join("new.toml");
+        config.save_repositories_to(&path).unwrap();
+        let value: Value = fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(value["repositories"].as_array().unwrap().len(), 2);
+    }
+}
@@ -1,15 +1,22 @@
 // Imports
-use super::{Database, DatabaseError, DatabaseResult};
-use super::{ExtractedPackage, Package};
+use super::journal::{write_durable, JOURNAL_FILE_NAME};
+use super::{Database, DatabaseError, DatabaseResult, Journal, RecoveryReport};
+use super::{ExtractedPackage, FileEntry, InstallReason, Package, PackageScripts};
 
 use crate::lock::{ExclusiveLock, Lock, SharedLock};
 
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+
+use stabby::str::Str as StabStr;
+use stabby::string::String as StabString;
 
 use toml::{from_str, to_string_pretty};
 
+use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
+use upac_types::{InstallerError, InstallerStabbyResult};
+
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -19,18 +26,48 @@ const DATABASE_LOCK_FILE_NAME: &str = "database.lock";
 const PACKAGE_DIR_NAME: &str = "packages";
 
 const PACKAGES_MAP_FILE_NAME: &str = "packages_map.toml";
+const FILES_INDEX_FILE_NAME: &str = "files_index.toml";
 const FILES_TOML_FILE_NAME: &str = "files.toml";
+const SCRIPTS_TOML_FILE_NAME: &str = "scripts.toml";
 
 // Struct definition for database
 #[derive(Debug, Clone)]
 pub struct PackageDatabase {
     database_path: PathBuf,
     packages_map: HashMap<String, Package>,
+    files_index: HashMap<PathBuf, Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct FileList {
-    files: Vec<PathBuf>,
+    #[serde(deserialize_with = "deserialize_file_entries")]
+    files: Vec<FileEntry>,
+}
+
+// Packages registered before metadata was stored list bare paths
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredFile {
+    Path(PathBuf),
+    Entry(FileEntry),
+}
+
+fn deserialize_file_entries<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<FileEntry>, D::Error> {
+    Ok(Vec::<StoredFile>::deserialize(deserializer)?
+        .into_iter()
+        .map(|file| match file {
+            StoredFile::Path(path) => FileEntry::from(path),
+            StoredFile::Entry(entry) => entry,
+        })
+        .collect())
+}
+
+// Reverse index from file path to the packages owning it; directories may have several owners
+#[derive(Serialize, Deserialize, Default)]
+struct FilesIndex {
+    files: HashMap<PathBuf, Vec<String>>,
 }
 
 // Implementation of Database struct own functions
@@ -40,21 +77,204 @@ impl PackageDatabase {
         Self::ensure_directory(&database_path)?;
         Self::ensure_directory(&database_path.join(PACKAGE_DIR_NAME))?;
 
+        // A mutation interrupted by a crash is finished or dropped before anything is read
+        if database_path.join(JOURNAL_FILE_NAME).exists() {
+            let lock = ExclusiveLock::new(database_path.join(DATABASE_LOCK_FILE_NAME));
+            let _guard = lock.lock()?;
+            Journal::recover(&database_path)?;
+        }
+
         let packages_map: HashMap<String, Package> = {
             let packages_map_file = database_path.join(PACKAGES_MAP_FILE_NAME);
             if packages_map_file.exists() {
                 Self::read_toml(&packages_map_file)?
             } else {
-                HashMap::default()
+                let packages_map = HashMap::default();
+                Self::write_toml(&packages_map_file, &packages_map)?;
+                packages_map
             }
         };
 
+        // Databases created before the index existed get it rebuilt once
+        let files_index_file = database_path.join(FILES_INDEX_FILE_NAME);
+        let files_index = if files_index_file.exists() {
+            Self::read_toml::<FilesIndex>(&files_index_file)?.files
+        } else {
+            let files_index = Self::build_files_index(&database_path, &packages_map)?;
+            Self::write_toml(
+                &files_index_file,
+                &FilesIndex {
+                    files: files_index.clone(),
+                },
+            )?;
+            files_index
+        };
+
         Ok(Self {
             database_path,
             packages_map,
+            files_index,
+        })
+    }
+
+    // Function to build the reverse file index from every package's files.toml
+    fn build_files_index(
+        database_path: &Path,
+        packages_map: &HashMap<String, Package>,
+    ) -> DatabaseResult<HashMap<PathBuf, Vec<String>>> {
+        let mut files_index: HashMap<PathBuf, Vec<String>> = HashMap::new();
+
+        for package_name in packages_map.keys() {
+            let package_files_file_path = database_path
+                .join(PACKAGE_DIR_NAME)
+                .join(package_name)
+                .join(FILES_TOML_FILE_NAME);
+
+            if !package_files_file_path.exists() {
+                continue;
+            }
+
+            let file_list: FileList = Self::read_toml(&package_files_file_path)?;
+            for file in file_list.files {
+                files_index
+                    .entry(file.path)
+                    .or_default()
+                    .push(package_name.clone());
+            }
+        }
+
+        Ok(files_index)
+    }
+
+    // Function to record a package as owner of a file in the reverse index
+    fn index_file(
+        files_index: &mut HashMap<PathBuf, Vec<String>>,
+        file_path: &Path,
+        package_id: &str,
+    ) {
+        let owners = files_index.entry(file_path.to_path_buf()).or_default();
+        if !owners.iter().any(|owner| owner == package_id) {
+            owners.push(package_id.to_string());
+        }
+    }
+
+    // Function to drop a package from the owners of a file in the reverse index
+    fn unindex_file(
+        files_index: &mut HashMap<PathBuf, Vec<String>>,
+        file_path: &Path,
+        package_id: &str,
+    ) {
+        if let Some(owners) = files_index.get_mut(file_path) {
+            owners.retain(|owner| owner != package_id);
+            if owners.is_empty() {
+                files_index.remove(file_path);
+            }
+        }
+    }
+
+    // Function to get the path of a package file relative to the database directory
+    fn package_file_path(package_id: &str, file_name: &str) -> PathBuf {
+        Path::new(PACKAGE_DIR_NAME).join(package_id).join(file_name)
+    }
+
+    // Function to journal the package map and file index, commit, and only then adopt the new state
+    fn commit(
+        &mut self,
+        mut journal: Journal,
+        packages_map: HashMap<String, Package>,
+        files_index: HashMap<PathBuf, Vec<String>>,
+    ) -> DatabaseResult<()> {
+        journal.write(PathBuf::from(PACKAGES_MAP_FILE_NAME), &packages_map)?;
+        journal.write(
+            PathBuf::from(FILES_INDEX_FILE_NAME),
+            &FilesIndex {
+                files: files_index.clone(),
+            },
+        )?;
+        journal.commit(&self.database_path)?;
+
+        self.packages_map = packages_map;
+        self.files_index = files_index;
+
+        Ok(())
+    }
+
+    // Function to finish an interrupted mutation and repair packages left half-registered
+    pub fn recover(database_path: PathBuf) -> DatabaseResult<RecoveryReport> {
+        Self::ensure_directory(&database_path)?;
+        Self::ensure_directory(&database_path.join(PACKAGE_DIR_NAME))?;
+
+        let lock = ExclusiveLock::new(database_path.join(DATABASE_LOCK_FILE_NAME));
+        let _guard = lock.lock()?;
+
+        let journal_state = Journal::recover(&database_path)?;
+
+        let packages_map_file = database_path.join(PACKAGES_MAP_FILE_NAME);
+        let mut packages_map: HashMap<String, Package> = if packages_map_file.exists() {
+            Self::read_toml(&packages_map_file)?
+        } else {
+            HashMap::default()
+        };
+
+        let mut journal = Journal::new();
+
+        // Package directories without a map entry are leftovers of an interrupted add or remove
+        let mut orphaned_packages = Vec::new();
+        for entry in fs::read_dir(database_path.join(PACKAGE_DIR_NAME))? {
+            let entry = entry?;
+            let package_id = entry.file_name().to_string_lossy().into_owned();
+            if packages_map.contains_key(&package_id) || !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(entry.path())? {
+                journal.remove_file(Self::package_file_path(
+                    &package_id,
+                    &file?.file_name().to_string_lossy(),
+                ));
+            }
+            journal.remove_dir(Path::new(PACKAGE_DIR_NAME).join(&package_id));
+            orphaned_packages.push(package_id);
+        }
+
+        // Map entries without a file list cannot be removed cleanly and are dropped
+        let mut missing_packages: Vec<String> = packages_map
+            .keys()
+            .filter(|package_id| {
+                !database_path
+                    .join(Self::package_file_path(package_id, FILES_TOML_FILE_NAME))
+                    .exists()
+            })
+            .cloned()
+            .collect();
+        for package_id in &missing_packages {
+            packages_map.remove(package_id);
+        }
+
+        let files_index = Self::build_files_index(&database_path, &packages_map)?;
+
+        journal.write(PathBuf::from(PACKAGES_MAP_FILE_NAME), &packages_map)?;
+        journal.write(
+            PathBuf::from(FILES_INDEX_FILE_NAME),
+            &FilesIndex { files: files_index },
+        )?;
+        journal.commit(&database_path)?;
+
+        orphaned_packages.sort();
+        missing_packages.sort();
+
+        Ok(RecoveryReport {
+            orphaned_packages: orphaned_packages.into_iter().map(StabString::from).collect(),
+            missing_packages: missing_packages.into_iter().map(StabString::from).collect(),
+            journal: journal_state,
         })
     }
 
+    // Function to check whether a TOML database was already created at the path
+    pub(super) fn is_initialized(database_path: &Path) -> bool {
+        database_path.join(PACKAGES_MAP_FILE_NAME).exists()
+    }
+
     // Function to ensure a directory exists, creating it if necessary
     pub(super) fn ensure_directory(path: &Path) -> DatabaseResult<()> {
         if path.exists() {
@@ -77,15 +297,17 @@ impl PackageDatabase {
     // Function to write a TOML file from a value
     pub(super) fn write_toml<T: Serialize>(path: &Path, value: &T) -> DatabaseResult<()> {
         let content = to_string_pretty(value)?;
-        let tmp = path.with_extension("tmp");
-        fs::write(&tmp, content)?;
-        fs::rename(&tmp, path)?;
-        Ok(())
+        write_durable(path, content.as_bytes())
     }
 }
 
 impl Database for PackageDatabase {
-    fn add_package(&mut self, package: &ExtractedPackage) -> DatabaseResult<()> {
+    fn add_package(
+        &mut self,
+        package: &ExtractedPackage,
+        reason: InstallReason,
+        files: &[FileEntry],
+    ) -> DatabaseResult<()> {
         let lock = ExclusiveLock::new(self.database_path.join(DATABASE_LOCK_FILE_NAME));
         let _guard = lock.lock()?;
 
@@ -101,40 +323,60 @@ impl Database for PackageDatabase {
             return Err(DatabaseError::Path(self.database_path.clone()));
         }
 
-        let install_package_date = OffsetDateTime::now_utc().to_string();
+        let install_package_date = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(|err| DatabaseError::Toml(err.to_string()))?;
 
         let package_info = Package {
             name: package.name.to_string().clone(),
             version: package.version.to_string().clone(),
             format: package.format.to_string().clone(),
             install_date: install_package_date,
+            dependencies: package
+                .dependencies
+                .iter()
+                .map(|dependency| dependency.to_string())
+                .collect(),
+            provides: package.provides.iter().map(|name| name.to_string()).collect(),
+            conflicts: package.conflicts.iter().map(|name| name.to_string()).collect(),
+            reason,
         };
 
         let file_list = FileList {
-            files: package
-                .file_list
-                .iter()
-                .map(|string| PathBuf::from(string.as_str()))
-                .collect(),
+            files: files.to_vec(),
         };
 
-        self.packages_map
-            .insert(package.name.to_string().clone(), package_info);
+        let mut packages_map = self.packages_map.clone();
+        packages_map.insert(package.name.to_string().clone(), package_info);
 
-        let package_dir_path = self
+        let mut files_index = self.files_index.clone();
+
+        // A reinstalled package replaces its previous file list in the index
+        let previous_files_file_path = self
             .database_path
-            .join(PACKAGE_DIR_NAME)
-            .join(&package.name.to_string());
+            .join(Self::package_file_path(&package.name, FILES_TOML_FILE_NAME));
+        if previous_files_file_path.exists() {
+            let previous_file_list: FileList = Self::read_toml(&previous_files_file_path)?;
+            for file in &previous_file_list.files {
+                Self::unindex_file(&mut files_index, &file.path, &package.name);
+            }
+        }
 
-        Self::ensure_directory(&package_dir_path)?;
+        for file in &file_list.files {
+            Self::index_file(&mut files_index, &file.path, &package.name);
+        }
 
-        Self::write_toml(&package_dir_path.join(FILES_TOML_FILE_NAME), &file_list)?;
-        Self::write_toml(
-            &self.database_path.join(PACKAGES_MAP_FILE_NAME),
-            &self.packages_map,
+        let mut journal = Journal::new();
+        journal.write(
+            Self::package_file_path(&package.name, FILES_TOML_FILE_NAME),
+            &file_list,
+        )?;
+        journal.write(
+            Self::package_file_path(&package.name, SCRIPTS_TOML_FILE_NAME),
+            &package.scripts(),
         )?;
 
-        Ok(())
+        self.commit(journal, packages_map, files_index)
     }
 
     fn remove_package(&mut self, package_id: &str) -> DatabaseResult<()> {
@@ -153,23 +395,27 @@ impl Database for PackageDatabase {
             return Err(DatabaseError::Path(self.database_path.clone()));
         }
 
-        let package_dir_path = self.database_path.join(PACKAGE_DIR_NAME).join(&package_id);
+        let package_dir_path = self.database_path.join(PACKAGE_DIR_NAME).join(package_id);
 
         if !package_dir_path.exists() {
             return Err(DatabaseError::NotFound);
         }
 
-        fs::remove_file(package_dir_path.join(FILES_TOML_FILE_NAME))?;
-        fs::remove_dir(package_dir_path)?;
+        let mut files_index = self.files_index.clone();
+        let file_list: FileList = Self::read_toml(&package_dir_path.join(FILES_TOML_FILE_NAME))?;
+        for file in &file_list.files {
+            Self::unindex_file(&mut files_index, &file.path, package_id);
+        }
 
-        self.packages_map.remove(package_id);
+        let mut packages_map = self.packages_map.clone();
+        packages_map.remove(package_id);
 
-        Self::write_toml(
-            &self.database_path.join(PACKAGES_MAP_FILE_NAME),
-            &self.packages_map,
-        )?;
+        let mut journal = Journal::new();
+        journal.remove_file(Self::package_file_path(package_id, FILES_TOML_FILE_NAME));
+        journal.remove_file(Self::package_file_path(package_id, SCRIPTS_TOML_FILE_NAME));
+        journal.remove_dir(Path::new(PACKAGE_DIR_NAME).join(package_id));
 
-        Ok(())
+        self.commit(journal, packages_map, files_index)
     }
 
     fn get_package(&self, query: &str) -> DatabaseResult<Package> {
@@ -186,11 +432,25 @@ impl Database for PackageDatabase {
 
         self.packages_map
             .get(query)
-            .ok_or_else(|| DatabaseError::NotFound)
+            .ok_or(DatabaseError::NotFound)
             .cloned()
     }
 
-    fn get_package_files(&self, package_id: &str) -> DatabaseResult<Vec<PathBuf>> {
+    fn list_packages(&self) -> DatabaseResult<Vec<Package>> {
+        let lock = SharedLock::new(self.database_path.join(DATABASE_LOCK_FILE_NAME));
+        let _guard = lock.lock()?;
+
+        if !self.database_path.exists() {
+            return Err(DatabaseError::Path(self.database_path.clone()));
+        }
+
+        let mut packages: Vec<Package> = self.packages_map.values().cloned().collect();
+        packages.sort_by(|first, second| first.name.cmp(&second.name));
+
+        Ok(packages)
+    }
+
+    fn get_file_entries(&self, package_id: &str) -> DatabaseResult<Vec<FileEntry>> {
         let lock = SharedLock::new(self.database_path.join(DATABASE_LOCK_FILE_NAME));
         let _guard = lock.lock()?;
 
@@ -214,7 +474,39 @@ impl Database for PackageDatabase {
         Ok(file_list.files)
     }
 
-    fn add_file(&mut self, package_id: &str, file_path: &Path) -> DatabaseResult<()> {
+    fn get_package_scripts(&self, package_id: &str) -> DatabaseResult<PackageScripts> {
+        let lock = SharedLock::new(self.database_path.join(DATABASE_LOCK_FILE_NAME));
+        let _guard = lock.lock()?;
+
+        let package_dir_path = self.database_path.join(PACKAGE_DIR_NAME).join(package_id);
+        let package_scripts_file_path = package_dir_path.join(SCRIPTS_TOML_FILE_NAME);
+
+        if !package_dir_path.exists() {
+            return Err(DatabaseError::Path(package_dir_path));
+        }
+
+        // Packages registered before scripts were stored have none
+        if !package_scripts_file_path.exists() {
+            return Ok(PackageScripts::default());
+        }
+
+        Self::read_toml(&package_scripts_file_path)
+    }
+
+    fn owner_of(&self, file_path: &Path) -> DatabaseResult<Vec<String>> {
+        let lock = SharedLock::new(self.database_path.join(DATABASE_LOCK_FILE_NAME));
+        let _guard = lock.lock()?;
+
+        // Paths are stored relative to the root
+        let file_path = file_path.strip_prefix("/").unwrap_or(file_path);
+
+        self.files_index
+            .get(file_path)
+            .cloned()
+            .ok_or(DatabaseError::NotFound)
+    }
+
+    fn add_file(&mut self, package_id: &str, file: &FileEntry) -> DatabaseResult<()> {
         let lock = ExclusiveLock::new(self.database_path.join(DATABASE_LOCK_FILE_NAME));
         let _guard = lock.lock()?;
 
@@ -229,16 +521,24 @@ impl Database for PackageDatabase {
             return Err(DatabaseError::Path(self.database_path.clone()));
         }
 
-        if self.packages_map.get(package_id).is_none() {
+        if !self.packages_map.contains_key(package_id) {
             return Err(DatabaseError::NotFound);
         }
 
         let mut file_list: FileList = Self::read_toml(&package_files_file_path)?;
-        file_list.files.push(file_path.to_path_buf());
+        file_list.files.retain(|entry| entry.path != file.path);
+        file_list.files.push(file.clone());
 
-        Self::write_toml(&package_files_file_path, &file_list)?;
+        let mut files_index = self.files_index.clone();
+        Self::index_file(&mut files_index, &file.path, package_id);
 
-        Ok(())
+        let mut journal = Journal::new();
+        journal.write(
+            Self::package_file_path(package_id, FILES_TOML_FILE_NAME),
+            &file_list,
+        )?;
+
+        self.commit(journal, self.packages_map.clone(), files_index)
     }
 
     fn remove_file(&mut self, package_id: &str, file_path: &Path) -> DatabaseResult<()> {
@@ -256,20 +556,88 @@ impl Database for PackageDatabase {
             return Err(DatabaseError::Path(self.database_path.clone()));
         }
 
-        if self.packages_map.get(package_id).is_none() {
+        if !self.packages_map.contains_key(package_id) {
             return Err(DatabaseError::NotFound);
         }
 
         let mut file_list: FileList = Self::read_toml(&package_files_file_path)?;
         let original_len = file_list.files.len();
-        file_list.files.retain(|f| f != file_path);
+        file_list.files.retain(|file| file.path != file_path);
 
         if file_list.files.len() == original_len {
             return Err(DatabaseError::NotFound);
         }
 
-        Self::write_toml(&package_files_file_path, &file_list)?;
+        let mut files_index = self.files_index.clone();
+        Self::unindex_file(&mut files_index, file_path, package_id);
 
-        Ok(())
+        let mut journal = Journal::new();
+        journal.write(
+            Self::package_file_path(package_id, FILES_TOML_FILE_NAME),
+            &file_list,
+        )?;
+
+        self.commit(journal, self.packages_map.clone(), files_index)
+    }
+}
+
+// Публичные extern "C" функции
+#[no_mangle]
+pub extern "C" fn upac_database_recover(
+    database_path: StabStr,
+) -> InstallerStabbyResult<RecoveryReport> {
+    PackageDatabase::recover(PathBuf::from(database_path.as_str()))
+        .map_err(InstallerError::from)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{file_entries, package};
+
+    #[test]
+    fn owners_follow_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut database = PackageDatabase::new(dir.path().to_path_buf()).unwrap();
+
+        for (name, file) in [("one", "usr/bin/one"), ("two", "usr/bin/two")] {
+            database
+                .add_package(
+                    &package(name, "1.0", &["usr", file]),
+                    InstallReason::Explicit,
+                    &file_entries(&["usr", file]),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            database.owner_of(Path::new("/usr/bin/one")).unwrap(),
+            ["one"]
+        );
+        let mut owners = database.owner_of(Path::new("usr")).unwrap();
+        owners.sort();
+        assert_eq!(owners, ["one", "two"]);
+
+        database.remove_package("one").unwrap();
+        database
+            .add_file("two", &FileEntry::from(PathBuf::from("usr/bin/extra")))
+            .unwrap();
+
+        assert!(matches!(
+            database.owner_of(Path::new("usr/bin/one")),
+            Err(DatabaseError::NotFound)
+        ));
+        assert_eq!(database.owner_of(Path::new("usr")).unwrap(), ["two"]);
+
+        // A database without the index gets it rebuilt from the file lists
+        fs::remove_file(dir.path().join(FILES_INDEX_FILE_NAME)).unwrap();
+        let database = PackageDatabase::new(dir.path().to_path_buf()).unwrap();
+        assert_eq!(
+            database.owner_of(Path::new("usr/bin/extra")).unwrap(),
+            ["two"]
+        );
+        assert!(database.owner_of(Path::new("usr/bin/one")).is_err());
     }
 }
@@ -0,0 +1,153 @@
+// Imports
+use super::{InstallReason, Package};
+
+use regex::Regex;
+
+use time::format_description::well_known::Rfc3339;
+use time::format_description::{self, FormatItem};
+use time::OffsetDateTime;
+
+// Format used for install dates before they were stored as RFC 3339
+const LEGACY_DATE_FORMAT: &str = "[year]-[month]-[day] [hour padding:none]:[minute]:[second].[subsecond] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]";
+
+// How a package name is matched
+#[derive(Debug, Clone)]
+pub enum NameMatch {
+    Exact(String),
+    Substring(String),
+    Regex(Regex),
+}
+
+// Criteria for listing installed packages; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct PackageFilter {
+    pub name: Option<NameMatch>,
+    pub format: Option<String>,
+    pub installed_after: Option<OffsetDateTime>,
+    pub installed_before: Option<OffsetDateTime>,
+    pub reason: Option<InstallReason>,
+}
+
+impl PackageFilter {
+    // Function to parse a stored install date in the current or legacy format
+    pub fn parse_install_date(install_date: &str) -> Option<OffsetDateTime> {
+        if let Ok(date) = OffsetDateTime::parse(install_date, &Rfc3339) {
+            return Some(date);
+        }
+
+        let legacy_format: Vec<FormatItem> =
+            format_description::parse_borrowed::<1>(LEGACY_DATE_FORMAT).ok()?;
+        OffsetDateTime::parse(install_date, &legacy_format).ok()
+    }
+
+    // Function to check whether a package satisfies every set criterion
+    pub fn matches(&self, package: &Package) -> bool {
+        let name_matches = match &self.name {
+            None => true,
+            Some(NameMatch::Exact(name)) => package.name == *name,
+            Some(NameMatch::Substring(query)) => {
+                package.name.to_lowercase().contains(&query.to_lowercase())
+            }
+            Some(NameMatch::Regex(regex)) => regex.is_match(&package.name),
+        };
+
+        let format_matches = self
+            .format
+            .as_ref()
+            .is_none_or(|format| package.format == *format);
+
+        let reason_matches = self.reason.is_none_or(|reason| package.reason == reason);
+
+        let date_matches = if self.installed_after.is_none() && self.installed_before.is_none() {
+            true
+        } else {
+            match Self::parse_install_date(&package.install_date) {
+                Some(date) => {
+                    self.installed_after.is_none_or(|after| date >= after)
+                        && self.installed_before.is_none_or(|before| date <= before)
+                }
+                None => false,
+            }
+        };
+
+        name_matches && format_matches && reason_matches && date_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::Duration;
+
+    fn installed(name: &str, format: &str, install_date: &str, reason: InstallReason) -> Package {
+        Package {
+            name: name.to_owned(),
+            version: String::from("1.0"),
+            format: format.to_owned(),
+            install_date: install_date.to_owned(),
+            dependencies: Vec::new(),
+            provides: Vec::new(),
+            conflicts: Vec::new(),
+            reason,
+        }
+    }
+
+    #[test]
+    fn every_set_criterion_must_match() {
+        let now = OffsetDateTime::now_utc();
+        let package = installed(
+            "python-requests",
+            "alpm",
+            &now.format(&Rfc3339).unwrap(),
+            InstallReason::Dependency,
+        );
+
+        let matches = |filter: PackageFilter| filter.matches(&package);
+
+        assert!(matches(PackageFilter::default()));
+        assert!(matches(PackageFilter {
+            name: Some(NameMatch::Substring(String::from("Requests"))),
+            format: Some(String::from("alpm")),
+            reason: Some(InstallReason::Dependency),
+            installed_after: Some(now - Duration::hours(1)),
+            installed_before: Some(now + Duration::hours(1)),
+        }));
+        assert!(matches(PackageFilter {
+            name: Some(NameMatch::Regex(Regex::new("^python-").unwrap())),
+            ..PackageFilter::default()
+        }));
+
+        assert!(!matches(PackageFilter {
+            name: Some(NameMatch::Exact(String::from("requests"))),
+            ..PackageFilter::default()
+        }));
+        assert!(!matches(PackageFilter {
+            format: Some(String::from("deb")),
+            ..PackageFilter::default()
+        }));
+        assert!(!matches(PackageFilter {
+            reason: Some(InstallReason::Explicit),
+            ..PackageFilter::default()
+        }));
+        assert!(!matches(PackageFilter {
+            installed_after: Some(now + Duration::hours(1)),
+            ..PackageFilter::default()
+        }));
+    }
+
+    #[test]
+    fn reads_legacy_install_dates() {
+        let date = PackageFilter::parse_install_date("2024-03-05 7:08:09.5 +02:00:00").unwrap();
+        assert_eq!(date.unix_timestamp(), 1_709_615_289);
+
+        // A date that cannot be read only matches filters without a date range
+        let package = installed("tool", "upac", "yesterday", InstallReason::Explicit);
+        assert!(PackageFilter::default().matches(&package));
+        assert!(!PackageFilter {
+            installed_before: Some(OffsetDateTime::now_utc()),
+            ..PackageFilter::default()
+        }
+        .matches(&package));
+    }
+}
@@ -0,0 +1,260 @@
+// Imports
+use super::{DatabaseError, DatabaseResult, JournalState};
+
+use serde::{Deserialize, Serialize};
+
+use toml::{from_str, to_string_pretty};
+
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+pub(crate) const JOURNAL_FILE_NAME: &str = "journal.toml";
+
+// Single change to a database file; paths are relative to the database directory
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum JournalOperation {
+    Write { path: PathBuf, content: String },
+    RemoveFile { path: PathBuf },
+    RemoveDir { path: PathBuf },
+}
+
+// Write-ahead journal: every change of a mutation is persisted before any database file is touched
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct Journal {
+    operations: Vec<JournalOperation>,
+}
+
+impl Journal {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    // Function to schedule a full rewrite of a database file
+    pub(crate) fn write<T: Serialize>(&mut self, path: PathBuf, value: &T) -> DatabaseResult<()> {
+        self.operations.push(JournalOperation::Write {
+            path,
+            content: to_string_pretty(value)?,
+        });
+        Ok(())
+    }
+
+    // Function to schedule removal of a database file
+    pub(crate) fn remove_file(&mut self, path: PathBuf) {
+        self.operations.push(JournalOperation::RemoveFile { path });
+    }
+
+    // Function to schedule removal of an empty database directory
+    pub(crate) fn remove_dir(&mut self, path: PathBuf) {
+        self.operations.push(JournalOperation::RemoveDir { path });
+    }
+
+    // Function to persist the journal, apply it and drop it once every change is on disk
+    pub(crate) fn commit(self, database_path: &Path) -> DatabaseResult<()> {
+        let journal_path = database_path.join(JOURNAL_FILE_NAME);
+
+        // The rename of the journal is the commit point; after it the mutation is replayed on crash
+        write_durable(&journal_path, to_string_pretty(&self)?.as_bytes())?;
+        self.apply(database_path)?;
+        fs::remove_file(&journal_path)?;
+        sync_dir(database_path)?;
+
+        Ok(())
+    }
+
+    // Function to finish or drop a mutation interrupted by a crash
+    pub(crate) fn recover(database_path: &Path) -> DatabaseResult<JournalState> {
+        let journal_path = database_path.join(JOURNAL_FILE_NAME);
+        let mut state = JournalState::Clean;
+
+        // A leftover temporary journal was never committed
+        let temp_journal_path = temp_path(&journal_path);
+        if temp_journal_path.exists() {
+            fs::remove_file(&temp_journal_path)?;
+            state = JournalState::Discarded;
+        }
+
+        if !journal_path.exists() {
+            return Ok(state);
+        }
+
+        let journal: Journal = match from_str(&fs::read_to_string(&journal_path)?) {
+            Ok(journal) => journal,
+            Err(_) => {
+                fs::remove_file(&journal_path)?;
+                sync_dir(database_path)?;
+                return Ok(JournalState::Discarded);
+            }
+        };
+
+        journal.apply(database_path)?;
+        fs::remove_file(&journal_path)?;
+        sync_dir(database_path)?;
+
+        Ok(JournalState::Replayed)
+    }
+
+    // Function to apply every operation; operations are idempotent so a replay may repeat them
+    fn apply(&self, database_path: &Path) -> DatabaseResult<()> {
+        for operation in &self.operations {
+            match operation {
+                JournalOperation::Write { path, content } => {
+                    let file_path = database_path.join(path);
+                    if let Some(parent) = file_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    write_durable(&file_path, content.as_bytes())?;
+                }
+                JournalOperation::RemoveFile { path } => {
+                    ignore_not_found(fs::remove_file(database_path.join(path)))?;
+                }
+                JournalOperation::RemoveDir { path } => {
+                    ignore_not_found(fs::remove_dir(database_path.join(path)))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Function to write a file through a synced temporary file and an atomic rename
+pub(crate) fn write_durable(path: &Path, content: &[u8]) -> DatabaseResult<()> {
+    let tmp = temp_path(path);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    match path.parent() {
+        Some(parent) => sync_dir(parent),
+        None => Ok(()),
+    }
+}
+
+// Function to flush directory entries so renames and removals survive power loss
+fn sync_dir(path: &Path) -> DatabaseResult<()> {
+    File::open(path)?.sync_all().map_err(DatabaseError::from)
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    path.with_extension("tmp")
+}
+
+fn ignore_not_found(result: std::io::Result<()>) -> DatabaseResult<()> {
+    match result {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn version(version: &str) -> HashMap<&str, &str> {
+        HashMap::from([("version", version)])
+    }
+
+    #[test]
+    fn replays_committed_journal_after_crash() {
+        let database = tempfile::tempdir().unwrap();
+        let database_path = database.path();
+
+        fs::write(
+            database_path.join("packages_map.toml"),
+            to_string_pretty(&version("1")).unwrap(),
+        )
+        .unwrap();
+        fs::create_dir_all(database_path.join("packages/old")).unwrap();
+        fs::write(
+            database_path.join("packages/old/files.toml"),
+            "files = []\n",
+        )
+        .unwrap();
+
+        let mut journal = Journal::new();
+        journal
+            .write(PathBuf::from("packages_map.toml"), &version("2"))
+            .unwrap();
+        journal
+            .write(PathBuf::from("packages/new/files.toml"), &version("2"))
+            .unwrap();
+        journal.remove_file(PathBuf::from("packages/old/files.toml"));
+        journal.remove_dir(PathBuf::from("packages/old"));
+
+        // The journal reached the disk and the crash came after the first change was applied
+        write_durable(
+            &database_path.join(JOURNAL_FILE_NAME),
+            to_string_pretty(&journal).unwrap().as_bytes(),
+        )
+        .unwrap();
+        Journal {
+            operations: journal.operations.drain(..1).collect(),
+        }
+        .apply(database_path)
+        .unwrap();
+
+        assert_eq!(
+            Journal::recover(database_path).unwrap(),
+            JournalState::Replayed
+        );
+
+        let read = |path: &str| fs::read_to_string(database_path.join(path)).unwrap();
+        assert_eq!(
+            read("packages_map.toml"),
+            to_string_pretty(&version("2")).unwrap()
+        );
+        assert_eq!(
+            read("packages/new/files.toml"),
+            to_string_pretty(&version("2")).unwrap()
+        );
+        assert!(!database_path.join("packages/old").exists());
+        assert!(!database_path.join(JOURNAL_FILE_NAME).exists());
+
+        assert_eq!(
+            Journal::recover(database_path).unwrap(),
+            JournalState::Clean
+        );
+    }
+
+    #[test]
+    fn discards_uncommitted_journal() {
+        let database = tempfile::tempdir().unwrap();
+        let database_path = database.path();
+        let journal_path = database_path.join(JOURNAL_FILE_NAME);
+
+        fs::write(database_path.join("packages_map.toml"), "old").unwrap();
+
+        let mut journal = Journal::new();
+        journal
+            .write(PathBuf::from("packages_map.toml"), &version("2"))
+            .unwrap();
+        let content = to_string_pretty(&journal).unwrap();
+
+        // The crash came before the temporary journal was renamed into place
+        fs::write(temp_path(&journal_path), &content).unwrap();
+        assert_eq!(
+            Journal::recover(database_path).unwrap(),
+            JournalState::Discarded
+        );
+        assert!(!temp_path(&journal_path).exists());
+
+        // A torn journal is dropped rather than half applied
+        fs::write(&journal_path, &content[..content.len() / 2]).unwrap();
+        assert_eq!(
+            Journal::recover(database_path).unwrap(),
+            JournalState::Discarded
+        );
+        assert!(!journal_path.exists());
+
+        assert_eq!(
+            fs::read_to_string(database_path.join("packages_map.toml")).unwrap(),
+            "old"
+        );
+    }
+}
@@ -1,20 +1,101 @@
 // Imports
 use upac_types::{DatabaseError, DatabaseResult};
-use upac_types::{ExtractedPackage, Package};
+use upac_types::{ExtractedPackage, FileEntry, InstallReason, Package, PackageScripts};
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
 
 use std::path::{Path, PathBuf};
 
 // Mods
 pub mod database;
+pub mod filter;
+mod journal;
+pub mod sqlite;
 
 pub use database::PackageDatabase;
+pub use filter::{NameMatch, PackageFilter};
+pub use sqlite::SqliteDatabase;
+
+pub(crate) use journal::Journal;
+
+// Storage used for the package registry
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DatabaseBackend {
+    #[default]
+    Toml,
+    Sqlite,
+}
+
+impl DatabaseBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Toml => "toml",
+            Self::Sqlite => "sqlite",
+        }
+    }
+}
+
+// What happened to an interrupted mutation found when opening the database
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalState {
+    Clean,
+    Replayed,
+    Discarded,
+}
+
+// Result of a database consistency recovery
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct RecoveryReport {
+    pub orphaned_packages: StabVec<StabString>,
+    pub missing_packages: StabVec<StabString>,
+    pub journal: JournalState,
+}
+
+// Function to open the database with the configured backend
+pub fn open_database(
+    backend: DatabaseBackend,
+    database_path: PathBuf,
+) -> DatabaseResult<Box<dyn Database>> {
+    Ok(match backend {
+        DatabaseBackend::Toml => Box::new(PackageDatabase::new(database_path)?),
+        DatabaseBackend::Sqlite => Box::new(SqliteDatabase::new(database_path)?),
+    })
+}
 
 // Trait for package registry operations
 pub trait Database {
-    fn add_package(&mut self, package: &ExtractedPackage) -> DatabaseResult<()>;
+    fn add_package(
+        &mut self,
+        package: &ExtractedPackage,
+        reason: InstallReason,
+        files: &[FileEntry],
+    ) -> DatabaseResult<()>;
     fn remove_package(&mut self, package_id: &str) -> DatabaseResult<()>;
     fn get_package(&self, query: &str) -> DatabaseResult<Package>;
-    fn get_package_files(&self, package_id: &str) -> DatabaseResult<Vec<PathBuf>>;
-    fn add_file(&mut self, package_id: &str, file_path: &Path) -> DatabaseResult<()>;
+    fn list_packages(&self) -> DatabaseResult<Vec<Package>>;
+    fn filter_packages(&self, filter: &PackageFilter) -> DatabaseResult<Vec<Package>> {
+        Ok(self
+            .list_packages()?
+            .into_iter()
+            .filter(|package| filter.matches(package))
+            .collect())
+    }
+    fn get_package_files(&self, package_id: &str) -> DatabaseResult<Vec<PathBuf>> {
+        Ok(self
+            .get_file_entries(package_id)?
+            .into_iter()
+            .map(|file| file.path)
+            .collect())
+    }
+    fn get_file_entries(&self, package_id: &str) -> DatabaseResult<Vec<FileEntry>>;
+    fn get_package_scripts(&self, package_id: &str) -> DatabaseResult<PackageScripts>;
+    fn owner_of(&self, file_path: &Path) -> DatabaseResult<Vec<String>>;
+    fn add_file(&mut self, package_id: &str, file: &FileEntry) -> DatabaseResult<()>;
     fn remove_file(&mut self, package_id: &str, file_path: &Path) -> DatabaseResult<()>;
 }
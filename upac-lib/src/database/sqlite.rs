@@ -0,0 +1,592 @@
+// Imports
+use super::{Database, DatabaseError, DatabaseResult, PackageFilter};
+use super::{ExtractedPackage, FileEntry, InstallReason, Package, PackageDatabase, PackageScripts};
+
+use upac_types::{FileType, LinkStrategy};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub(crate) const SQLITE_DATABASE_FILE_NAME: &str = "upac.sqlite";
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+
+    CREATE TABLE IF NOT EXISTS packages (
+        name         TEXT PRIMARY KEY NOT NULL,
+        version      TEXT NOT NULL,
+        format       TEXT NOT NULL,
+        install_date TEXT NOT NULL,
+        reason       TEXT NOT NULL,
+        pre_install  TEXT,
+        post_install TEXT,
+        pre_remove   TEXT,
+        post_remove  TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS dependencies (
+        package    TEXT NOT NULL REFERENCES packages(name) ON DELETE CASCADE,
+        dependency TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS files (
+        id          INTEGER PRIMARY KEY,
+        package     TEXT NOT NULL REFERENCES packages(name) ON DELETE CASCADE,
+        path        TEXT NOT NULL,
+        file_type   TEXT NOT NULL DEFAULT 'unknown',
+        size        INTEGER NOT NULL DEFAULT 0,
+        mode        INTEGER NOT NULL DEFAULT 0,
+        uid         INTEGER NOT NULL DEFAULT 0,
+        gid         INTEGER NOT NULL DEFAULT 0,
+        sha256      TEXT,
+        link_target TEXT,
+        link_strategy TEXT,
+        config      INTEGER NOT NULL DEFAULT 0,
+        UNIQUE (package, path)
+    );
+
+    CREATE TABLE IF NOT EXISTS provides (
+        package TEXT NOT NULL REFERENCES packages(name) ON DELETE CASCADE,
+        name    TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS conflicts (
+        package TEXT NOT NULL REFERENCES packages(name) ON DELETE CASCADE,
+        name    TEXT NOT NULL
+    );
+
+    CREATE INDEX IF NOT EXISTS dependencies_package ON dependencies(package);
+    CREATE INDEX IF NOT EXISTS provides_package ON provides(package);
+    CREATE INDEX IF NOT EXISTS conflicts_package ON conflicts(package);
+    CREATE INDEX IF NOT EXISTS files_path ON files(path);
+";
+
+// Columns added to the files table after the first schema version
+const FILE_METADATA_COLUMNS: &[(&str, &str)] = &[
+    ("file_type", "TEXT NOT NULL DEFAULT 'unknown'"),
+    ("size", "INTEGER NOT NULL DEFAULT 0"),
+    ("mode", "INTEGER NOT NULL DEFAULT 0"),
+    ("uid", "INTEGER NOT NULL DEFAULT 0"),
+    ("gid", "INTEGER NOT NULL DEFAULT 0"),
+    ("sha256", "TEXT"),
+    ("link_target", "TEXT"),
+    ("link_strategy", "TEXT"),
+    ("config", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+const SCHEMA_VERSION: i64 = 4;
+
+const PACKAGE_COLUMNS: &str = "name, version, format, install_date, reason";
+const FILE_COLUMNS: &str =
+    "path, file_type, size, mode, uid, gid, sha256, link_target, link_strategy, config";
+
+// Struct definition for the SQLite-backed database
+pub struct SqliteDatabase {
+    connection: Connection,
+}
+
+// Implementation of SqliteDatabase own functions
+impl SqliteDatabase {
+    // Function to open the database, migrating an existing TOML database on first use
+    pub fn new(database_path: PathBuf) -> DatabaseResult<Self> {
+        PackageDatabase::ensure_directory(&database_path)?;
+
+        let sqlite_path = database_path.join(SQLITE_DATABASE_FILE_NAME);
+        if !sqlite_path.exists() && PackageDatabase::is_initialized(&database_path) {
+            Self::migrate_from_toml(&database_path, &sqlite_path)?;
+        }
+
+        Self::open(&sqlite_path)
+    }
+
+    // Function to open a SQLite file and make sure the schema exists
+    fn open(sqlite_path: &Path) -> DatabaseResult<Self> {
+        let connection = Connection::open(sqlite_path)?;
+        connection.execute_batch(SCHEMA)?;
+        Self::upgrade_schema(&connection)?;
+        Ok(Self { connection })
+    }
+
+    // Function to bring a database created by an older version up to the current schema
+    fn upgrade_schema(connection: &Connection) -> DatabaseResult<()> {
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        let columns = connection
+            .prepare("SELECT name FROM pragma_table_info('files')")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        for (column, definition) in FILE_METADATA_COLUMNS {
+            if !columns.iter().any(|existing| existing == column) {
+                connection.execute(
+                    &format!("ALTER TABLE files ADD COLUMN {column} {definition}"),
+                    [],
+                )?;
+            }
+        }
+
+        connection.execute(&format!("PRAGMA user_version = {SCHEMA_VERSION}"), [])?;
+        Ok(())
+    }
+
+    // Function to copy every package of the TOML database into a new SQLite file
+    fn migrate_from_toml(database_path: &Path, sqlite_path: &Path) -> DatabaseResult<()> {
+        let toml_database = PackageDatabase::new(database_path.to_path_buf())?;
+
+        // Building into a temporary file keeps a failed migration from leaving a half-filled database
+        let temp_path = sqlite_path.with_extension("sqlite.tmp");
+        if temp_path.exists() {
+            fs::remove_file(&temp_path)?;
+        }
+
+        let mut sqlite_database = Self::open(&temp_path)?;
+        let transaction = sqlite_database.connection.transaction()?;
+
+        for package in toml_database.list_packages()? {
+            let files = toml_database.get_file_entries(&package.name)?;
+            let scripts = toml_database.get_package_scripts(&package.name)?;
+            Self::insert_package(&transaction, &package, &files, &scripts)?;
+        }
+
+        transaction.commit()?;
+        drop(sqlite_database);
+
+        fs::rename(&temp_path, sqlite_path)?;
+        Ok(())
+    }
+
+    // Function to insert a complete package record
+    fn insert_package(
+        connection: &Connection,
+        package: &Package,
+        files: &[FileEntry],
+        scripts: &PackageScripts,
+    ) -> DatabaseResult<()> {
+        connection.execute(
+            "INSERT INTO packages (name, version, format, install_date, reason, pre_install, post_install, pre_remove, post_remove)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                package.name,
+                package.version,
+                package.format,
+                package.install_date,
+                package.reason.as_str(),
+                scripts.pre_install,
+                scripts.post_install,
+                scripts.pre_remove,
+                scripts.post_remove,
+            ],
+        )?;
+
+        let mut insert_dependency =
+            connection.prepare("INSERT INTO dependencies (package, dependency) VALUES (?1, ?2)")?;
+        for dependency in &package.dependencies {
+            insert_dependency.execute(params![package.name, dependency])?;
+        }
+
+        let mut insert_provide =
+            connection.prepare("INSERT INTO provides (package, name) VALUES (?1, ?2)")?;
+        for name in &package.provides {
+            insert_provide.execute(params![package.name, name])?;
+        }
+
+        let mut insert_conflict =
+            connection.prepare("INSERT INTO conflicts (package, name) VALUES (?1, ?2)")?;
+        for name in &package.conflicts {
+            insert_conflict.execute(params![package.name, name])?;
+        }
+
+        for file in files {
+            Self::insert_file(connection, &package.name, file)?;
+        }
+
+        Ok(())
+    }
+
+    // Function to insert or replace the record of a single file
+    fn insert_file(
+        connection: &Connection,
+        package_id: &str,
+        file: &FileEntry,
+    ) -> DatabaseResult<()> {
+        let link_target = file
+            .link_target
+            .as_deref()
+            .map(Self::path_to_text)
+            .transpose()?;
+
+        connection
+            .prepare_cached(&format!(
+                "INSERT OR REPLACE INTO files (package, {FILE_COLUMNS})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"
+            ))?
+            .execute(params![
+                package_id,
+                Self::path_to_text(&file.path)?,
+                file.file_type.as_str(),
+                file.size,
+                file.mode,
+                file.uid,
+                file.gid,
+                file.sha256,
+                link_target,
+                file.link_strategy.as_ref().map(LinkStrategy::as_str),
+                file.config,
+            ])?;
+
+        Ok(())
+    }
+
+    // Function to build a file entry from a row selected with FILE_COLUMNS
+    fn file_from_row(row: &Row) -> rusqlite::Result<FileEntry> {
+        let file_type: String = row.get(1)?;
+        let link_strategy: Option<String> = row.get(8)?;
+
+        Ok(FileEntry {
+            path: PathBuf::from(row.get::<_, String>(0)?),
+            file_type: match file_type.as_str() {
+                "regular" => FileType::Regular,
+                "directory" => FileType::Directory,
+                "symlink" => FileType::Symlink,
+                "other" => FileType::Other,
+                _ => FileType::Unknown,
+            },
+            size: row.get(2)?,
+            mode: row.get(3)?,
+            uid: row.get(4)?,
+            gid: row.get(5)?,
+            sha256: row.get(6)?,
+            link_target: row.get::<_, Option<String>>(7)?.map(PathBuf::from),
+            link_strategy: link_strategy.map(|strategy| match strategy.as_str() {
+                "reflink" => LinkStrategy::Reflink,
+                "copy_file_range" => LinkStrategy::CopyFileRange,
+                "copy" => LinkStrategy::Copy,
+                _ => LinkStrategy::HardLink,
+            }),
+            config: row.get(9)?,
+        })
+    }
+
+    // Function to store a path as text; SQLite columns cannot hold arbitrary bytes as TEXT
+    fn path_to_text(path: &Path) -> DatabaseResult<&str> {
+        path.to_str()
+            .ok_or_else(|| DatabaseError::Path(path.to_path_buf()))
+    }
+
+    // Function to build a package from a row selected with PACKAGE_COLUMNS
+    fn package_from_row(&self, row: &Row) -> rusqlite::Result<Package> {
+        let reason: String = row.get(4)?;
+
+        Ok(Package {
+            name: row.get(0)?,
+            version: row.get(1)?,
+            format: row.get(2)?,
+            install_date: row.get(3)?,
+            dependencies: Vec::new(),
+            provides: Vec::new(),
+            conflicts: Vec::new(),
+            reason: match reason.as_str() {
+                "dependency" => InstallReason::Dependency,
+                _ => InstallReason::Explicit,
+            },
+        })
+    }
+
+    // Function to fill in the dependencies, provides and conflicts of a loaded package
+    fn load_dependencies(&self, package: &mut Package) -> DatabaseResult<()> {
+        package.dependencies = self.load_names(
+            "SELECT dependency FROM dependencies WHERE package = ?1 ORDER BY rowid",
+            &package.name,
+        )?;
+        package.provides = self.load_names(
+            "SELECT name FROM provides WHERE package = ?1 ORDER BY rowid",
+            &package.name,
+        )?;
+        package.conflicts = self.load_names(
+            "SELECT name FROM conflicts WHERE package = ?1 ORDER BY rowid",
+            &package.name,
+        )?;
+
+        Ok(())
+    }
+
+    fn load_names(&self, query: &str, package_id: &str) -> DatabaseResult<Vec<String>> {
+        let mut statement = self.connection.prepare_cached(query)?;
+
+        let names = statement
+            .query_map(params![package_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(names)
+    }
+
+    // Function to check that a package is registered
+    fn ensure_package(&self, package_id: &str) -> DatabaseResult<()> {
+        self.connection
+            .query_row(
+                "SELECT 1 FROM packages WHERE name = ?1",
+                params![package_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .ok_or(DatabaseError::NotFound)
+    }
+}
+
+impl Database for SqliteDatabase {
+    fn add_package(
+        &mut self,
+        package: &ExtractedPackage,
+        reason: InstallReason,
+        files: &[FileEntry],
+    ) -> DatabaseResult<()> {
+        let install_package_date = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(|err| DatabaseError::Sqlite(err.to_string()))?;
+
+        let package_info = Package {
+            name: package.name.to_string(),
+            version: package.version.to_string(),
+            format: package.format.to_string(),
+            install_date: install_package_date,
+            dependencies: package
+                .dependencies
+                .iter()
+                .map(|dependency| dependency.to_string())
+                .collect(),
+            provides: package.provides.iter().map(|name| name.to_string()).collect(),
+            conflicts: package.conflicts.iter().map(|name| name.to_string()).collect(),
+            reason,
+        };
+
+        let transaction = self.connection.transaction()?;
+
+        // A reinstalled package replaces its previous record
+        transaction.execute(
+            "DELETE FROM packages WHERE name = ?1",
+            params![package_info.name],
+        )?;
+        Self::insert_package(&transaction, &package_info, files, &package.scripts())?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove_package(&mut self, package_id: &str) -> DatabaseResult<()> {
+        let removed = self
+            .connection
+            .execute("DELETE FROM packages WHERE name = ?1", params![package_id])?;
+
+        if removed == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    fn get_package(&self, query: &str) -> DatabaseResult<Package> {
+        let mut package = self
+            .connection
+            .query_row(
+                &format!("SELECT {PACKAGE_COLUMNS} FROM packages WHERE name = ?1"),
+                params![query],
+                |row| self.package_from_row(row),
+            )
+            .optional()?
+            .ok_or(DatabaseError::NotFound)?;
+
+        self.load_dependencies(&mut package)?;
+        Ok(package)
+    }
+
+    fn list_packages(&self) -> DatabaseResult<Vec<Package>> {
+        self.filter_packages(&PackageFilter::default())
+    }
+
+    fn filter_packages(&self, filter: &PackageFilter) -> DatabaseResult<Vec<Package>> {
+        // Format and reason are narrowed in SQL, the remaining criteria in Rust
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {PACKAGE_COLUMNS} FROM packages
+             WHERE (?1 IS NULL OR format = ?1) AND (?2 IS NULL OR reason = ?2)
+             ORDER BY name"
+        ))?;
+
+        let packages = statement
+            .query_map(
+                params![
+                    filter.format,
+                    filter.reason.as_ref().map(InstallReason::as_str)
+                ],
+                |row| self.package_from_row(row),
+            )?
+            .collect::<rusqlite::Result<Vec<Package>>>()?;
+
+        let mut matching = Vec::new();
+        for mut package in packages {
+            self.load_dependencies(&mut package)?;
+            if filter.matches(&package) {
+                matching.push(package);
+            }
+        }
+
+        Ok(matching)
+    }
+
+    fn get_file_entries(&self, package_id: &str) -> DatabaseResult<Vec<FileEntry>> {
+        self.ensure_package(package_id)?;
+
+        let mut statement = self.connection.prepare_cached(&format!(
+            "SELECT {FILE_COLUMNS} FROM files WHERE package = ?1 ORDER BY id"
+        ))?;
+
+        let files = statement
+            .query_map(params![package_id], Self::file_from_row)?
+            .collect::<rusqlite::Result<Vec<FileEntry>>>()?;
+
+        Ok(files)
+    }
+
+    fn get_package_scripts(&self, package_id: &str) -> DatabaseResult<PackageScripts> {
+        self.connection
+            .query_row(
+                "SELECT pre_install, post_install, pre_remove, post_remove FROM packages WHERE name = ?1",
+                params![package_id],
+                |row| {
+                    Ok(PackageScripts {
+                        pre_install: row.get(0)?,
+                        post_install: row.get(1)?,
+                        pre_remove: row.get(2)?,
+                        post_remove: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?
+            .ok_or(DatabaseError::NotFound)
+    }
+
+    fn owner_of(&self, file_path: &Path) -> DatabaseResult<Vec<String>> {
+        // Paths are stored relative to the root
+        let file_path = file_path.strip_prefix("/").unwrap_or(file_path);
+
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT package FROM files WHERE path = ?1 ORDER BY id")?;
+
+        let owners = statement
+            .query_map(params![Self::path_to_text(file_path)?], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        if owners.is_empty() {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(owners)
+    }
+
+    fn add_file(&mut self, package_id: &str, file: &FileEntry) -> DatabaseResult<()> {
+        self.ensure_package(package_id)?;
+        Self::insert_file(&self.connection, package_id, file)
+    }
+
+    fn remove_file(&mut self, package_id: &str, file_path: &Path) -> DatabaseResult<()> {
+        self.ensure_package(package_id)?;
+
+        let removed = self.connection.execute(
+            "DELETE FROM files WHERE package = ?1 AND path = ?2",
+            params![package_id, Self::path_to_text(file_path)?],
+        )?;
+
+        if removed == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{file_entries, package, stab_vec};
+
+    use stabby::option::Option as StabOption;
+
+    #[test]
+    fn migrates_toml_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().to_path_buf();
+
+        let mut tool = package("tool", "1.0", &["usr", "usr/bin/tool"]);
+        tool.dependencies = stab_vec(&["libfoo>=1.0"]);
+        tool.provides = stab_vec(&["editor"]);
+        tool.conflicts = stab_vec(&["vim<9", "nano"]);
+        tool.post_install = StabOption::Some("echo installed".into());
+
+        let mut toml_database = PackageDatabase::new(database_path.clone()).unwrap();
+        toml_database
+            .add_package(
+                &package("libfoo", "1.2", &["usr", "usr/lib/libfoo.so"]),
+                InstallReason::Dependency,
+                &file_entries(&["usr", "usr/lib/libfoo.so"]),
+            )
+            .unwrap();
+        toml_database
+            .add_package(
+                &tool,
+                InstallReason::Explicit,
+                &file_entries(&["usr", "usr/bin/tool"]),
+            )
+            .unwrap();
+        let expected = toml_database.get_package("tool").unwrap();
+
+        let database = SqliteDatabase::new(database_path.clone()).unwrap();
+        assert!(database_path.join(SQLITE_DATABASE_FILE_NAME).exists());
+
+        let migrated = database.get_package("tool").unwrap();
+        assert_eq!(
+            (&migrated.version, &migrated.install_date, migrated.reason),
+            (&expected.version, &expected.install_date, expected.reason)
+        );
+        assert_eq!(migrated.dependencies, ["libfoo>=1.0"]);
+        assert_eq!(migrated.provides, ["editor"]);
+        assert_eq!(migrated.conflicts, ["vim<9", "nano"]);
+        assert_eq!(
+            database.get_package("libfoo").unwrap().reason,
+            InstallReason::Dependency
+        );
+        assert_eq!(
+            database
+                .get_package_scripts("tool")
+                .unwrap()
+                .post_install
+                .as_deref(),
+            Some("echo installed")
+        );
+        assert_eq!(
+            database.get_package_files("tool").unwrap(),
+            [PathBuf::from("usr"), PathBuf::from("usr/bin/tool")]
+        );
+        let mut owners = database.owner_of(Path::new("/usr")).unwrap();
+        owners.sort();
+        assert_eq!(owners, ["libfoo", "tool"]);
+
+        // Later changes go to SQLite only, the TOML files are left as they were
+        drop(database);
+        let mut database = SqliteDatabase::new(database_path.clone()).unwrap();
+        database.remove_package("libfoo").unwrap();
+        assert_eq!(database.owner_of(Path::new("usr")).unwrap(), ["tool"]);
+        assert!(PackageDatabase::new(database_path)
+            .unwrap()
+            .get_package("libfoo")
+            .is_ok());
+    }
+}
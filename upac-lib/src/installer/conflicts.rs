@@ -0,0 +1,134 @@
+// Imports
+use super::{ExtractedPackage, InstallerError, InstallerResult};
+
+use upac_types::DatabaseError;
+
+use crate::database::Database;
+
+use glob::Pattern;
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// File of an incoming package that is already present on the system
+pub(crate) struct FileConflict {
+    pub(crate) path: PathBuf,
+    pub(crate) owner: Option<String>,
+}
+
+impl fmt::Display for FileConflict {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.owner {
+            Some(owner) => write!(formatter, "/{} (owned by {owner})", self.path.display()),
+            None => write!(formatter, "/{} (exists in filesystem)", self.path.display()),
+        }
+    }
+}
+
+// Pre-flight check of a package's files against installed packages and the root
+pub(crate) struct ConflictChecker<'a> {
+    database: &'a dyn Database,
+    root_path: &'a Path,
+    temp_path: &'a Path,
+}
+
+impl<'a> ConflictChecker<'a> {
+    pub(crate) fn new(
+        database: &'a dyn Database,
+        root_path: &'a Path,
+        temp_path: &'a Path,
+    ) -> Self {
+        Self {
+            database,
+            root_path,
+            temp_path,
+        }
+    }
+
+    // Function to compile overwrite globs; a leading `/` is optional
+    pub(crate) fn compile_patterns(patterns: &[&str]) -> InstallerResult<Vec<Pattern>> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern.trim_start_matches('/')).map_err(|err| {
+                    InstallerError::Installer(
+                        format!("Invalid path pattern {pattern}: {err}").into(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    // Function to find the installed package other than `package_name` owning a path
+    fn other_owner(&self, file_path: &Path, package_name: &str) -> InstallerResult<Option<String>> {
+        match self.database.owner_of(file_path) {
+            Ok(owners) => Ok(owners.into_iter().find(|owner| owner != package_name)),
+            Err(DatabaseError::NotFound) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Function to find every conflicting file; conflicts matching `overwrite` are split out
+    pub(crate) fn check(
+        &self,
+        package: &ExtractedPackage,
+        overwrite: &[Pattern],
+    ) -> InstallerResult<(Vec<FileConflict>, Vec<FileConflict>)> {
+        let own_files = self
+            .database
+            .get_package_files(&package.name)
+            .unwrap_or_default();
+
+        let mut conflicts = Vec::new();
+        let mut overwritten = Vec::new();
+
+        for file_path in package
+            .file_list
+            .iter()
+            .map(|string| PathBuf::from(string.as_str()))
+        {
+            let incoming_is_dir = fs::symlink_metadata(self.temp_path.join(&file_path))?.is_dir();
+            let existing = fs::symlink_metadata(self.root_path.join(&file_path)).ok();
+
+            // Directories are shared between packages unless a file is in the way
+            let conflict = if incoming_is_dir {
+                match existing {
+                    Some(metadata) if !metadata.is_dir() => Some(FileConflict {
+                        owner: self.other_owner(&file_path, &package.name)?,
+                        path: file_path,
+                    }),
+                    _ => None,
+                }
+            } else if let Some(owner) = self.other_owner(&file_path, &package.name)? {
+                Some(FileConflict {
+                    owner: Some(owner),
+                    path: file_path,
+                })
+            } else if existing.is_some() && !own_files.contains(&file_path) {
+                Some(FileConflict {
+                    owner: None,
+                    path: file_path,
+                })
+            } else {
+                None
+            };
+
+            let Some(conflict) = conflict else {
+                continue;
+            };
+
+            if !incoming_is_dir
+                && overwrite
+                    .iter()
+                    .any(|pattern| pattern.matches_path(&conflict.path))
+            {
+                overwritten.push(conflict);
+            } else {
+                conflicts.push(conflict);
+            }
+        }
+
+        Ok((conflicts, overwritten))
+    }
+}
@@ -1,17 +1,39 @@
-use super::ExtractedPackage;
-use super::{Installer, InstallerState};
+use super::{ExtractedPackage, FileEntry, FileType, InstallReason, LinkStrategy};
+use super::{read_file_entry, ConflictChecker, FileConflict, Transaction};
+use super::link::link_or_copy;
+use super::{ScriptOutput, ScriptPhase, ScriptRunner};
+use super::{InstallPolicy, Installer, InstallerState, RemovePolicy};
 use super::{InstallerError, InstallerResult, InstallerStabbyResult};
 
-use crate::database::{Database, PackageDatabase};
+use crate::checksum::sha256_file;
+use crate::config::config::UpacConfig;
+use crate::database::{open_database, Database, DatabaseBackend};
+use crate::database::{NameMatch, PackageFilter};
+use crate::resolver::{DependencyResolver, Resolver};
+use crate::verifier::{PackageVerifier, Verifier, VerifyReport};
+use crate::version::VersionSchemes;
 
+use upac_types::{DatabaseError, Package, PackageScripts};
+
+use stabby::option::Option as StabOption;
 use stabby::result::Result as StabResult;
 use stabby::str::Str as StabStr;
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
 
 use nix::unistd::{Gid, Uid, chown};
 
-use std::ffi::c_void;
+use glob::Pattern;
+
+use regex::Regex;
+
+use time::OffsetDateTime;
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_void, OsString};
 use std::fs;
-use std::os::unix::fs::MetadataExt;
+use std::io::ErrorKind;
+use std::os::unix::fs::{self as unix_fs, lchown, MetadataExt};
 use std::path::{Path, PathBuf};
 
 pub struct PackageInstaller {
@@ -20,6 +42,77 @@ pub struct PackageInstaller {
     repo_path: String,
     temp_path: String,
     database: Box<dyn Database>,
+    script_outputs: Vec<ScriptOutput>,
+    config_patterns: Vec<Pattern>,
+    version_schemes: VersionSchemes,
+}
+
+// Suffixes of configuration files kept next to a locally modified one
+const NEW_CONFIG_SUFFIX: &str = ".upacnew";
+const SAVED_CONFIG_SUFFIX: &str = ".upacsave";
+
+// Handling of an installed configuration file when a new version of its package is installed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConfigAction {
+    // Modified locally and unchanged by the package, the local file stays
+    Keep,
+    // Modified locally and changed by the package, the new file goes next to it as .upacnew
+    InstallNew,
+}
+
+// Installed package as the database records it, kept to restore the record on rollback
+struct RecordedPackage {
+    package: Package,
+    files: Vec<FileEntry>,
+    scripts: PackageScripts,
+}
+
+impl RecordedPackage {
+    // Function to rebuild the package description add_package takes; the install date is renewed
+    fn to_extracted(&self) -> ExtractedPackage {
+        let to_stab_vec = |values: &[String]| -> StabVec<StabString> {
+            values.iter().map(|value| value.as_str().into()).collect()
+        };
+        let script = |script: &Option<String>| match script {
+            Some(script) => StabOption::Some(StabString::from(script.as_str())),
+            None => StabOption::None(),
+        };
+        let file_paths = |config_only: bool| -> StabVec<StabString> {
+            self.files
+                .iter()
+                .filter(|file| file.config || !config_only)
+                .map(|file| file.path.to_string_lossy().as_ref().into())
+                .collect()
+        };
+
+        ExtractedPackage {
+            name: self.package.name.as_str().into(),
+            version: self.package.version.as_str().into(),
+            format: self.package.format.as_str().into(),
+            file_list: file_paths(false),
+            dependencies: to_stab_vec(&self.package.dependencies),
+            provides: to_stab_vec(&self.package.provides),
+            conflicts: to_stab_vec(&self.package.conflicts),
+            config_files: file_paths(true),
+            pre_install: script(&self.scripts.pre_install),
+            post_install: script(&self.scripts.post_install),
+            pre_remove: script(&self.scripts.pre_remove),
+            post_remove: script(&self.scripts.post_remove),
+        }
+    }
+}
+
+// Package applied within a transaction, with what is left to do once it is committed
+struct StagedInstall {
+    name: String,
+    new_version: String,
+    old_version: Option<String>,
+    post_install: Option<String>,
+    // Directories of the old version to remove after the commit
+    dropped_dirs: Vec<PathBuf>,
+    // Database records changed by the package: its previous version and files taken from other owners
+    replaced: Option<RecordedPackage>,
+    overwritten: Vec<(String, FileEntry)>,
 }
 
 impl PackageInstaller {
@@ -41,13 +134,38 @@ impl PackageInstaller {
             repo.create(ostree::RepoMode::BareUser, ostree::gio::Cancellable::NONE)?;
         }
 
-        Ok(Self {
+        Ok(Self::with_repo_dir(root_path, repo_path, temp_path, database))
+    }
+
+    // Function to build an installer over a repo directory that is already in place; files are
+    // linked from it directly, so opening it as an OSTree repo is left to `new`
+    pub(crate) fn with_repo_dir(
+        root_path: String,
+        repo_path: String,
+        temp_path: String,
+        database: Box<dyn Database>,
+    ) -> Self {
+        Self {
             state: InstallerState::Idle,
             root_path,
             repo_path,
             temp_path,
             database,
-        })
+            script_outputs: Vec::new(),
+            config_patterns: Vec::new(),
+            version_schemes: VersionSchemes::new(),
+        }
+    }
+
+    // Function to set globs of paths protected as configuration files in addition to those packages declare
+    pub fn set_config_files(&mut self, patterns: &[&str]) -> InstallerResult<()> {
+        self.config_patterns = ConflictChecker::compile_patterns(patterns)?;
+        Ok(())
+    }
+
+    // Function to set the version ordering of each format, normally the one the backends state
+    pub fn set_version_schemes(&mut self, version_schemes: VersionSchemes) {
+        self.version_schemes = version_schemes;
     }
 
     pub fn state(&self) -> &InstallerState {
@@ -58,6 +176,225 @@ impl PackageInstaller {
         self.state = state;
     }
 
+    // Outputs of the scripts run by the last operation
+    pub fn script_outputs(&self) -> &[ScriptOutput] {
+        &self.script_outputs
+    }
+
+    // Function to check installed files of one package, or of all packages, against the database
+    pub fn verify(&self, package: Option<&str>) -> InstallerResult<VerifyReport> {
+        let root_path = PathBuf::from(&self.root_path);
+        let repo_path = PathBuf::from(&self.repo_path);
+
+        PackageVerifier::new(self.database.as_ref(), &root_path, &repo_path).verify(package)
+    }
+
+    // Function to run a package script if present; only a failing pre-script is an error
+    fn run_script(
+        &mut self,
+        phase: ScriptPhase,
+        package: &str,
+        script: Option<&str>,
+        new_version: Option<&str>,
+        old_version: Option<&str>,
+    ) -> InstallerResult<()> {
+        let Some(script) = script else {
+            return Ok(());
+        };
+
+        let runner = ScriptRunner::new(
+            PathBuf::from(&self.root_path),
+            PathBuf::from(&self.temp_path),
+        );
+
+        let output = match runner.run(phase, package, script, new_version, old_version) {
+            Ok(output) => output,
+            Err(err) if phase.is_pre() => return Err(err),
+            Err(err) => ScriptOutput {
+                phase,
+                stdout: "".into(),
+                stderr: err.to_string().as_str().into(),
+                exit_code: -1,
+            },
+        };
+
+        let exit_code = output.exit_code;
+        let stderr = output.stderr.to_string();
+        self.script_outputs.push(output);
+
+        if exit_code != 0 && phase.is_pre() {
+            return Err(InstallerError::Script(
+                format!(
+                    "{} script of {package} exited with code {exit_code}: {}",
+                    phase.as_str(),
+                    stderr.trim()
+                )
+                .into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Function to run a script after the operation is committed; a failure cannot undo anything
+    // anymore, so it is only reported in the script outputs
+    fn run_post_script(
+        &mut self,
+        phase: ScriptPhase,
+        package: &str,
+        script: Option<&str>,
+        new_version: Option<&str>,
+        old_version: Option<&str>,
+    ) {
+        debug_assert!(!phase.is_pre());
+        let _ = self.run_script(phase, package, script, new_version, old_version);
+    }
+
+    fn is_config(&self, package: &ExtractedPackage, file_path: &Path) -> bool {
+        package
+            .config_files
+            .iter()
+            .any(|config| Path::new(config.trim_start_matches('/')) == file_path)
+            || self
+                .config_patterns
+                .iter()
+                .any(|pattern| pattern.matches_path(file_path))
+    }
+
+    // Function to check whether an installed regular file differs from the recorded package version
+    fn is_modified(path: &Path, entry: &FileEntry) -> InstallerResult<bool> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_file() => {
+                Ok(entry.sha256.as_deref() != Some(sha256_file(path)?.as_str()))
+            }
+            Ok(_) => Ok(false),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Function to decide which installed configuration files must not be replaced by the new version
+    fn plan_config_files(
+        &self,
+        package: &ExtractedPackage,
+        installed_files: &HashMap<PathBuf, FileEntry>,
+    ) -> InstallerResult<HashMap<PathBuf, ConfigAction>> {
+        let root_path = PathBuf::from(&self.root_path);
+        let temp_dir_path = PathBuf::from(&self.temp_path);
+        let mut actions = HashMap::new();
+
+        for file_path in package
+            .file_list
+            .iter()
+            .map(|string| PathBuf::from(string.as_str()))
+        {
+            let Some(installed) = installed_files.get(&file_path) else {
+                continue;
+            };
+            if !self.is_config(package, &file_path) || installed.file_type != FileType::Regular {
+                continue;
+            }
+
+            let dest_path = root_path.join(&file_path);
+            let temp_file_path = temp_dir_path.join(&file_path);
+            if !fs::symlink_metadata(&temp_file_path)?.is_file()
+                || !Self::is_modified(&dest_path, installed)?
+            {
+                continue;
+            }
+
+            let current_sum = sha256_file(&dest_path)?;
+            let new_sum = sha256_file(&temp_file_path)?;
+
+            if current_sum == new_sum {
+                continue;
+            }
+
+            let action = if installed.sha256.as_deref() == Some(new_sum.as_str()) {
+                ConfigAction::Keep
+            } else {
+                ConfigAction::InstallNew
+            };
+            actions.insert(file_path, action);
+        }
+
+        Ok(actions)
+    }
+
+    // Function to drop files of the installed version the new one no longer ships; returns directories to remove after commit
+    fn remove_dropped_files(
+        &mut self,
+        package: &ExtractedPackage,
+        installed_files: &[FileEntry],
+        transaction: &mut Transaction,
+    ) -> InstallerResult<Vec<PathBuf>> {
+        let root_path = PathBuf::from(&self.root_path);
+        let repo_path = PathBuf::from(&self.repo_path);
+
+        let shipped: HashSet<&Path> = package
+            .file_list
+            .iter()
+            .map(|string| Path::new(string.as_str()))
+            .collect();
+        let mut dropped_dirs = Vec::new();
+
+        for entry in installed_files.iter().rev() {
+            if shipped.contains(entry.path.as_path()) {
+                continue;
+            }
+
+            let shared = self
+                .database
+                .owner_of(&entry.path)
+                .map(|owners| owners.iter().any(|owner| owner != package.name.as_str()))
+                .unwrap_or(false);
+            if shared {
+                continue;
+            }
+
+            if entry.file_type == FileType::Directory {
+                dropped_dirs.push(entry.path.clone());
+                continue;
+            }
+
+            let dest_path = root_path.join(&entry.path);
+            if entry.config && Self::is_modified(&dest_path, entry)? {
+                transaction.rename(&dest_path, &with_suffix(&dest_path, SAVED_CONFIG_SUFFIX))?;
+            } else {
+                transaction.backup(&dest_path)?;
+            }
+            transaction.backup(&repo_path.join(&entry.path))?;
+        }
+
+        Ok(dropped_dirs)
+    }
+
+    // Function to give a symlink the owner of the source link; symlink modes are not used on Linux
+    fn copy_link_ownership(&mut self, source_path: &Path, dest_path: &Path) -> InstallerResult<()> {
+        let link_metadata = fs::symlink_metadata(source_path)?;
+        lchown(
+            dest_path,
+            Some(link_metadata.uid()),
+            Some(link_metadata.gid()),
+        )?;
+        Ok(())
+    }
+
+    // Function to delete an installed entry without following symlinks; directories go only once empty
+    fn remove_entry(path: &Path) -> InstallerResult<()> {
+        let result = match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir(path),
+            Ok(_) => fs::remove_file(path),
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) if err.kind() == ErrorKind::DirectoryNotEmpty => Ok(()),
+            result => result.map_err(InstallerError::from),
+        }
+    }
+
     fn copy_with_permissions(
         &mut self,
         source_path: &Path,
@@ -73,28 +410,133 @@ impl PackageInstaller {
         .map_err(|err| InstallerError::Io(err.to_string().into()))?;
         Ok(())
     }
-}
 
-impl Installer for PackageInstaller {
-    fn install(&mut self, package: ExtractedPackage) -> InstallerResult<()> {
-        self.set_state(InstallerState::Preparing);
+    // Function to copy package files into the repo and link them into the root
+    fn install_files(
+        &mut self,
+        package: &ExtractedPackage,
+        replaced_paths: &HashSet<PathBuf>,
+        config_actions: &HashMap<PathBuf, ConfigAction>,
+        transaction: &mut Transaction,
+    ) -> InstallerResult<HashMap<PathBuf, LinkStrategy>> {
+        let mut strategies = HashMap::new();
+
+        let root_path = PathBuf::from(&self.root_path);
+        let repo_path = PathBuf::from(&self.repo_path);
+        let temp_dir_path = PathBuf::from(&self.temp_path);
+
+        for file_path in package
+            .file_list
+            .iter()
+            .map(|string| PathBuf::from(string.as_str()))
+        {
+            self.set_state(InstallerState::Copying);
+
+            let temp_file_path = temp_dir_path.join(&file_path);
+            let repo_file_path = repo_path.join(&file_path);
+            let dest_path = root_path.join(&file_path);
+
+            let temp_file_metadata = fs::symlink_metadata(&temp_file_path)?;
+
+            if temp_file_metadata.is_dir() {
+                transaction.create_dir_all(&repo_file_path)?;
+                transaction.create_dir_all(&dest_path)?;
+                self.copy_with_permissions(&temp_file_path, &repo_file_path)?;
+            } else if temp_file_metadata.file_type().is_symlink() {
+                // Links are recreated with the exact target, relative targets stay relative
+                let link_target = fs::read_link(&temp_file_path)?;
 
+                if let Some(parent) = repo_file_path.parent() {
+                    transaction.create_dir_all(parent)?;
+                }
+                if let Some(parent) = dest_path.parent() {
+                    transaction.create_dir_all(parent)?;
+                }
+
+                transaction.replace(&repo_file_path, |staging_path| {
+                    unix_fs::symlink(&link_target, staging_path)?;
+                    self.copy_link_ownership(&temp_file_path, staging_path)
+                })?;
+
+                // Installed paths are swapped in a single rename, new ones must not exist yet
+                if replaced_paths.contains(&file_path) {
+                    transaction.replace(&dest_path, |staging_path| {
+                        unix_fs::symlink(&link_target, staging_path)?;
+                        self.copy_link_ownership(&temp_file_path, staging_path)
+                    })?;
+                } else {
+                    transaction.symlink(&link_target, &dest_path)?;
+                    self.copy_link_ownership(&temp_file_path, &dest_path)?;
+                }
+            } else {
+                if let Some(parent) = repo_file_path.parent() {
+                    transaction.create_dir_all(parent)?;
+                }
+                if let Some(parent) = dest_path.parent() {
+                    transaction.create_dir_all(parent)?;
+                }
+
+                transaction.replace(&repo_file_path, |staging_path| {
+                    fs::copy(&temp_file_path, staging_path)?;
+                    self.copy_with_permissions(&temp_file_path, staging_path)
+                })?;
+
+                // Locally modified configuration files are never overwritten
+                let link_path = match config_actions.get(&file_path) {
+                    Some(ConfigAction::Keep) => continue,
+                    Some(ConfigAction::InstallNew) => with_suffix(&dest_path, NEW_CONFIG_SUFFIX),
+                    None => dest_path,
+                };
+
+                // Copies on another filesystem do not share the inode and need their own attributes
+                let strategy = if config_actions.contains_key(&file_path)
+                    || replaced_paths.contains(&file_path)
+                {
+                    transaction.replace(&link_path, |staging_path| {
+                        let strategy = link_or_copy(&repo_file_path, staging_path)?;
+                        if strategy != LinkStrategy::HardLink {
+                            self.copy_with_permissions(&temp_file_path, staging_path)?;
+                        }
+                        Ok(strategy)
+                    })?
+                } else {
+                    let strategy = transaction.link(&repo_file_path, &link_path)?;
+                    if strategy != LinkStrategy::HardLink {
+                        self.copy_with_permissions(&temp_file_path, &link_path)?;
+                    }
+                    strategy
+                };
+
+                if !config_actions.contains_key(&file_path) {
+                    strategies.insert(file_path, strategy);
+                }
+            }
+        }
+
+        Ok(strategies)
+    }
+
+    // Function to check a package extracted into the temp directory and apply its files and database
+    // record within a transaction; the caller commits it and then finishes the package
+    fn stage_install(
+        &mut self,
+        package: &ExtractedPackage,
+        policy: &InstallPolicy,
+        transaction: &mut Transaction,
+    ) -> InstallerResult<StagedInstall> {
         let root_path = PathBuf::from(&self.root_path);
         let repo_path = PathBuf::from(&self.repo_path);
         let temp_dir_path = PathBuf::from(&self.temp_path);
 
         if !root_path.exists() {
-            self.set_state(InstallerState::Failed);
             return Err(InstallerError::Installer("Root path not found".into()));
         }
 
         if !repo_path.exists() {
-            self.set_state(InstallerState::Failed);
             return Err(InstallerError::Installer("Repo path not found".into()));
         }
 
         if !temp_dir_path.exists() {
-            self.set_state(InstallerState::Failed);
             return Err(InstallerError::Installer("Temp path not found".into()));
         }
 
@@ -103,133 +545,741 @@ impl Installer for PackageInstaller {
             .iter()
             .map(|string| PathBuf::from(string.as_str()))
         {
-            if !file_path.exists() {
-                self.set_state(InstallerState::Failed);
+            if fs::symlink_metadata(temp_dir_path.join(&file_path)).is_err() {
                 return Err(InstallerError::Installer(
                     format!("File not found: {}", file_path.display()).into(),
                 ));
             }
         }
 
-        for file_path in package
-            .file_list
+        let missing_dependencies = DependencyResolver::new(self.database.as_ref(), &self.version_schemes)
+            .missing_dependencies(package)
+            .map_err(InstallerError::from)?;
+
+        if !missing_dependencies.is_empty() {
+            return Err(InstallerError::Dependency(
+                format!(
+                    "Missing dependencies for {}: {}",
+                    package.name,
+                    missing_dependencies.join(", ")
+                )
+                .into(),
+            ));
+        }
+
+        let conflicting_packages = DependencyResolver::new(self.database.as_ref(), &self.version_schemes)
+            .conflicting_packages(package)
+            .map_err(InstallerError::from)?;
+
+        if !conflicting_packages.is_empty() {
+            return Err(InstallerError::Conflict(
+                format!(
+                    "{} conflicts with installed packages: {}",
+                    package.name,
+                    conflicting_packages.join(", ")
+                )
+                .into(),
+            ));
+        }
+
+        let overwrite_patterns: Vec<&str> = policy.overwrite.iter().map(|s| s.as_str()).collect();
+        let overwrite_patterns = ConflictChecker::compile_patterns(&overwrite_patterns)?;
+
+        let (conflicts, overwritten) =
+            ConflictChecker::new(self.database.as_ref(), &root_path, &temp_dir_path)
+                .check(package, &overwrite_patterns)?;
+
+        if !conflicts.is_empty() {
+            let conflicts: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+            return Err(InstallerError::Conflict(conflicts.join(", ").into()));
+        }
+
+        // Only a package missing from the database is not installed, any other error stops here
+        let replaced = match self.database.get_package(&package.name) {
+            Ok(installed) => Some(RecordedPackage {
+                files: self
+                    .database
+                    .get_file_entries(&installed.name)
+                    .map_err(InstallerError::from)?,
+                scripts: self
+                    .database
+                    .get_package_scripts(&installed.name)
+                    .map_err(InstallerError::from)?,
+                package: installed,
+            }),
+            Err(DatabaseError::NotFound) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        // Files the package already owns and allowed overwrites are replaced in place
+        let installed_files = replaced
+            .as_ref()
+            .map(|installed| installed.files.clone())
+            .unwrap_or_default();
+        let installed_by_path: HashMap<PathBuf, FileEntry> = installed_files
             .iter()
-            .map(|string| PathBuf::from(string.as_str()))
-        {
-            self.set_state(InstallerState::Copying);
+            .map(|entry| (entry.path.clone(), entry.clone()))
+            .collect();
 
-            let temp_file_path = temp_dir_path.join(&file_path);
-            let repo_file_path = repo_path.join(&file_path);
-            let dest_path = root_path.join(&file_path);
+        let mut replaced_paths: HashSet<PathBuf> = installed_by_path.keys().cloned().collect();
+        replaced_paths.extend(overwritten.iter().map(|conflict| conflict.path.clone()));
 
-            if temp_file_path.is_dir() {
-                fs::create_dir_all(&repo_file_path)?;
-                fs::create_dir_all(&dest_path)?;
-                self.copy_with_permissions(&temp_file_path, &repo_file_path)?;
-            } else {
-                if let Some(parent) = repo_file_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                if let Some(parent) = dest_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
+        let config_actions = self.plan_config_files(package, &installed_by_path)?;
 
-                fs::copy(&temp_file_path, &repo_file_path)?;
-                self.copy_with_permissions(&temp_file_path, &repo_file_path)?;
+        let scripts = package.scripts();
+        let new_version = package.version.to_string();
+        let old_version = replaced
+            .as_ref()
+            .map(|installed| installed.package.version.clone());
 
-                fs::hard_link(&repo_file_path, &dest_path)?;
-            }
+        // Replacing an installed version is an upgrade: the removal scripts of the old version run
+        // around the swap, like dpkg runs them
+        if let Some(installed) = &replaced {
+            self.run_script(
+                ScriptPhase::PreRemove,
+                &package.name,
+                installed.scripts.pre_remove.as_deref(),
+                Some(&new_version),
+                old_version.as_deref(),
+            )?;
         }
 
+        self.run_script(
+            ScriptPhase::PreInstall,
+            &package.name,
+            scripts.pre_install.as_deref(),
+            Some(&new_version),
+            old_version.as_deref(),
+        )?;
+
+        let strategies =
+            self.install_files(package, &replaced_paths, &config_actions, transaction)?;
+        let dropped_dirs = self.remove_dropped_files(package, &installed_files, transaction)?;
+
         self.set_state(InstallerState::Registering);
+
+        // Metadata is taken from the installed files so it can be verified later;
+        // kept configuration files are described by the packaged version instead
+        let mut files = package
+            .file_list
+            .iter()
+            .map(|file_path| {
+                let file_path = Path::new(file_path.as_str());
+                if config_actions.contains_key(file_path) {
+                    read_file_entry(&repo_path, file_path)
+                } else {
+                    read_file_entry(&root_path, file_path)
+                }
+            })
+            .collect::<InstallerResult<Vec<FileEntry>>>()?;
+
+        for file in &mut files {
+            file.link_strategy = strategies.get(&file.path).copied();
+            file.config = self.is_config(package, &file.path);
+        }
+
+        let mut staged = StagedInstall {
+            name: package.name.to_string(),
+            new_version,
+            old_version,
+            post_install: scripts.post_install,
+            dropped_dirs,
+            replaced,
+            overwritten: Vec::new(),
+        };
+
+        // The database is written last, a failure part way puts back what was already changed
+        if let Err(err) = self.register(package, policy.reason, &files, &overwritten, &mut staged) {
+            return Err(self.restore_failed(std::slice::from_ref(&staged), err));
+        }
+
+        Ok(staged)
+    }
+
+    // Function to record a staged package and hand it the overwritten files of other owners; every
+    // changed owner is noted in `staged` as soon as it changes
+    fn register(
+        &mut self,
+        package: &ExtractedPackage,
+        reason: InstallReason,
+        files: &[FileEntry],
+        overwritten: &[FileConflict],
+        staged: &mut StagedInstall,
+    ) -> InstallerResult<()> {
         self.database
-            .add_package(&package)
+            .add_package(package, reason, files)
             .map_err(InstallerError::from)?;
-        self.set_state(InstallerState::Success);
 
-        Ok(())
-    }
+        for conflict in overwritten {
+            if let Some(owner) = &conflict.owner {
+                let entry = self
+                    .database
+                    .get_file_entries(owner)
+                    .map_err(InstallerError::from)?
+                    .into_iter()
+                    .find(|entry| entry.path == conflict.path);
 
-    fn remove(&mut self, package: &str) -> InstallerResult<()> {
-        self.set_state(InstallerState::Preparing);
+                self.database
+                    .remove_file(owner, &conflict.path)
+                    .map_err(InstallerError::from)?;
 
-        let package_files_paths = self
-            .database
-            .get_package_files(package)
-            .map_err(InstallerError::from)?;
+                if let Some(entry) = entry {
+                    staged.overwritten.push((owner.clone(), entry));
+                }
+            }
+        }
+
+        Ok(())
+    }
 
+    // Function to finish a committed package: the post-remove script of a replaced version runs,
+    // its directories go once nothing else is left in them, then the post-install script runs. The
+    // package stays installed either way, a directory that could not be removed is returned last
+    fn finish_install(&mut self, staged: StagedInstall) -> InstallerResult<()> {
         let root_path = PathBuf::from(&self.root_path);
         let repo_path = PathBuf::from(&self.repo_path);
 
-        for file_path in package_files_paths {
-            self.set_state(InstallerState::Deleting);
+        if let Some(installed) = &staged.replaced {
+            self.run_post_script(
+                ScriptPhase::PostRemove,
+                &staged.name,
+                installed.scripts.post_remove.as_deref(),
+                Some(&staged.new_version),
+                staged.old_version.as_deref(),
+            );
+        }
 
-            let root_file = root_path.join(&file_path);
-            let repo_file = repo_path.join(&file_path);
+        let removed = staged.dropped_dirs.iter().try_for_each(|dir_path| {
+            Self::remove_entry(&root_path.join(dir_path))?;
+            Self::remove_entry(&repo_path.join(dir_path))
+        });
 
-            if root_file.is_dir() {
-                fs::remove_dir_all(&root_file)?;
-            } else if root_file.exists() {
-                fs::remove_file(&root_file)?;
-            }
+        self.run_post_script(
+            ScriptPhase::PostInstall,
+            &staged.name,
+            staged.post_install.as_deref(),
+            Some(&staged.new_version),
+            staged.old_version.as_deref(),
+        );
 
-            if repo_file.is_dir() {
-                fs::remove_dir_all(&repo_file)?;
-            } else if repo_file.exists() {
-                fs::remove_file(&repo_file)?;
-            }
+        removed
+    }
+
+    // Function to put back the database records a staged package replaced; the new record may be
+    // missing when registering it failed
+    fn restore_records(&mut self, staged: &StagedInstall) -> InstallerResult<()> {
+        match self.database.remove_package(&staged.name) {
+            Ok(()) | Err(DatabaseError::NotFound) => {}
+            Err(err) => return Err(err.into()),
         }
 
-        self.set_state(InstallerState::Registering);
-        self.database
-            .remove_package(package)
-            .map_err(InstallerError::from)?;
-        self.set_state(InstallerState::Success);
+        if let Some(replaced) = &staged.replaced {
+            self.database
+                .add_package(
+                    &replaced.to_extracted(),
+                    replaced.package.reason,
+                    &replaced.files,
+                )
+                .map_err(InstallerError::from)?;
+        }
+
+        for (owner, entry) in &staged.overwritten {
+            self.database
+                .add_file(owner, entry)
+                .map_err(InstallerError::from)?;
+        }
 
         Ok(())
     }
-}
 
-// Публичные extern "C" функции
-#[no_mangle]
-pub extern "C" fn upac_new(
-    root_path: StabStr,
-    repo_path: StabStr,
-    temp_path: StabStr,
-    database_path: StabStr,
-) -> StabResult<*mut c_void, InstallerError> {
-    let database = match PackageDatabase::new(PathBuf::from(database_path.as_str())) {
-        Ok(database) => database,
-        Err(err) => return Err(InstallerError::from(err)).into(),
-    };
+    // Function to restore the records of staged packages after a failure, keeping the original error.
+    // Newest first, so a record changed by several packages ends up as it was
+    fn restore_failed(
+        &mut self,
+        staged_installs: &[StagedInstall],
+        err: InstallerError,
+    ) -> InstallerError {
+        let restored = staged_installs
+            .iter()
+            .rev()
+            .try_for_each(|staged| self.restore_records(staged));
 
-    let installer = Box::new(PackageInstaller::new(
-        root_path.as_str().to_owned(),
-        repo_path.as_str().to_owned(),
-        temp_path.as_str().to_owned(),
-        Box::new(database),
-    ));
+        match restored {
+            Ok(()) => err,
+            Err(restore_err) => InstallerError::Installer(
+                format!("{err}; restoring the database failed: {restore_err}").into(),
+            ),
+        }
+    }
 
-    Ok(Box::into_raw(installer) as *mut c_void).into()
-}
+    // Function to move the files of a removed package out of the way within a transaction and
+    // return its directories, removed after the commit. Locally modified configuration files are
+    // kept aside as .upacsave unless purging
+    fn stage_remove(
+        &self,
+        package: &str,
+        package_files: &[FileEntry],
+        policy: &RemovePolicy,
+        transaction: &mut Transaction,
+    ) -> InstallerResult<Vec<PathBuf>> {
+        let root_path = PathBuf::from(&self.root_path);
+        let repo_path = PathBuf::from(&self.repo_path);
+        let mut dropped_dirs = Vec::new();
 
-#[no_mangle]
-pub extern "C" fn upac_install(
-    installer: *mut c_void,
-    package: ExtractedPackage,
-) -> InstallerStabbyResult<()> {
-    let installer = unsafe { &mut *(installer as *mut PackageInstaller) };
-    installer.install(package).into()
-}
+        // Entries go in reverse order so directories are emptied before they are removed
+        for entry in package_files.iter().rev() {
+            // Directories shared with other packages stay in place
+            let shared = self
+                .database
+                .owner_of(&entry.path)
+                .map(|owners| owners.iter().any(|owner| owner != package))
+                .unwrap_or(false);
+            if shared {
+                continue;
+            }
 
-#[no_mangle]
-pub extern "C" fn upac_remove(
-    installer: *mut c_void,
-    package: StabStr,
-) -> InstallerStabbyResult<()> {
-    let installer = unsafe { &mut *(installer as *mut PackageInstaller) };
-    installer.remove(package.as_str()).into()
-}
+            let dest_path = root_path.join(&entry.path);
+
+            let is_dir = fs::symlink_metadata(&dest_path)
+                .map(|metadata| metadata.is_dir())
+                .unwrap_or(entry.file_type == FileType::Directory);
+            if is_dir {
+                dropped_dirs.push(entry.path.clone());
+                continue;
+            }
+
+            if entry.config && policy.purge {
+                transaction.backup(&with_suffix(&dest_path, NEW_CONFIG_SUFFIX))?;
+                transaction.backup(&with_suffix(&dest_path, SAVED_CONFIG_SUFFIX))?;
+            }
+
+            if entry.config && !policy.purge && Self::is_modified(&dest_path, entry)? {
+                transaction.rename(&dest_path, &with_suffix(&dest_path, SAVED_CONFIG_SUFFIX))?;
+            } else {
+                transaction.backup(&dest_path)?;
+            }
+            transaction.backup(&repo_path.join(&entry.path))?;
+        }
+
+        Ok(dropped_dirs)
+    }
+
+    // Function to check that replacing installed versions keeps the dependencies of other packages
+    // satisfied. Runs once every package of the set is recorded, so a dependent replaced in the
+    // same set is checked against its new version
+    fn check_dependents(&self, staged_installs: &[StagedInstall]) -> InstallerResult<()> {
+        let resolver = DependencyResolver::new(self.database.as_ref(), &self.version_schemes);
+
+        for staged in staged_installs {
+            let Some(replaced) = &staged.replaced else {
+                continue;
+            };
+
+            let broken_dependents = resolver
+                .broken_dependents(&replaced.package)
+                .map_err(InstallerError::from)?;
+
+            if !broken_dependents.is_empty() {
+                return Err(InstallerError::Dependency(
+                    format!(
+                        "{} {} breaks dependencies of: {}",
+                        staged.name,
+                        staged.new_version,
+                        broken_dependents.join(", ")
+                    )
+                    .into(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Function to undo the files of a failed transaction, keeping the original error
+    fn roll_back(&mut self, transaction: Transaction, err: InstallerError) -> InstallerError {
+        self.set_state(InstallerState::RollingBack);
+        let rollback_result = transaction.rollback();
+        self.set_state(InstallerState::Failed);
+
+        match rollback_result {
+            Ok(()) => err,
+            Err(rollback_err) => {
+                InstallerError::Installer(format!("{err}; rollback failed: {rollback_err}").into())
+            }
+        }
+    }
+}
+
+impl Installer for PackageInstaller {
+    fn install(&mut self, package: ExtractedPackage, policy: InstallPolicy) -> InstallerResult<()> {
+        self.set_state(InstallerState::Preparing);
+        self.script_outputs.clear();
+
+        let mut transaction = Transaction::new();
+
+        let staged = match self.stage_install(&package, &policy, &mut transaction) {
+            Ok(staged) => staged,
+            Err(err) => return Err(self.roll_back(transaction, err)),
+        };
+
+        if let Err(err) = self.check_dependents(std::slice::from_ref(&staged)) {
+            let err = self.restore_failed(std::slice::from_ref(&staged), err);
+            return Err(self.roll_back(transaction, err));
+        }
+
+        transaction.commit();
+
+        let finished = self.finish_install(staged);
+        self.set_state(InstallerState::Success);
+
+        finished
+    }
+
+    fn install_all(
+        &mut self,
+        packages: impl IntoIterator<Item = InstallerResult<(ExtractedPackage, InstallPolicy)>>,
+    ) -> InstallerResult<()> {
+        self.set_state(InstallerState::Preparing);
+        self.script_outputs.clear();
+
+        let mut transaction = Transaction::new();
+        let mut staged_installs = Vec::new();
+
+        for next in packages {
+            let result = next.and_then(|(package, policy)| {
+                self.stage_install(&package, &policy, &mut transaction)
+            });
+
+            let err = match result {
+                Ok(staged) => {
+                    staged_installs.push(staged);
+                    continue;
+                }
+                Err(err) => err,
+            };
+
+            // The failed package has already put back its own records
+            let err = self.restore_failed(&staged_installs, err);
+            return Err(self.roll_back(transaction, err));
+        }
+
+        if let Err(err) = self.check_dependents(&staged_installs) {
+            let err = self.restore_failed(&staged_installs, err);
+            return Err(self.roll_back(transaction, err));
+        }
+
+        transaction.commit();
+
+        // Every committed package is finished, the first error is reported afterwards
+        let mut first_error = None;
+        for staged in staged_installs {
+            if let Err(err) = self.finish_install(staged) {
+                first_error.get_or_insert(err);
+            }
+        }
+        self.set_state(InstallerState::Success);
+
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn upgrade(
+        &mut self,
+        old: &str,
+        package: ExtractedPackage,
+        policy: InstallPolicy,
+    ) -> InstallerResult<()> {
+        self.set_state(InstallerState::Preparing);
+        self.script_outputs.clear();
+
+        if package.name.as_str() != old {
+            self.set_state(InstallerState::Failed);
+            return Err(InstallerError::Installer(
+                format!(
+                    "{old} can only be upgraded to a package of the same name, not {}",
+                    package.name
+                )
+                .into(),
+            ));
+        }
+
+        match self.database.get_package(old) {
+            Ok(_) => {}
+            Err(DatabaseError::NotFound) => {
+                self.set_state(InstallerState::Failed);
+                return Err(InstallerError::Installer(
+                    format!("{old} is not installed").into(),
+                ));
+            }
+            Err(err) => {
+                self.set_state(InstallerState::Failed);
+                return Err(err.into());
+            }
+        }
+
+        // Installing over the recorded version swaps the files in place and runs the scripts of both
+        self.install(package, policy)
+    }
+
+    fn remove(&mut self, package: &str, policy: RemovePolicy) -> InstallerResult<()> {
+        self.set_state(InstallerState::Preparing);
+        self.script_outputs.clear();
+
+        if !policy.force {
+            let dependents = DependencyResolver::new(self.database.as_ref(), &self.version_schemes)
+                .dependents(package)
+                .map_err(InstallerError::from)?;
+
+            if !dependents.is_empty() {
+                self.set_state(InstallerState::Failed);
+                return Err(InstallerError::Dependency(
+                    format!("{package} is required by: {}", dependents.join(", ")).into(),
+                ));
+            }
+        }
+
+        let package_files = self
+            .database
+            .get_file_entries(package)
+            .map_err(InstallerError::from)?;
+
+        let scripts = self
+            .database
+            .get_package_scripts(package)
+            .map_err(InstallerError::from)?;
+        let old_version = self
+            .database
+            .get_package(package)
+            .map_err(InstallerError::from)?
+            .version;
+
+        if let Err(err) = self.run_script(
+            ScriptPhase::PreRemove,
+            package,
+            scripts.pre_remove.as_deref(),
+            None,
+            Some(&old_version),
+        ) {
+            self.set_state(InstallerState::Failed);
+            return Err(err);
+        }
+
+        // Files are moved aside until the record is gone, so a failure part way puts them all back
+        let mut transaction = Transaction::new();
+        self.set_state(InstallerState::Deleting);
+
+        let dropped_dirs =
+            match self.stage_remove(package, &package_files, &policy, &mut transaction) {
+                Ok(dropped_dirs) => dropped_dirs,
+                Err(err) => return Err(self.roll_back(transaction, err)),
+            };
+
+        self.set_state(InstallerState::Registering);
+        if let Err(err) = self.database.remove_package(package) {
+            return Err(self.roll_back(transaction, err.into()));
+        }
+
+        transaction.commit();
+
+        // Directories go once the files moved aside are gone too
+        let root_path = PathBuf::from(&self.root_path);
+        let repo_path = PathBuf::from(&self.repo_path);
+        let removed = dropped_dirs.iter().try_for_each(|dir_path| {
+            Self::remove_entry(&root_path.join(dir_path))?;
+            Self::remove_entry(&repo_path.join(dir_path))
+        });
+
+        self.run_post_script(
+            ScriptPhase::PostRemove,
+            package,
+            scripts.post_remove.as_deref(),
+            None,
+            Some(&old_version),
+        );
+        self.set_state(InstallerState::Success);
+
+        removed
+    }
+}
+
+// Function to name a file kept next to a configuration file
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Публичные extern "C" функции
+#[no_mangle]
+pub extern "C" fn upac_new(
+    root_path: StabStr,
+    repo_path: StabStr,
+    temp_path: StabStr,
+    database_path: StabStr,
+) -> StabResult<*mut c_void, InstallerError> {
+    upac_new_with_backend(
+        root_path,
+        repo_path,
+        temp_path,
+        database_path,
+        DatabaseBackend::Toml,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn upac_new_with_backend(
+    root_path: StabStr,
+    repo_path: StabStr,
+    temp_path: StabStr,
+    database_path: StabStr,
+    backend: DatabaseBackend,
+) -> StabResult<*mut c_void, InstallerError> {
+    match open_installer(
+        root_path.as_str(),
+        repo_path.as_str(),
+        temp_path.as_str(),
+        database_path.as_str(),
+        backend,
+    ) {
+        Ok(installer) => Ok(Box::into_raw(Box::new(installer)) as *mut c_void).into(),
+        Err(err) => Err(err).into(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn upac_new_with_config(
+    config: *mut c_void,
+) -> StabResult<*mut c_void, InstallerError> {
+    let config = unsafe { &*(config as *const UpacConfig) };
+    let config_files: Vec<&str> = config.config_files.iter().map(|s| s.as_str()).collect();
+
+    let installer = open_installer(
+        config.root_dir.as_str(),
+        config.ostree.repo_path.as_str(),
+        config.temp_dir.as_str(),
+        config.database_path.as_str(),
+        config.database_backend,
+    )
+    .and_then(|mut installer| {
+        installer.set_config_files(&config_files)?;
+        Ok(installer)
+    });
+
+    match installer {
+        Ok(installer) => Ok(Box::into_raw(Box::new(installer)) as *mut c_void).into(),
+        Err(err) => Err(err).into(),
+    }
+}
+
+fn open_installer(
+    root_path: &str,
+    repo_path: &str,
+    temp_path: &str,
+    database_path: &str,
+    backend: DatabaseBackend,
+) -> InstallerResult<PackageInstaller> {
+    let database =
+        open_database(backend, PathBuf::from(database_path)).map_err(InstallerError::from)?;
+
+    PackageInstaller::new(
+        root_path.to_owned(),
+        repo_path.to_owned(),
+        temp_path.to_owned(),
+        database,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn upac_install(
+    installer: *mut c_void,
+    package: ExtractedPackage,
+    policy: InstallPolicy,
+) -> InstallerStabbyResult<()> {
+    let installer = unsafe { &mut *(installer as *mut PackageInstaller) };
+    installer.install(package, policy).into()
+}
+
+#[no_mangle]
+pub extern "C" fn upac_upgrade(
+    installer: *mut c_void,
+    old: StabStr,
+    package: ExtractedPackage,
+    policy: InstallPolicy,
+) -> InstallerStabbyResult<()> {
+    let installer = unsafe { &mut *(installer as *mut PackageInstaller) };
+    installer.upgrade(old.as_str(), package, policy).into()
+}
+
+#[no_mangle]
+pub extern "C" fn upac_remove(
+    installer: *mut c_void,
+    package: StabStr,
+    policy: RemovePolicy,
+) -> InstallerStabbyResult<()> {
+    let installer = unsafe { &mut *(installer as *mut PackageInstaller) };
+    installer.remove(package.as_str(), policy).into()
+}
+
+// Empty strings and zero timestamps leave a criterion unset; reason is 0 explicit, 1 dependency, any other value for both
+#[no_mangle]
+pub extern "C" fn upac_list_packages(
+    installer: *mut c_void,
+    name_pattern: StabStr,
+    name_is_regex: bool,
+    format: StabStr,
+    installed_after: i64,
+    installed_before: i64,
+    reason: u8,
+) -> InstallerStabbyResult<StabVec<StabString>> {
+    let installer = unsafe { &*(installer as *mut PackageInstaller) };
+
+    let name = if name_pattern.is_empty() {
+        None
+    } else if name_is_regex {
+        match Regex::new(name_pattern.as_str()) {
+            Ok(regex) => Some(NameMatch::Regex(regex)),
+            Err(err) => {
+                return Err(InstallerError::Installer(
+                    format!("Invalid name pattern: {err}").into(),
+                ))
+                .into()
+            }
+        }
+    } else {
+        Some(NameMatch::Substring(name_pattern.as_str().to_owned()))
+    };
+
+    let timestamp = |seconds: i64| match seconds {
+        0 => None,
+        seconds => OffsetDateTime::from_unix_timestamp(seconds).ok(),
+    };
+
+    let filter = PackageFilter {
+        name,
+        format: (!format.is_empty()).then(|| format.as_str().to_owned()),
+        installed_after: timestamp(installed_after),
+        installed_before: timestamp(installed_before),
+        reason: match reason {
+            0 => Some(InstallReason::Explicit),
+            1 => Some(InstallReason::Dependency),
+            _ => None,
+        },
+    };
+
+    installer
+        .database
+        .filter_packages(&filter)
+        .map(|packages| {
+            packages
+                .into_iter()
+                .map(|package| StabString::from(package.name.as_str()))
+                .collect::<StabVec<StabString>>()
+        })
+        .map_err(InstallerError::from)
+        .into()
+}
 
 #[no_mangle]
 pub extern "C" fn upac_state(installer: *mut c_void) -> InstallerState {
@@ -237,9 +1287,773 @@ pub extern "C" fn upac_state(installer: *mut c_void) -> InstallerState {
     *installer.state()
 }
 
+#[no_mangle]
+pub extern "C" fn upac_script_outputs(installer: *mut c_void) -> StabVec<ScriptOutput> {
+    let installer = unsafe { &*(installer as *mut PackageInstaller) };
+    installer.script_outputs().iter().cloned().collect()
+}
+
 #[no_mangle]
 pub extern "C" fn upac_free(installer: *mut c_void) {
     if !installer.is_null() {
         unsafe { drop(Box::from_raw(installer as *mut PackageInstaller)) };
     }
 }
+
+#[no_mangle]
+pub extern "C" fn upac_verify(
+    installer: *mut c_void,
+    package: StabStr,
+) -> InstallerStabbyResult<VerifyReport> {
+    let installer = unsafe { &*(installer as *const PackageInstaller) };
+
+    // Empty package name verifies every installed package
+    let package = Some(package.as_str()).filter(|package| !package.is_empty());
+    installer.verify(package).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database::PackageDatabase;
+    use crate::testing::{package, stab_vec};
+    use crate::verifier::FileIssueKind;
+
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+
+    use tempfile::TempDir;
+
+    struct TestSystem {
+        dir: TempDir,
+    }
+
+    impl TestSystem {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            for subdir in ["root", "repo", "temp", "db"] {
+                fs::create_dir(dir.path().join(subdir)).unwrap();
+            }
+            Self { dir }
+        }
+
+        fn path(&self, subdir: &str) -> PathBuf {
+            self.dir.path().join(subdir)
+        }
+
+        fn installer(&self) -> PackageInstaller {
+            let path = |subdir| self.path(subdir).display().to_string();
+            let database = PackageDatabase::new(self.path("db")).unwrap();
+            PackageInstaller::with_repo_dir(path("root"), path("repo"), path("temp"), Box::new(database))
+        }
+
+        // Function to replace the extracted package in the temp directory
+        fn stage(&self, files: &[(&str, &str)]) {
+            fs::remove_dir_all(self.path("temp")).unwrap();
+            for (file_path, content) in files {
+                let path = self.path("temp").join(file_path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+        }
+
+        // Function to add a symlink to the extracted package
+        fn stage_link(&self, file_path: &str, target: &str) {
+            let path = self.path("temp").join(file_path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            unix_fs::symlink(target, path).unwrap();
+        }
+
+        fn read(&self, path: &str) -> String {
+            fs::read_to_string(self.path(path)).unwrap()
+        }
+
+        // Function to stage a socket: it passes the checks before copying, then fs::copy cannot open it
+        fn stage_socket(&self, file_path: &str) -> UnixListener {
+            UnixListener::bind(self.path("temp").join(file_path)).unwrap()
+        }
+
+        // Function to list every path under a directory with the content of regular files
+        fn snapshot(&self, subdir: &str) -> Vec<(PathBuf, Option<String>)> {
+            fn walk(base: &Path, dir: &Path, entries: &mut Vec<(PathBuf, Option<String>)>) {
+                for entry in fs::read_dir(dir).unwrap() {
+                    let path = entry.unwrap().path();
+                    let relative = path.strip_prefix(base).unwrap().to_path_buf();
+                    if path.is_dir() {
+                        entries.push((relative, None));
+                        walk(base, &path, entries);
+                    } else {
+                        entries.push((relative, fs::read_to_string(&path).ok()));
+                    }
+                }
+            }
+
+            let mut entries = Vec::new();
+            walk(&self.path(subdir), &self.path(subdir), &mut entries);
+            entries.sort();
+            entries
+        }
+
+        // Function to read the version, install date and files of a package the way the next run
+        // of upac would
+        fn record(&self, name: &str) -> Option<(String, String, Vec<FileEntry>)> {
+            let database = PackageDatabase::new(self.path("db")).unwrap();
+            match database.get_package(name) {
+                Ok(package) => Some((
+                    package.version,
+                    package.install_date,
+                    database.get_file_entries(name).unwrap(),
+                )),
+                Err(DatabaseError::NotFound) => None,
+                Err(err) => panic!("{err}"),
+            }
+        }
+    }
+
+    #[test]
+    fn failed_install_leaves_nothing_behind() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        system.stage(&[("usr/bin/tool", "tool"), ("usr/share/tool/data", "data")]);
+        let _socket = system.stage_socket("usr/share/tool/socket");
+
+        let files = [
+            "usr/bin/tool",
+            "usr/share/tool/data",
+            "usr/share/tool/socket",
+        ];
+        installer
+            .install(package("tool", "1.0", &files), InstallPolicy::default())
+            .unwrap_err();
+
+        assert!(
+            system.snapshot("root").is_empty(),
+            "{:?}",
+            system.snapshot("root")
+        );
+        assert!(system
+            .snapshot("repo")
+            .iter()
+            .all(|(path, _)| !path.starts_with("usr")));
+        assert!(system.record("tool").is_none());
+        assert!(system
+            .installer()
+            .database
+            .owner_of(Path::new("usr/bin/tool"))
+            .unwrap_or_default()
+            .is_empty());
+    }
+
+    #[test]
+    fn failed_upgrade_restores_root_and_database() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        system.stage(&[("usr/bin/tool", "1.0"), ("usr/share/tool/old", "old")]);
+        installer
+            .install(
+                package("tool", "1.0", &["usr/bin/tool", "usr/share/tool/old"]),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+
+        let root = system.snapshot("root");
+        let repo = system.snapshot("repo");
+        let record = system.record("tool").unwrap();
+
+        // The new version replaces tool and adds extra before the copy fails part way
+        system.stage(&[("usr/bin/tool", "2.0"), ("usr/bin/extra", "extra")]);
+        let _socket = system.stage_socket("usr/bin/socket");
+
+        let files = ["usr/bin/tool", "usr/bin/extra", "usr/bin/socket"];
+        installer
+            .install(package("tool", "2.0", &files), InstallPolicy::default())
+            .unwrap_err();
+
+        assert_eq!(system.snapshot("root"), root);
+        assert_eq!(system.snapshot("repo"), repo);
+        assert_eq!(system.record("tool").unwrap(), record);
+        assert_eq!(
+            installer.database.get_package("tool").unwrap().version,
+            "1.0"
+        );
+
+        // The restored version still checks out and can be upgraded later
+        assert!(installer.verify(Some("tool")).unwrap().issues.is_empty());
+        system.stage(&[("usr/bin/tool", "2.0")]);
+        installer
+            .install(
+                package("tool", "2.0", &["usr/bin/tool"]),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(system.path("root/usr/bin/tool")).unwrap(),
+            "2.0"
+        );
+        assert!(!system.path("root/usr/share/tool/old").exists());
+    }
+
+    #[test]
+    fn dependencies_block_install_and_removal() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        let app = || {
+            let mut app = package("app", "1.0", &["usr/bin/app"]);
+            app.dependencies = stab_vec(&["libfoo"]);
+            app
+        };
+
+        system.stage(&[("usr/bin/app", "app")]);
+        let err = installer
+            .install(app(), InstallPolicy::default())
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Dependency error"), "{err}");
+        assert!(system.record("app").is_none());
+
+        system.stage(&[("usr/lib/libfoo.so", "foo")]);
+        installer
+            .install(
+                package("libfoo", "1.0", &["usr/lib/libfoo.so"]),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+        system.stage(&[("usr/bin/app", "app")]);
+        installer.install(app(), InstallPolicy::default()).unwrap();
+
+        let err = installer
+            .remove("libfoo", RemovePolicy::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("required by: app"), "{err}");
+        assert!(system.path("root/usr/lib/libfoo.so").exists());
+
+        // Forcing the removal leaves the dependent package as it is
+        let force = RemovePolicy {
+            force: true,
+            ..RemovePolicy::default()
+        };
+        installer.remove("libfoo", force).unwrap();
+        assert!(!system.path("root/usr/lib/libfoo.so").exists());
+        assert!(system.record("app").is_some());
+    }
+
+    #[test]
+    fn declared_conflicts_and_provides_are_honoured() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        let mut postfix = package("postfix", "3.8", &["usr/sbin/postfix"]);
+        postfix.provides = stab_vec(&["mail-transport"]);
+        postfix.conflicts = stab_vec(&["exim"]);
+        system.stage(&[("usr/sbin/postfix", "postfix")]);
+        installer.install(postfix, InstallPolicy::default()).unwrap();
+
+        // A dependency is met by what an installed package provides
+        let mut mailer = package("mailer", "1.0", &["usr/bin/mailer"]);
+        mailer.dependencies = stab_vec(&["mail-transport"]);
+        system.stage(&[("usr/bin/mailer", "mailer")]);
+        installer.install(mailer, InstallPolicy::default()).unwrap();
+
+        // Conflicts count whichever side declares them
+        system.stage(&[("usr/sbin/exim", "exim")]);
+        let err = installer
+            .install(
+                package("exim", "4.97", &["usr/sbin/exim"]),
+                InstallPolicy::default(),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("exim conflicts with installed packages: postfix"),
+            "{err}"
+        );
+
+        let mut sendmail = package("sendmail", "8.18", &["usr/sbin/sendmail"]);
+        sendmail.conflicts = stab_vec(&["mail-transport"]);
+        system.stage(&[("usr/sbin/sendmail", "sendmail")]);
+        let err = installer
+            .install(sendmail, InstallPolicy::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("postfix"), "{err}");
+        assert!(system.record("exim").is_none());
+        assert!(system.record("sendmail").is_none());
+    }
+
+    #[test]
+    fn scripts_get_versions_in_pacman_order() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+        let log_path = system.path("scripts.log");
+
+        // Every script appends its phase and arguments to a log outside the root
+        let tool = |version: &str| {
+            let script = || {
+                let script = format!("echo \"$UPAC_SCRIPT_PHASE $*\" >> {}", log_path.display());
+                StabOption::Some(StabString::from(script.as_str()))
+            };
+            let mut tool = package("tool", version, &["usr/bin/tool"]);
+            tool.pre_install = script();
+            tool.post_install = script();
+            tool.pre_remove = script();
+            tool.post_remove = script();
+            tool
+        };
+
+        system.stage(&[("usr/bin/tool", "1.0")]);
+        installer
+            .install(tool("1.0"), InstallPolicy::default())
+            .unwrap();
+        system.stage(&[("usr/bin/tool", "2.0")]);
+        installer
+            .upgrade("tool", tool("2.0"), InstallPolicy::default())
+            .unwrap();
+        installer.remove("tool", RemovePolicy::default()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&log_path).unwrap(),
+            "pre_install 1.0\n\
+             post_install 1.0\n\
+             pre_remove 1.0 2.0\n\
+             pre_install 2.0 1.0\n\
+             post_remove 1.0 2.0\n\
+             post_install 2.0 1.0\n\
+             pre_remove 2.0\n\
+             post_remove 2.0\n"
+        );
+    }
+
+    #[test]
+    fn failing_pre_install_script_stops_install() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        let mut tool = package("tool", "1.0", &["usr/bin/tool"]);
+        tool.pre_install = StabOption::Some("echo refused >&2; exit 3".into());
+
+        system.stage(&[("usr/bin/tool", "tool")]);
+        let err = installer
+            .install(tool, InstallPolicy::default())
+            .unwrap_err();
+
+        assert!(
+            err.to_string().starts_with("Script error: pre_install script of tool exited with code 3: refused"),
+            "{err}"
+        );
+        assert!(system.snapshot("root").is_empty());
+        assert!(system.record("tool").is_none());
+    }
+
+    #[test]
+    fn conflicting_files_need_overwrite() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        system.stage(&[("usr/bin/tool", "one")]);
+        installer
+            .install(
+                package("one", "1.0", &["usr/bin/tool"]),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+        fs::create_dir(system.path("root/etc")).unwrap();
+        fs::write(system.path("root/etc/tool.conf"), "local").unwrap();
+
+        let two = || package("two", "1.0", &["usr/bin/tool", "etc/tool.conf"]);
+
+        system.stage(&[("usr/bin/tool", "two"), ("etc/tool.conf", "two")]);
+        let err = installer
+            .install(two(), InstallPolicy::default())
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with("File conflicts"), "{message}");
+        assert!(message.contains("/usr/bin/tool (owned by one)"), "{message}");
+        assert!(message.contains("/etc/tool.conf (exists in filesystem)"), "{message}");
+        assert_eq!(system.read("root/usr/bin/tool"), "one");
+
+        let policy = InstallPolicy {
+            overwrite: stab_vec(&["/usr/bin/*", "etc/tool.conf"]),
+            ..InstallPolicy::default()
+        };
+        installer.install(two(), policy).unwrap();
+
+        assert_eq!(system.read("root/usr/bin/tool"), "two");
+        assert_eq!(system.read("root/etc/tool.conf"), "two");
+        assert_eq!(
+            installer
+                .database
+                .owner_of(Path::new("usr/bin/tool"))
+                .unwrap(),
+            ["two"]
+        );
+        assert!(installer
+            .database
+            .get_package_files("one")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn verify_reports_changed_files() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        system.stage(&[
+            ("usr/bin/tool", "tool"),
+            ("usr/bin/gone", "gone"),
+            ("etc/tool.conf", "conf"),
+        ]);
+        installer
+            .install(
+                package(
+                    "tool",
+                    "1.0",
+                    &["usr/bin/tool", "usr/bin/gone", "etc/tool.conf"],
+                ),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+
+        let report = installer.verify(None).unwrap();
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert_eq!((report.packages_checked, report.files_checked), (1, 3));
+
+        // Writing through the hard link changes the repo copy as well
+        fs::remove_file(system.path("root/usr/bin/gone")).unwrap();
+        fs::write(system.path("root/usr/bin/tool"), "changed").unwrap();
+        fs::set_permissions(
+            system.path("root/etc/tool.conf"),
+            fs::Permissions::from_mode(0o600),
+        )
+        .unwrap();
+
+        let report = installer.verify(Some("tool")).unwrap();
+        let mut issues: Vec<(&str, FileIssueKind)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.path.as_str(), issue.kind))
+            .collect();
+        issues.sort_by(|(path, kind), (other_path, other_kind)| {
+            (path, kind.as_str()).cmp(&(other_path, other_kind.as_str()))
+        });
+
+        assert_eq!(
+            issues,
+            [
+                ("etc/tool.conf", FileIssueKind::ModeMismatch),
+                ("usr/bin/gone", FileIssueKind::Missing),
+                ("usr/bin/tool", FileIssueKind::ChecksumMismatch),
+                ("usr/bin/tool", FileIssueKind::RepoMismatch),
+                ("usr/bin/tool", FileIssueKind::SizeMismatch),
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_reports_database_errors() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        system.stage(&[("usr/bin/tool", "tool")]);
+        installer
+            .install(
+                package("tool", "1.0", &["usr/bin/tool"]),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+
+        let err = installer.verify(Some("other")).unwrap_err();
+        assert_eq!(err.to_string(), "Installer error: Package other is not installed");
+
+        // A database that cannot be read is not the same as a package that is not installed
+        fs::remove_dir_all(system.path("db/packages")).unwrap();
+        let err = installer.verify(Some("tool")).unwrap_err();
+        assert!(!err.to_string().contains("not installed"), "{err}");
+    }
+
+    #[test]
+    fn symlinks_are_installed_as_links() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        system.stage(&[("usr/lib/libfoo.so.1", "foo")]);
+        system.stage_link("usr/lib/libfoo.so", "libfoo.so.1");
+        system.stage_link("usr/lib/dangling", "../missing");
+
+        let files = ["usr/lib/libfoo.so.1", "usr/lib/libfoo.so", "usr/lib/dangling"];
+        installer
+            .install(package("foo", "1.0", &files), InstallPolicy::default())
+            .unwrap();
+
+        for (link, target) in [("libfoo.so", "libfoo.so.1"), ("dangling", "../missing")] {
+            for subdir in ["root", "repo"] {
+                let path = system.path(subdir).join("usr/lib").join(link);
+                assert_eq!(fs::read_link(path).unwrap(), Path::new(target));
+            }
+        }
+
+        let (_, _, entries) = system.record("foo").unwrap();
+        assert_eq!(entries[1].file_type, FileType::Symlink);
+        assert_eq!(
+            entries[1].link_target.as_deref(),
+            Some(Path::new("libfoo.so.1"))
+        );
+        assert!(installer.verify(None).unwrap().issues.is_empty());
+
+        // Removing the package removes the links themselves, dangling or not
+        installer.remove("foo", RemovePolicy::default()).unwrap();
+        assert!(fs::symlink_metadata(system.path("root/usr/lib/libfoo.so")).is_err());
+        assert!(fs::symlink_metadata(system.path("root/usr/lib/dangling")).is_err());
+    }
+
+    fn config_package(version: &str) -> ExtractedPackage {
+        let mut tool = package("tool", version, &["usr/bin/tool", "etc/tool.conf"]);
+        tool.config_files = stab_vec(&["/etc/tool.conf"]);
+        tool
+    }
+
+    // Function to edit a file the way editors do, replacing it instead of writing through the link
+    fn edit(path: &Path, content: &str) {
+        fs::remove_file(path).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn modified_config_files_are_kept() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+        let config_path = system.path("root/etc/tool.conf");
+
+        system.stage(&[("usr/bin/tool", "1.0"), ("etc/tool.conf", "1.0")]);
+        installer
+            .install(config_package("1.0"), InstallPolicy::default())
+            .unwrap();
+
+        // An unmodified configuration file follows the package
+        system.stage(&[("usr/bin/tool", "2.0"), ("etc/tool.conf", "2.0")]);
+        installer
+            .install(config_package("2.0"), InstallPolicy::default())
+            .unwrap();
+        assert_eq!(system.read("root/etc/tool.conf"), "2.0");
+
+        // A modified one stays and the new version is put next to it
+        edit(&config_path, "local");
+        system.stage(&[("usr/bin/tool", "3.0"), ("etc/tool.conf", "3.0")]);
+        installer
+            .install(config_package("3.0"), InstallPolicy::default())
+            .unwrap();
+        assert_eq!(system.read("root/etc/tool.conf"), "local");
+        assert_eq!(system.read("root/etc/tool.conf.upacnew"), "3.0");
+        assert_eq!(system.read("root/usr/bin/tool"), "3.0");
+
+        // Nothing new is written when the package did not change the file
+        fs::remove_file(system.path("root/etc/tool.conf.upacnew")).unwrap();
+        system.stage(&[("usr/bin/tool", "3.1"), ("etc/tool.conf", "3.0")]);
+        installer
+            .install(config_package("3.1"), InstallPolicy::default())
+            .unwrap();
+        assert_eq!(system.read("root/etc/tool.conf"), "local");
+        assert!(!system.path("root/etc/tool.conf.upacnew").exists());
+
+        // Removing the package keeps the local file as .upacsave
+        installer.remove("tool", RemovePolicy::default()).unwrap();
+        assert!(!config_path.exists());
+        assert_eq!(system.read("root/etc/tool.conf.upacsave"), "local");
+    }
+
+    #[test]
+    fn purge_removes_modified_config_files() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+        let config_path = system.path("root/etc/tool.conf");
+
+        system.stage(&[("usr/bin/tool", "1.0"), ("etc/tool.conf", "1.0")]);
+        installer
+            .install(config_package("1.0"), InstallPolicy::default())
+            .unwrap();
+
+        edit(&config_path, "local");
+        system.stage(&[("usr/bin/tool", "2.0"), ("etc/tool.conf", "2.0")]);
+        installer
+            .install(config_package("2.0"), InstallPolicy::default())
+            .unwrap();
+        fs::write(system.path("root/etc/tool.conf.upacsave"), "older").unwrap();
+
+        let purge = RemovePolicy {
+            purge: true,
+            ..RemovePolicy::default()
+        };
+        installer.remove("tool", purge).unwrap();
+
+        // Only the directories the files were in are left
+        assert!(
+            system.snapshot("root").iter().all(|(_, content)| content.is_none()),
+            "{:?}",
+            system.snapshot("root")
+        );
+    }
+
+    #[test]
+    fn failed_remove_puts_modified_config_back() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+        let config_path = system.path("root/etc/tool.conf");
+
+        system.stage(&[("usr/bin/tool", "1.0"), ("etc/tool.conf", "1.0")]);
+        installer
+            .install(config_package("1.0"), InstallPolicy::default())
+            .unwrap();
+        edit(&config_path, "local");
+
+        // Files go in reverse order: the configuration file is set aside before usr/bin/tool,
+        // whose repo copy is now a directory and cannot be moved away
+        fs::remove_file(system.path("repo/usr/bin/tool")).unwrap();
+        fs::create_dir(system.path("repo/usr/bin/tool")).unwrap();
+        fs::write(system.path("repo/usr/bin/tool/file"), "").unwrap();
+
+        let root = system.snapshot("root");
+        installer
+            .remove("tool", RemovePolicy::default())
+            .unwrap_err();
+
+        assert_eq!(system.snapshot("root"), root);
+        assert_eq!(system.read("root/etc/tool.conf"), "local");
+        assert!(system.record("tool").is_some());
+    }
+
+    #[test]
+    fn upgrade_replaces_files_in_place() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        system.stage(&[
+            ("usr/bin/tool", "1.0"),
+            ("usr/share/tool/old", "old"),
+        ]);
+        installer
+            .install(
+                package("tool", "1.0", &["usr/bin/tool", "usr/share/tool", "usr/share/tool/old"]),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+
+        system.stage(&[("usr/bin/tool", "2.0"), ("usr/bin/new", "new")]);
+        installer
+            .upgrade(
+                "tool",
+                package("tool", "2.0", &["usr/bin/tool", "usr/bin/new"]),
+                InstallPolicy::default(),
+            )
+            .unwrap();
+
+        assert_eq!(system.read("root/usr/bin/tool"), "2.0");
+        assert_eq!(system.read("root/usr/bin/new"), "new");
+        assert!(!system.path("root/usr/share/tool").exists());
+        assert!(!system.path("repo/usr/share/tool").exists());
+        assert!(system
+            .snapshot("root")
+            .iter()
+            .all(|(path, _)| !path.to_string_lossy().contains(".upac-")));
+
+        let (version, _, entries) = system.record("tool").unwrap();
+        assert_eq!(version, "2.0");
+        assert_eq!(
+            entries.iter().map(|entry| entry.path.clone()).collect::<Vec<_>>(),
+            [PathBuf::from("usr/bin/tool"), PathBuf::from("usr/bin/new")]
+        );
+        assert!(installer.verify(None).unwrap().issues.is_empty());
+
+        let err = installer
+            .upgrade(
+                "tool",
+                package("other", "1.0", &["usr/bin/tool"]),
+                InstallPolicy::default(),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("same name"), "{err}");
+    }
+
+    #[test]
+    fn dependents_are_checked_against_the_whole_set() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        let versioned = |name: &str, version: &str, dependencies: &[&str]| {
+            let file = format!("usr/lib/{name}");
+            let mut package = package(name, version, &[file.as_str()]);
+            package.dependencies = stab_vec(dependencies);
+            package
+        };
+
+        system.stage(&[("usr/lib/a", "1.0")]);
+        installer
+            .install(versioned("a", "1.0", &[]), InstallPolicy::default())
+            .unwrap();
+        system.stage(&[("usr/lib/b", "1.0")]);
+        installer
+            .install(versioned("b", "1.0", &["a=1.0"]), InstallPolicy::default())
+            .unwrap();
+
+        // Alone, a 2.0 breaks b
+        system.stage(&[("usr/lib/a", "2.0")]);
+        let err = installer
+            .install(versioned("a", "2.0", &[]), InstallPolicy::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("breaks dependencies of: b"), "{err}");
+        assert_eq!(system.read("root/usr/lib/a"), "1.0");
+
+        // Together with b 2.0 it does not
+        let stage = |name: &str| {
+            system.stage(&[(format!("usr/lib/{name}").as_str(), "2.0")]);
+        };
+        let set = [("a", &[][..]), ("b", &["a=2.0"][..])]
+            .into_iter()
+            .map(|(name, dependencies)| {
+                stage(name);
+                Ok((versioned(name, "2.0", dependencies), InstallPolicy::default()))
+            });
+        installer.install_all(set).unwrap();
+
+        assert_eq!(system.record("a").unwrap().0, "2.0");
+        assert_eq!(system.record("b").unwrap().0, "2.0");
+    }
+
+    #[test]
+    fn versioned_conflicts_apply_to_the_versions_they_name() {
+        let system = TestSystem::new();
+        let mut installer = system.installer();
+
+        let libfoo = |version| {
+            system.stage(&[("usr/lib/libfoo.so", version)]);
+            package("libfoo", version, &["usr/lib/libfoo.so"])
+        };
+        let mut app = package("app", "1.0", &["usr/bin/app"]);
+        app.conflicts = stab_vec(&["libfoo<2", "libfoo>=3"]);
+
+        installer.install(libfoo("1.0"), InstallPolicy::default()).unwrap();
+        system.stage(&[("usr/bin/app", "app")]);
+        let err = installer
+            .install(app, InstallPolicy::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("conflicts"), "{err}");
+
+        installer.install(libfoo("2.0"), InstallPolicy::default()).unwrap();
+        let mut app = package("app", "1.0", &["usr/bin/app"]);
+        app.conflicts = stab_vec(&["libfoo<2", "libfoo>=3"]);
+        system.stage(&[("usr/bin/app", "app")]);
+        installer.install(app, InstallPolicy::default()).unwrap();
+
+        // The conflict of the installed package also stops an upgrade into the range it names
+        let err = installer
+            .install(libfoo("3.0"), InstallPolicy::default())
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("libfoo conflicts with installed packages: app"),
+            "{err}"
+        );
+        assert_eq!(system.read("root/usr/lib/libfoo.so"), "2.0");
+    }
+}
@@ -0,0 +1,142 @@
+// Imports
+use super::LinkStrategy;
+
+use nix::fcntl::copy_file_range;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+// Function to place a repo file at the destination, sharing data with it when the filesystem allows
+pub(crate) fn link_or_copy(source_path: &Path, dest_path: &Path) -> io::Result<LinkStrategy> {
+    match fs::hard_link(source_path, dest_path) {
+        Ok(()) => return Ok(LinkStrategy::HardLink),
+        Err(err) if err.raw_os_error() == Some(libc::EXDEV) => {}
+        Err(err) => return Err(err),
+    }
+
+    // A failed copy must not leave a partial file behind
+    let result = copy_across(source_path, dest_path);
+    if result.is_err() {
+        let _ = fs::remove_file(dest_path);
+    }
+
+    result
+}
+
+// Function to copy a file to another filesystem: reflink, then copy_file_range, then a plain copy
+fn copy_across(source_path: &Path, dest_path: &Path) -> io::Result<LinkStrategy> {
+    let mut source = File::open(source_path)?;
+    let mut dest = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest_path)?;
+
+    if reflink(&source, &dest).is_ok() {
+        return Ok(LinkStrategy::Reflink);
+    }
+
+    match copy_range(&source, &dest) {
+        Ok(()) => return Ok(LinkStrategy::CopyFileRange),
+        Err(err) if is_unsupported(&err) => {}
+        Err(err) => return Err(err),
+    }
+
+    // Start over in case copy_file_range gave up midway
+    dest.set_len(0)?;
+    dest.seek(SeekFrom::Start(0))?;
+    source.seek(SeekFrom::Start(0))?;
+    io::copy(&mut source, &mut dest)?;
+
+    Ok(LinkStrategy::Copy)
+}
+
+// Function to share the data extents of the source with the destination (btrfs, XFS)
+fn reflink(source: &File, dest: &File) -> io::Result<()> {
+    let result = unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// Function to copy inside the kernel; offsets are explicit so the file cursors stay untouched
+fn copy_range(source: &File, dest: &File) -> io::Result<()> {
+    let length = source.metadata()?.len() as i64;
+    let mut source_offset: i64 = 0;
+    let mut dest_offset: i64 = 0;
+
+    while source_offset < length {
+        let remaining = (length - source_offset) as usize;
+        let copied = copy_file_range(
+            source,
+            Some(&mut source_offset),
+            dest,
+            Some(&mut dest_offset),
+            remaining,
+        )?;
+
+        if copied == 0 {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Errors meaning copy_file_range cannot be used between these files
+fn is_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn links_within_a_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("source");
+        let dest_path = dir.path().join("dest");
+        fs::write(&source_path, "data").unwrap();
+
+        let strategy = link_or_copy(&source_path, &dest_path).unwrap();
+
+        assert_eq!(strategy, LinkStrategy::HardLink);
+        assert_eq!(
+            fs::metadata(&source_path).unwrap().ino(),
+            fs::metadata(&dest_path).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn copies_when_a_link_is_not_possible() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("source");
+        let dest_path = dir.path().join("dest");
+        // Large enough for copy_file_range to take more than one pass on some filesystems
+        let content = "data".repeat(64 * 1024);
+        fs::write(&source_path, &content).unwrap();
+
+        let strategy = copy_across(&source_path, &dest_path).unwrap();
+
+        assert_ne!(strategy, LinkStrategy::HardLink);
+        assert_eq!(fs::read_to_string(&dest_path).unwrap(), content);
+        assert_ne!(
+            fs::metadata(&source_path).unwrap().ino(),
+            fs::metadata(&dest_path).unwrap().ino()
+        );
+
+        // The copy never replaces an existing file
+        assert!(copy_across(&source_path, &dest_path).is_err());
+        assert_eq!(fs::read_to_string(&dest_path).unwrap(), content);
+    }
+}
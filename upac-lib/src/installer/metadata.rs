@@ -0,0 +1,100 @@
+// Imports
+use super::{FileEntry, FileType, InstallerResult};
+
+use crate::checksum::sha256_file;
+
+use std::fs::{self, Metadata};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+
+// Function to describe a file as it currently is on disk; the entry path stays relative
+pub(crate) fn read_file_entry(base_path: &Path, file_path: &Path) -> InstallerResult<FileEntry> {
+    let full_path = base_path.join(file_path);
+    let metadata = fs::symlink_metadata(&full_path)?;
+    let file_type = file_type(&metadata);
+
+    let sha256 = match file_type {
+        FileType::Regular => Some(sha256_file(&full_path)?),
+        _ => None,
+    };
+
+    let link_target = match file_type {
+        FileType::Symlink => Some(fs::read_link(&full_path)?),
+        _ => None,
+    };
+
+    Ok(FileEntry {
+        path: file_path.to_path_buf(),
+        file_type,
+        size: if file_type == FileType::Regular {
+            metadata.len()
+        } else {
+            0
+        },
+        mode: metadata.mode() & 0o7777,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        sha256,
+        link_target,
+        link_strategy: None,
+        config: false,
+    })
+}
+
+// Function to classify a file without following symlinks
+pub(crate) fn file_type(metadata: &Metadata) -> FileType {
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        FileType::Symlink
+    } else if file_type.is_dir() {
+        FileType::Directory
+    } else if file_type.is_file() {
+        FileType::Regular
+    } else if file_type.is_fifo()
+        || file_type.is_socket()
+        || file_type.is_block_device()
+        || file_type.is_char_device()
+    {
+        FileType::Other
+    } else {
+        FileType::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::path::PathBuf;
+
+    #[test]
+    fn describes_files_by_type() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("bin")).unwrap();
+        fs::write(dir.path().join("bin/tool"), "hello").unwrap();
+        fs::set_permissions(
+            dir.path().join("bin/tool"),
+            fs::Permissions::from_mode(0o4755),
+        )
+        .unwrap();
+        symlink("tool", dir.path().join("bin/link")).unwrap();
+
+        let tool = read_file_entry(dir.path(), Path::new("bin/tool")).unwrap();
+        assert_eq!(tool.path, Path::new("bin/tool"));
+        assert_eq!((tool.file_type, tool.size, tool.mode), (FileType::Regular, 5, 0o4755));
+        assert_eq!(
+            tool.sha256.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+
+        let link = read_file_entry(dir.path(), Path::new("bin/link")).unwrap();
+        assert_eq!((link.file_type, link.size), (FileType::Symlink, 0));
+        assert_eq!(link.link_target, Some(PathBuf::from("tool")));
+        assert_eq!(link.sha256, None);
+
+        let bin = read_file_entry(dir.path(), Path::new("bin")).unwrap();
+        assert_eq!((bin.file_type, bin.sha256), (FileType::Directory, None));
+    }
+}
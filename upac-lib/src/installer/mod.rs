@@ -1,9 +1,22 @@
 // mod.rs
-use upac_types::ExtractedPackage;
+use upac_types::{ExtractedPackage, FileEntry, FileType, InstallReason, LinkStrategy};
 use upac_types::{InstallerError, InstallerResult, InstallerStabbyResult};
 
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+mod conflicts;
 pub mod installer;
+mod link;
+mod metadata;
+mod scriptlet;
+mod transaction;
+
 pub use installer::PackageInstaller;
+pub(crate) use conflicts::{ConflictChecker, FileConflict};
+pub(crate) use metadata::read_file_entry;
+pub(crate) use scriptlet::ScriptRunner;
+pub(crate) use transaction::Transaction;
 
 #[repr(u8)]
 #[stabby::stabby]
@@ -19,7 +32,79 @@ pub enum InstallerState {
     Failed,
 }
 
-pub(crate) trait Installer {
-    fn install(&mut self, package: ExtractedPackage) -> InstallerResult<()>;
-    fn remove(&mut self, package: &str) -> InstallerResult<()>;
+// Phase of a package operation in which a script runs
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum ScriptPhase {
+    PreInstall,
+    PostInstall,
+    PreRemove,
+    PostRemove,
+}
+
+impl ScriptPhase {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::PreInstall => "pre_install",
+            Self::PostInstall => "post_install",
+            Self::PreRemove => "pre_remove",
+            Self::PostRemove => "post_remove",
+        }
+    }
+
+    pub fn is_pre(&self) -> bool {
+        matches!(self, Self::PreInstall | Self::PreRemove)
+    }
+
+    pub fn is_remove(&self) -> bool {
+        matches!(self, Self::PreRemove | Self::PostRemove)
+    }
+}
+
+// Captured result of a finished package script
+#[stabby::stabby]
+#[derive(Clone)]
+pub struct ScriptOutput {
+    pub stdout: StabString,
+    pub stderr: StabString,
+    pub exit_code: i32,
+    pub phase: ScriptPhase,
+}
+
+// Options controlling package installation
+#[stabby::stabby]
+#[derive(Clone, Default)]
+pub struct InstallPolicy {
+    // Globs of conflicting paths the package may take over
+    pub overwrite: StabVec<StabString>,
+    pub reason: InstallReason,
+}
+
+// Options controlling package removal
+#[stabby::stabby]
+#[derive(Clone, Copy, Default)]
+pub struct RemovePolicy {
+    pub force: bool,
+    // Delete modified configuration files instead of keeping them as .upacsave
+    pub purge: bool,
+}
+
+pub trait Installer {
+    fn install(&mut self, package: ExtractedPackage, policy: InstallPolicy) -> InstallerResult<()>;
+    // Function to install packages as one transaction, each extracted into the temp directory when
+    // the iterator yields it; if one fails, none of them stays installed
+    fn install_all(
+        &mut self,
+        packages: impl IntoIterator<Item = InstallerResult<(ExtractedPackage, InstallPolicy)>>,
+    ) -> InstallerResult<()>;
+    // Function to replace an installed package with a new version of the same package in place:
+    // shared files are swapped atomically and only files the new version no longer ships are deleted
+    fn upgrade(
+        &mut self,
+        old: &str,
+        package: ExtractedPackage,
+        policy: InstallPolicy,
+    ) -> InstallerResult<()>;
+    fn remove(&mut self, package: &str, policy: RemovePolicy) -> InstallerResult<()>;
 }
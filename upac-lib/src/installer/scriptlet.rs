@@ -0,0 +1,75 @@
+// Imports
+use super::InstallerResult;
+use super::{ScriptOutput, ScriptPhase};
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const SHELL_PATH: &str = "/bin/sh";
+
+// Runner for package scripts, executed inside the configured root
+pub(crate) struct ScriptRunner {
+    root_path: PathBuf,
+    temp_path: PathBuf,
+}
+
+impl ScriptRunner {
+    pub(crate) fn new(root_path: PathBuf, temp_path: PathBuf) -> Self {
+        Self {
+            root_path,
+            temp_path,
+        }
+    }
+
+    // Function to run a script and capture its output and exit code
+    pub(crate) fn run(
+        &self,
+        phase: ScriptPhase,
+        package: &str,
+        script: &str,
+        new_version: Option<&str>,
+        old_version: Option<&str>,
+    ) -> InstallerResult<ScriptOutput> {
+        fs::create_dir_all(&self.temp_path)?;
+
+        let script_path = self
+            .temp_path
+            .join(format!(".upac-{package}-{}.sh", phase.as_str()));
+        fs::write(&script_path, script)?;
+
+        let mut command = Command::new(SHELL_PATH);
+        command
+            .arg(&script_path)
+            .current_dir(&self.root_path)
+            .env("UPAC_ROOT", &self.root_path)
+            .env("UPAC_PACKAGE", package)
+            .env("UPAC_SCRIPT_PHASE", phase.as_str());
+
+        // Like pacman, the new version goes first and the old one second; removal scripts get the
+        // version being removed first, followed by its replacement during an upgrade
+        if phase.is_remove() {
+            command.args(old_version.into_iter().chain(new_version));
+        } else {
+            command.args(new_version.into_iter().chain(old_version));
+        }
+
+        if let Some(new_version) = new_version {
+            command.env("UPAC_NEW_VERSION", new_version);
+        }
+        if let Some(old_version) = old_version {
+            command.env("UPAC_OLD_VERSION", old_version);
+        }
+
+        let output = command.output();
+        let _ = fs::remove_file(&script_path);
+        let output = output?;
+
+        Ok(ScriptOutput {
+            phase,
+            stdout: String::from_utf8_lossy(&output.stdout).as_ref().into(),
+            stderr: String::from_utf8_lossy(&output.stderr).as_ref().into(),
+            exit_code: output.status.code().unwrap_or(-1),
+        })
+    }
+}
@@ -0,0 +1,229 @@
+// Imports
+use super::link::link_or_copy;
+use super::{InstallerError, InstallerResult, LinkStrategy};
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+
+const BACKUP_SUFFIX: &str = ".upac-rollback";
+const STAGING_SUFFIX: &str = ".upac-new";
+
+// Single filesystem change made during a transaction
+enum TransactionEntry {
+    CreatedDir(PathBuf),
+    CreatedFile(PathBuf),
+    Replaced { path: PathBuf, backup: PathBuf },
+    Moved { from: PathBuf, to: PathBuf },
+}
+
+// Journal of filesystem changes that can be undone if an operation fails
+#[derive(Default)]
+pub(crate) struct Transaction {
+    entries: Vec<TransactionEntry>,
+}
+
+impl Transaction {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    // Function to create a directory and all missing parents, remembering every created one
+    pub(crate) fn create_dir_all(&mut self, path: &Path) -> InstallerResult<()> {
+        let missing_dirs: Vec<PathBuf> = path
+            .ancestors()
+            .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+            .map(Path::to_path_buf)
+            .collect();
+
+        for dir_path in missing_dirs.into_iter().rev() {
+            match fs::create_dir(&dir_path) {
+                Ok(()) => self.entries.push(TransactionEntry::CreatedDir(dir_path)),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Function to move an existing file aside so it can be restored on rollback
+    pub(crate) fn backup(&mut self, path: &Path) -> InstallerResult<()> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.is_dir() => {}
+            Ok(_) => {
+                return Err(InstallerError::Installer(
+                    format!("Cannot replace directory: {}", path.display()).into(),
+                ))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+
+        // A path replaced twice in one transaction already has its original saved,
+        // what is there now was created by the transaction itself
+        if self.is_saved(path) {
+            fs::remove_file(path)?;
+            return Ok(());
+        }
+
+        let backup_path = suffixed(path, BACKUP_SUFFIX);
+
+        fs::rename(path, &backup_path)?;
+        self.entries.push(TransactionEntry::Replaced {
+            path: path.to_path_buf(),
+            backup: backup_path,
+        });
+
+        Ok(())
+    }
+
+    // Function to put a file in place of whatever is at the destination without a moment where the
+    // path is missing: `create` makes it under a temporary name and one rename moves it over. The
+    // previous file stays linked at its backup path until commit
+    pub(crate) fn replace<T>(
+        &mut self,
+        dest_path: &Path,
+        create: impl FnOnce(&Path) -> InstallerResult<T>,
+    ) -> InstallerResult<T> {
+        let staging_path = suffixed(dest_path, STAGING_SUFFIX);
+        remove_if_exists(&staging_path)?;
+
+        let result = create(&staging_path).and_then(|value| {
+            let saved = self.keep(dest_path)?;
+            fs::rename(&staging_path, dest_path)?;
+
+            if !saved {
+                self.entries
+                    .push(TransactionEntry::CreatedFile(dest_path.to_path_buf()));
+            }
+            Ok(value)
+        });
+
+        if result.is_err() {
+            let _ = remove_if_exists(&staging_path);
+        }
+        result
+    }
+
+    // Function to save an existing file under its backup path while leaving it in place;
+    // returns whether there was one
+    fn keep(&mut self, path: &Path) -> InstallerResult<bool> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.is_dir() => {}
+            Ok(_) => {
+                return Err(InstallerError::Installer(
+                    format!("Cannot replace directory: {}", path.display()).into(),
+                ))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+
+        if self.is_saved(path) {
+            return Ok(true);
+        }
+
+        let backup_path = suffixed(path, BACKUP_SUFFIX);
+        remove_if_exists(&backup_path)?;
+
+        // Filesystems without hard links fall back to moving the file aside
+        if fs::hard_link(path, &backup_path).is_err() {
+            fs::rename(path, &backup_path)?;
+        }
+        self.entries.push(TransactionEntry::Replaced {
+            path: path.to_path_buf(),
+            backup: backup_path,
+        });
+
+        Ok(true)
+    }
+
+    // Function to hard-link a file, or copy it when the destination is on another filesystem
+    pub(crate) fn link(
+        &mut self,
+        source_path: &Path,
+        dest_path: &Path,
+    ) -> InstallerResult<LinkStrategy> {
+        let strategy = link_or_copy(source_path, dest_path)?;
+        self.entries
+            .push(TransactionEntry::CreatedFile(dest_path.to_path_buf()));
+        Ok(strategy)
+    }
+
+    // Function to create a symbolic link; an existing destination is an error
+    pub(crate) fn symlink(&mut self, link_target: &Path, dest_path: &Path) -> InstallerResult<()> {
+        unix_fs::symlink(link_target, dest_path)?;
+        self.entries
+            .push(TransactionEntry::CreatedFile(dest_path.to_path_buf()));
+        Ok(())
+    }
+
+    // Function to rename a file, keeping any previous file at the destination for rollback
+    pub(crate) fn rename(&mut self, source_path: &Path, dest_path: &Path) -> InstallerResult<()> {
+        self.backup(dest_path)?;
+        fs::rename(source_path, dest_path)?;
+        self.entries.push(TransactionEntry::Moved {
+            from: source_path.to_path_buf(),
+            to: dest_path.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn is_saved(&self, path: &Path) -> bool {
+        self.entries.iter().any(
+            |entry| matches!(entry, TransactionEntry::Replaced { path: saved, .. } if saved == path),
+        )
+    }
+
+    // Function to make all changes permanent and drop the saved backups
+    pub(crate) fn commit(self) {
+        for entry in self.entries {
+            if let TransactionEntry::Replaced { backup, .. } = entry {
+                let _ = fs::remove_file(backup);
+            }
+        }
+    }
+
+    // Function to undo all changes in reverse order
+    pub(crate) fn rollback(self) -> InstallerResult<()> {
+        let mut first_error: Option<InstallerError> = None;
+
+        for entry in self.entries.into_iter().rev() {
+            let result = match entry {
+                TransactionEntry::CreatedFile(path) => fs::remove_file(path),
+                TransactionEntry::CreatedDir(path) => fs::remove_dir(path),
+                TransactionEntry::Replaced { path, backup } => fs::rename(backup, path),
+                TransactionEntry::Moved { from, to } => fs::rename(to, from),
+            };
+
+            match result {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => {
+                    first_error.get_or_insert(err.into());
+                }
+            }
+        }
+
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn remove_if_exists(path: &Path) -> InstallerResult<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
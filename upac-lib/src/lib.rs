@@ -1,12 +1,49 @@
+// Modules are laid out as `name/name.rs` on purpose
+#![allow(clippy::module_inception)]
+
+mod backend;
 mod backup;
+mod checksum;
 mod installer;
 
 mod config;
 mod database;
 mod lock;
+mod repository;
+mod resolver;
+mod signature;
+mod verifier;
+mod version;
+
+#[cfg(test)]
+mod testing;
+
+pub use backend::archive;
+pub use backend::script::embed_script;
+pub use backend::native::{build_package, ManifestScripts, NativeBackend, PackageManifest};
+pub use backend::native::{BUILD_MANIFEST_NAME, MANIFEST_NAME, NATIVE_EXTENSION};
+pub use backend::{Backend, BackendPlugin, BackendRegistry, PluginBackend, HEADER_LEN, PLUGIN_SYMBOL};
+#[doc(hidden)]
+pub use backend::plugin::{plugin_detect, plugin_extract, plugin_read_metadata};
 
 pub use backup::backup::OSTreeManager;
+pub use backup::OSTree;
 
-pub use installer::{InstallerState, PackageInstaller};
+pub use installer::{InstallPolicy, Installer, InstallerState, PackageInstaller, RemovePolicy};
+pub use installer::{ScriptOutput, ScriptPhase};
 
 pub use config::config::{OStreeConfig, UpacConfig};
+pub use config::Config;
+
+pub use database::{open_database, Database, DatabaseBackend, JournalState, RecoveryReport};
+pub use database::{NameMatch, PackageDatabase, PackageFilter, SqliteDatabase};
+
+pub use repository::{fetch, join_url, IndexEntry, RemotePackage, Repository, RepositoryIndex};
+pub use repository::{generate_index, IndexReport, RepositorySet, Upgrade, INDEX_NAME};
+
+pub use signature::{signature_path, KeyId, Keyring, PublicKey, SecretKey, SignatureFile};
+pub use signature::{SignaturePolicy, SignatureVerifier, DEFAULT_KEYRING_DIR, SIGNATURE_EXTENSION};
+
+pub use verifier::{FileIssue, FileIssueKind, VerifyReport};
+
+pub use version::{compare_versions, Constraint, Operator, VersionScheme, VersionSchemes};
@@ -11,6 +11,7 @@ use std::path::PathBuf;
 
 pub trait Lock {
     fn lock(&self) -> LockResult<Flock<File>>;
+    #[allow(dead_code)]
     fn is_lock(&self) -> LockResult<bool>;
 }
 
@@ -41,6 +42,7 @@ impl Lock for SharedLock {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&self.file_path)?;
         let lock_guard = Flock::lock(lock_file, FlockArg::LockShared)?;
         Ok(lock_guard)
@@ -89,6 +91,7 @@ impl Lock for ExclusiveLock {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&self.file_path)?;
         let lock_guard = Flock::lock(lock_file, FlockArg::LockExclusive)?;
         Ok(lock_guard)
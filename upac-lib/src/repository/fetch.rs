@@ -0,0 +1,78 @@
+// Imports
+use super::{RepositoryError, RepositoryResult};
+
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Function to join a file name onto a repository URL
+pub fn join_url(base: &str, file_name: &str) -> String {
+    format!("{}/{file_name}", base.trim_end_matches('/'))
+}
+
+// Function to download a `file://` or `http://` URL; returns false when the source does not exist.
+// The file is written next to the destination first, so an interrupted download leaves nothing behind
+pub fn fetch(url: &str, destination: &Path) -> RepositoryResult<bool> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut partial_path = destination.as_os_str().to_owned();
+    partial_path.push(".part");
+    let partial_path = PathBuf::from(partial_path);
+
+    let result = if let Some(path) = url.strip_prefix("file://") {
+        copy_local(Path::new(path), &partial_path)
+    } else if url.starts_with('/') {
+        copy_local(Path::new(url), &partial_path)
+    } else if url.starts_with("http://") {
+        download(url, &partial_path)
+    } else {
+        Err(RepositoryError::Network(
+            format!("Unsupported URL scheme: {url}").into(),
+        ))
+    };
+
+    match result {
+        Ok(true) => {
+            fs::rename(&partial_path, destination)?;
+            Ok(true)
+        }
+        other => {
+            let _ = fs::remove_file(&partial_path);
+            other
+        }
+    }
+}
+
+fn copy_local(source: &Path, destination: &Path) -> RepositoryResult<bool> {
+    match fs::copy(source, destination) {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn download(url: &str, destination: &Path) -> RepositoryResult<bool> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(false),
+        Err(err) => return Err(RepositoryError::Network(format!("{url}: {err}").into())),
+    };
+
+    let mut file = File::create(destination)?;
+    io::copy(&mut response.into_reader(), &mut file)
+        .map_err(|err| RepositoryError::Network(format!("{url}: {err}").into()))?;
+    file.sync_all()?;
+
+    Ok(true)
+}
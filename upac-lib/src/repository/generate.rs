@@ -0,0 +1,300 @@
+// Imports
+use super::index::{IndexEntry, RepositoryIndex, INDEX_NAME};
+use super::{RepositoryError, RepositoryResult};
+
+use crate::backend::BackendRegistry;
+use crate::checksum::sha256_file;
+use crate::signature::{signature_path, SecretKey, SIGNATURE_EXTENSION};
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// What a run of `generate_index` did
+#[derive(Debug, Clone, Default)]
+pub struct IndexReport {
+    // Entries hashed and read again because the file is new or changed
+    pub updated: Vec<String>,
+    // Entries kept from the previous index
+    pub reused: usize,
+    // Entries whose file is gone
+    pub removed: Vec<String>,
+    // Files no backend recognises
+    pub skipped: Vec<String>,
+}
+
+// Function to write `index.toml` for every package file of a directory. Files whose size and
+// modification time match the previous index keep their entry, so only new files are hashed.
+// With a key the index is signed, otherwise a stale signature is removed
+pub fn generate_index(
+    repo_dir: &Path,
+    backends: &BackendRegistry,
+    key: Option<&SecretKey>,
+) -> RepositoryResult<IndexReport> {
+    let index_path = repo_dir.join(INDEX_NAME);
+
+    // An unreadable previous index only costs a full rescan
+    let mut previous: HashMap<String, IndexEntry> = RepositoryIndex::load(&index_path)
+        .map(|index| {
+            index
+                .packages
+                .into_iter()
+                .map(|entry| (entry.filename.clone(), entry))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut report = IndexReport::default();
+    let mut index = RepositoryIndex::new();
+
+    for (filename, path) in package_files(repo_dir)? {
+        let metadata = fs::metadata(&path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+
+        if let Some(entry) = previous.remove(&filename) {
+            if entry.size == metadata.len() && entry.mtime == mtime {
+                index.packages.push(entry);
+                report.reused += 1;
+                continue;
+            }
+        }
+
+        let Some(backend) = backends.find(&path)? else {
+            report.skipped.push(filename);
+            continue;
+        };
+
+        let package = backend
+            .read_metadata(&path)
+            .map_err(|err| RepositoryError::Backend(format!("{filename}: {err}").into()))?;
+        let strings =
+            |values: &StabVec<StabString>| values.iter().map(|value| value.to_string()).collect();
+
+        index.packages.push(IndexEntry {
+            name: package.name.to_string(),
+            version: package.version.to_string(),
+            filename: filename.clone(),
+            format: package.format.to_string(),
+            dependencies: strings(&package.dependencies),
+            provides: strings(&package.provides),
+            conflicts: strings(&package.conflicts),
+            size: metadata.len(),
+            sha256: sha256_file(&path)?,
+            mtime,
+        });
+        report.updated.push(filename);
+    }
+
+    report.removed = previous.into_keys().collect();
+    report.removed.sort();
+
+    write_index(&index_path, &index, key)?;
+    Ok(report)
+}
+
+fn write_index(
+    index_path: &Path,
+    index: &RepositoryIndex,
+    key: Option<&SecretKey>,
+) -> RepositoryResult<()> {
+    let partial_path = index_path.with_extension("toml.part");
+    let partial_signature = signature_path(&partial_path);
+
+    let result = publish_index(index_path, &partial_path, index, key);
+    if result.is_err() {
+        let _ = fs::remove_file(&partial_path);
+        let _ = fs::remove_file(&partial_signature);
+    }
+    result
+}
+
+// Function to sign the new index before either file is renamed into place, the signature first,
+// so the published index never goes without its signature and a failed signing changes nothing
+fn publish_index(
+    index_path: &Path,
+    partial_path: &Path,
+    index: &RepositoryIndex,
+    key: Option<&SecretKey>,
+) -> RepositoryResult<()> {
+    fs::write(partial_path, index.to_toml()?)?;
+
+    let signature = signature_path(index_path);
+    match key {
+        Some(key) => {
+            // The trusted comment names the index, not the file being written
+            let file_name = index_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let partial_signature = key.sign_file_as(partial_path, &file_name)?;
+            fs::rename(&partial_signature, &signature)?;
+        }
+        None if signature.exists() => fs::remove_file(&signature)?,
+        None => {}
+    }
+
+    fs::rename(partial_path, index_path)?;
+    Ok(())
+}
+
+// Function to list candidate package files of a directory by name, skipping the index,
+// signatures, partial downloads and hidden files
+fn package_files(repo_dir: &Path) -> RepositoryResult<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(repo_dir)? {
+        let entry = entry?;
+        let Ok(filename) = entry.file_name().into_string() else {
+            continue;
+        };
+
+        let ignored = filename == INDEX_NAME
+            || filename.starts_with('.')
+            || filename.ends_with(".part")
+            || filename.ends_with(&format!(".{SIGNATURE_EXTENSION}"));
+
+        if !ignored && entry.file_type()?.is_file() {
+            files.push((filename, entry.path()));
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::backend::native::{build_package, NativeBackend};
+    use crate::signature::{PublicKey, SignatureFile};
+
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    // Function to pack `tool-1.0.upac` with a single file of the given content
+    fn build(dir: &Path, content: &str) -> PathBuf {
+        let staging_dir = dir.join("staging");
+        fs::create_dir_all(staging_dir.join("usr/bin")).unwrap();
+        fs::write(staging_dir.join("usr/bin/tool"), content).unwrap();
+        fs::write(
+            staging_dir.join("upac.toml"),
+            "name = \"tool\"\nversion = \"1.0\"\n",
+        )
+        .unwrap();
+
+        build_package(
+            &staging_dir,
+            &staging_dir.join("upac.toml"),
+            &dir.join("repo"),
+        )
+        .unwrap()
+    }
+
+    // Unencrypted minisign key pair for signing test indexes
+    const SECRET_KEY: &str = "untrusted comment: minisign secret key\n\
+        RWQAAEIyAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAdXBhY1JlcG9AQUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVpbXF1eXyVDuS/xCVURR2rcg2nbbdyTNmWhGXjdoUBO4QZsqVWdek4MBH0pjvgOk0JT7QM51MW6wIibecJENPozB6TS2x8=\n";
+    const PUBLIC_KEY: &str = "RWR1cGFjUmVwbyVDuS/xCVURR2rcg2nbbdyTNmWhGXjdoUBO4QZsqVWd";
+
+    // Function to replace the recorded checksum, so a reused entry can be told from a rehashed one
+    fn mark_stale(index_path: &Path) -> IndexEntry {
+        let mut index = RepositoryIndex::load(index_path).unwrap();
+        index.packages[0].sha256 = String::from("stale");
+        fs::write(index_path, index.to_toml().unwrap()).unwrap();
+        index.packages.remove(0)
+    }
+
+    fn set_mtime(path: &Path, mtime: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    #[test]
+    fn reuses_entries_by_mtime_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_dir = dir.path().join("repo");
+        let index_path = repo_dir.join(INDEX_NAME);
+        let package = build(dir.path(), "one");
+
+        let mut backends = BackendRegistry::new();
+        backends.register(Box::new(NativeBackend));
+
+        let report = generate_index(&repo_dir, &backends, None).unwrap();
+        assert_eq!(
+            (report.updated, report.reused),
+            (vec![String::from("tool-1.0.upac")], 0)
+        );
+
+        // Unchanged size and mtime keep the entry without hashing the file again
+        mark_stale(&index_path);
+        let report = generate_index(&repo_dir, &backends, None).unwrap();
+        assert_eq!((report.updated.len(), report.reused), (0, 1));
+        assert_eq!(
+            RepositoryIndex::load(&index_path).unwrap().packages[0].sha256,
+            "stale"
+        );
+
+        // A newer mtime alone forces a rehash
+        set_mtime(&package, SystemTime::now() + Duration::from_secs(5));
+        let report = generate_index(&repo_dir, &backends, None).unwrap();
+        assert_eq!((report.updated.len(), report.reused), (1, 0));
+        assert_eq!(
+            RepositoryIndex::load(&index_path).unwrap().packages[0].sha256,
+            sha256_file(&package).unwrap()
+        );
+
+        // So does a different size with the recorded mtime put back
+        let stale = mark_stale(&index_path);
+        build(dir.path(), &"more".repeat(1024));
+        set_mtime(&package, UNIX_EPOCH + Duration::from_nanos(stale.mtime));
+        assert_ne!(fs::metadata(&package).unwrap().len(), stale.size);
+
+        let report = generate_index(&repo_dir, &backends, None).unwrap();
+        assert_eq!((report.updated.len(), report.reused), (1, 0));
+        assert_eq!(
+            RepositoryIndex::load(&index_path).unwrap().packages[0].sha256,
+            sha256_file(&package).unwrap()
+        );
+    }
+
+    #[test]
+    fn signs_the_index_under_its_own_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_dir = dir.path().join("repo");
+        let index_path = repo_dir.join(INDEX_NAME);
+        let signature = signature_path(&index_path);
+        build(dir.path(), "one");
+
+        let mut backends = BackendRegistry::new();
+        backends.register(Box::new(NativeBackend));
+        let key = SecretKey::parse(SECRET_KEY, None).unwrap();
+
+        generate_index(&repo_dir, &backends, Some(&key)).unwrap();
+        let signature_file = SignatureFile::load(&signature).unwrap();
+        assert!(signature_file
+            .trusted_comment
+            .contains(&format!("\tfile:{INDEX_NAME}\t")));
+        PublicKey::parse(PUBLIC_KEY)
+            .unwrap()
+            .verify_file(&index_path, &signature_file)
+            .unwrap();
+        assert!(!index_path.with_extension("toml.part").exists());
+
+        // Without a key the signature of the previous index goes away
+        generate_index(&repo_dir, &backends, None).unwrap();
+        assert!(index_path.exists());
+        assert!(!signature.exists());
+    }
+}
@@ -0,0 +1,189 @@
+// Imports
+use super::{RepositoryError, RepositoryResult};
+
+use crate::version::{compare_versions, Constraint, VersionSchemes};
+
+use serde::{Deserialize, Serialize};
+
+use toml::{from_str, to_string_pretty};
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+// Index file at the root of every repository
+pub const INDEX_NAME: &str = "index.toml";
+
+// Version of the index layout written by this upac
+const FORMAT_VERSION: u32 = 1;
+
+// One package file of a repository
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct IndexEntry {
+    pub name: String,
+    pub version: String,
+    // Package file name, relative to the repository URL
+    pub filename: String,
+    // Name of the backend that reads the file
+    pub format: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub provides: Vec<String>,
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    // Size of the package file in bytes
+    pub size: u64,
+    // Lowercase hex SHA-256 of the package file
+    pub sha256: String,
+    // Modification time of the file in nanoseconds, lets `repo-index` skip unchanged files
+    #[serde(default)]
+    pub mtime: u64,
+}
+
+impl IndexEntry {
+    // Function to check whether the entry, or one of its provides, satisfies a constraint
+    pub fn satisfies(&self, constraint: &Constraint, version_schemes: &VersionSchemes) -> bool {
+        constraint.satisfied_by(
+            &self.name,
+            &self.version,
+            self.provides.iter().map(String::as_str),
+            version_schemes.get(&self.format),
+        )
+    }
+}
+
+// List of packages a repository serves
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RepositoryIndex {
+    #[serde(default)]
+    pub format_version: u32,
+    #[serde(default, rename = "package")]
+    pub packages: Vec<IndexEntry>,
+}
+
+impl RepositoryIndex {
+    pub fn new() -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            packages: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> RepositoryResult<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> RepositoryResult<Self> {
+        let index: Self =
+            from_str(content).map_err(|err| RepositoryError::Index(err.to_string().into()))?;
+
+        if index.format_version > FORMAT_VERSION {
+            return Err(RepositoryError::Index(
+                format!(
+                    "Index format version {} is newer than this upac",
+                    index.format_version
+                )
+                .into(),
+            ));
+        }
+
+        // File names are joined to the cache directory, so they must not leave it
+        for entry in &index.packages {
+            let plain_name = !entry.filename.is_empty()
+                && !entry.filename.contains('/')
+                && entry.filename != "."
+                && entry.filename != "..";
+
+            if !plain_name || entry.name.is_empty() || entry.version.is_empty() {
+                return Err(RepositoryError::Index(
+                    format!("Invalid entry for {}", entry.filename).into(),
+                ));
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub fn to_toml(&self) -> RepositoryResult<String> {
+        to_string_pretty(self).map_err(|err| RepositoryError::Index(err.to_string().into()))
+    }
+
+    // Function to find the newest package matching a relation like `foo` or `foo>=1.2`, falling back
+    // to a package providing it
+    pub fn find(&self, relation: &str, version_schemes: &VersionSchemes) -> Option<&IndexEntry> {
+        let constraint = Constraint::parse(relation);
+
+        self.find_named(&constraint, version_schemes).or_else(|| {
+            self.packages
+                .iter()
+                .find(|entry| entry.satisfies(&constraint, version_schemes))
+        })
+    }
+
+    // Function to find the newest package named exactly as the constraint requires, ignoring providers
+    pub(crate) fn find_named(
+        &self,
+        constraint: &Constraint,
+        version_schemes: &VersionSchemes,
+    ) -> Option<&IndexEntry> {
+        self.packages
+            .iter()
+            .filter(|entry| {
+                entry.name == constraint.name && entry.satisfies(constraint, version_schemes)
+            })
+            .reduce(|newest, entry| {
+                let scheme = version_schemes.get(&entry.format);
+                match compare_versions(scheme, &entry.version, &newest.version) {
+                    Ordering::Greater => entry,
+                    _ => newest,
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::version::VersionScheme;
+
+    fn entry(name: &str, version: &str, provides: &[&str]) -> IndexEntry {
+        IndexEntry {
+            name: name.to_string(),
+            version: version.to_string(),
+            filename: format!("{name}-{version}.slack"),
+            format: String::from("slack"),
+            provides: provides.iter().map(|provide| provide.to_string()).collect(),
+            ..IndexEntry::default()
+        }
+    }
+
+    #[test]
+    fn finds_the_newest_by_the_scheme_of_the_format() {
+        let mut index = RepositoryIndex::new();
+        index.packages = vec![
+            entry("tool", "1.1", &[]),
+            entry("tool", "1.1a", &[]),
+            entry("tool-ng", "2.0", &["tool-api=3"]),
+        ];
+
+        // pacman sorts a letter suffix before the release, rpm after it
+        let alpm = VersionSchemes::new();
+        let mut rpm = VersionSchemes::new();
+        rpm.insert("slack", VersionScheme::Rpm);
+
+        let version = |relation, schemes| {
+            index
+                .find(relation, schemes)
+                .map(|entry: &IndexEntry| entry.version.as_str())
+        };
+        assert_eq!(version("tool", &alpm), Some("1.1"));
+        assert_eq!(version("tool", &rpm), Some("1.1a"));
+        assert_eq!(version("tool>1.1", &rpm), Some("1.1a"));
+        assert_eq!(version("tool>1.1", &alpm), None);
+
+        // A relation no package is named after goes to a provider
+        assert_eq!(version("tool-api>=3", &alpm), Some("2.0"));
+    }
+}
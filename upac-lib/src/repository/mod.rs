@@ -0,0 +1,13 @@
+// Imports
+use upac_types::{RepositoryError, RepositoryResult};
+
+// Mods
+pub mod fetch;
+pub mod generate;
+pub mod index;
+pub mod repository;
+
+pub use fetch::{fetch, join_url};
+pub use generate::{generate_index, IndexReport};
+pub use index::{IndexEntry, RepositoryIndex, INDEX_NAME};
+pub use repository::{RemotePackage, Repository, RepositorySet, Upgrade};
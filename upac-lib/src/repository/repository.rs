@@ -0,0 +1,368 @@
+// Imports
+use super::fetch::{fetch, join_url};
+use super::index::{IndexEntry, RepositoryIndex, INDEX_NAME};
+use super::{RepositoryError, RepositoryResult};
+
+use crate::checksum::{sha256_file, sha256_hex};
+use crate::config::config::UpacConfig;
+use crate::database::Database;
+use crate::resolver::DependencyResolver;
+use crate::signature::{signature_path, SignatureVerifier};
+use crate::version::{compare_versions, Constraint, VersionSchemes};
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const INDEXES_DIR: &str = "indexes";
+const PACKAGES_DIR: &str = "packages";
+const INDEX_DIR_PREFIX_LEN: usize = 48;
+const INDEX_DIR_HASH_LEN: usize = 16;
+
+// Configured repository with the index cached by the last update
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pub url: String,
+    pub index: RepositoryIndex,
+}
+
+// Package of a repository index, with the repository serving it
+#[derive(Debug, Clone)]
+pub struct RemotePackage {
+    pub repository: String,
+    pub entry: IndexEntry,
+}
+
+// Package of an upgrade set: a newer version of an installed package, or a package it newly needs
+#[derive(Debug, Clone)]
+pub struct Upgrade {
+    // Installed version being replaced, none for a new dependency
+    pub installed: Option<String>,
+    pub package: RemotePackage,
+}
+
+// Configured repositories in priority order, with their local cache
+pub struct RepositorySet {
+    repositories: Vec<Repository>,
+    cache_dir: PathBuf,
+    // Version ordering of each format, guessed from the format name until the backends set it
+    version_schemes: VersionSchemes,
+}
+
+impl RepositorySet {
+    // Function to open the repositories with whatever indexes are cached; none is an empty index
+    pub fn new(urls: &[String], cache_dir: &Path) -> RepositoryResult<Self> {
+        let mut repositories = Vec::with_capacity(urls.len());
+
+        for url in urls {
+            let index_path = index_dir(cache_dir, url).join(INDEX_NAME);
+            let index = if index_path.exists() {
+                RepositoryIndex::load(&index_path)?
+            } else {
+                RepositoryIndex::new()
+            };
+
+            repositories.push(Repository {
+                url: url.clone(),
+                index,
+            });
+        }
+
+        Ok(Self {
+            repositories,
+            cache_dir: cache_dir.to_path_buf(),
+            version_schemes: VersionSchemes::new(),
+        })
+    }
+
+    pub fn from_config(config: &UpacConfig) -> RepositoryResult<Self> {
+        let urls: Vec<String> = config
+            .repositories
+            .iter()
+            .map(|url| url.to_string())
+            .collect();
+
+        Self::new(&urls, Path::new(config.cache_dir.as_str()))
+    }
+
+    pub fn set_version_schemes(&mut self, version_schemes: VersionSchemes) {
+        self.version_schemes = version_schemes;
+    }
+
+    pub fn repositories(&self) -> &[Repository] {
+        &self.repositories
+    }
+
+    // Function to download every index into the cache. An index replaces the cached one only
+    // after its signature passed the verifier, so a failed update keeps the previous state
+    pub fn update(&mut self, verifier: &SignatureVerifier) -> RepositoryResult<()> {
+        let indexes_dir = self.cache_dir.join(INDEXES_DIR);
+        fs::create_dir_all(&indexes_dir)?;
+
+        for repository in &mut self.repositories {
+            let target_dir = index_dir(&self.cache_dir, &repository.url);
+            let staging_dir = target_dir.with_extension("new");
+            if staging_dir.exists() {
+                fs::remove_dir_all(&staging_dir)?;
+            }
+
+            let index_url = join_url(&repository.url, INDEX_NAME);
+            let index_path = staging_dir.join(INDEX_NAME);
+
+            if !fetch(&index_url, &index_path)? {
+                return Err(RepositoryError::Network(
+                    format!("{index_url} does not exist").into(),
+                ));
+            }
+            fetch(
+                &join_url(&repository.url, &format!("{INDEX_NAME}.minisig")),
+                &signature_path(&index_path),
+            )?;
+
+            let index = verifier
+                .verify(&index_path)
+                .map_err(RepositoryError::from)
+                .and_then(|_| RepositoryIndex::load(&index_path));
+
+            let index = match index {
+                Ok(index) => index,
+                Err(err) => {
+                    let _ = fs::remove_dir_all(&staging_dir);
+                    return Err(err);
+                }
+            };
+
+            if target_dir.exists() {
+                fs::remove_dir_all(&target_dir)?;
+            }
+            fs::rename(&staging_dir, &target_dir)?;
+
+            repository.index = index;
+        }
+
+        // Indexes of repositories no longer configured are dropped
+        let configured: HashSet<PathBuf> = self
+            .repositories
+            .iter()
+            .map(|repository| index_dir(&self.cache_dir, &repository.url))
+            .collect();
+
+        for entry in fs::read_dir(&indexes_dir)? {
+            let path = entry?.path();
+            if !configured.contains(&path) {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Function to find a package by name or relation like `foo>=1.2`. The first repository shipping
+    // a package of that name wins over earlier ones that only provide it
+    pub fn find(&self, relation: &str) -> Option<RemotePackage> {
+        let constraint = Constraint::parse(relation);
+        let remote = |repository: &Repository, entry: &IndexEntry| RemotePackage {
+            repository: repository.url.clone(),
+            entry: entry.clone(),
+        };
+
+        self.repositories
+            .iter()
+            .find_map(|repository| {
+                let entry = repository.index.find_named(&constraint, &self.version_schemes)?;
+                Some(remote(repository, entry))
+            })
+            .or_else(|| {
+                self.repositories.iter().find_map(|repository| {
+                    let entry = repository.index.find(relation, &self.version_schemes)?;
+                    Some(remote(repository, entry))
+                })
+            })
+    }
+
+    // Function to list the packages to install for the given names or relations, dependencies first.
+    // Dependencies an installed package already satisfies are skipped
+    pub fn resolve(
+        &self,
+        names: &[&str],
+        database: &dyn Database,
+    ) -> RepositoryResult<Vec<RemotePackage>> {
+        let resolver = DependencyResolver::new(database, &self.version_schemes);
+        let mut visited = HashSet::new();
+        let mut packages = Vec::new();
+
+        for name in names {
+            let package = self
+                .find(name)
+                .ok_or_else(|| RepositoryError::NotFound((*name).into()))?;
+            self.visit(package, &[], &resolver, &mut visited, &mut packages)?;
+        }
+
+        Ok(packages)
+    }
+
+    // Function to list the installed packages the repositories have a newer version of, dependencies
+    // first, together with the packages those versions newly depend on
+    pub fn upgrades(&self, database: &dyn Database) -> RepositoryResult<Vec<Upgrade>> {
+        let installed = database.list_packages()?;
+
+        // A package found only as a provider of the name is not a version of it
+        let candidates: Vec<RemotePackage> = installed
+            .iter()
+            .filter_map(|package| {
+                self.find(&package.name)
+                    .filter(|remote| remote.entry.name == package.name)
+                    .filter(|remote| {
+                        compare_versions(
+                            self.version_schemes.get(&remote.entry.format),
+                            &remote.entry.version,
+                            &package.version,
+                        ) == Ordering::Greater
+                    })
+            })
+            .collect();
+
+        let resolver = DependencyResolver::new(database, &self.version_schemes);
+        let mut visited = HashSet::new();
+        let mut packages = Vec::new();
+
+        for candidate in &candidates {
+            self.visit(
+                candidate.clone(),
+                &candidates,
+                &resolver,
+                &mut visited,
+                &mut packages,
+            )?;
+        }
+
+        Ok(packages
+            .into_iter()
+            .map(|package| Upgrade {
+                installed: installed
+                    .iter()
+                    .find(|installed| installed.name == package.entry.name)
+                    .map(|installed| installed.version.clone()),
+                package,
+            })
+            .collect())
+    }
+
+    fn visit(
+        &self,
+        package: RemotePackage,
+        upgrades: &[RemotePackage],
+        resolver: &DependencyResolver,
+        visited: &mut HashSet<String>,
+        packages: &mut Vec<RemotePackage>,
+    ) -> RepositoryResult<()> {
+        // Marked before the dependencies, so a dependency cycle ends here
+        if !visited.insert(package.entry.name.clone()) {
+            return Ok(());
+        }
+
+        for dependency in &package.entry.dependencies {
+            let constraint = Constraint::parse(dependency);
+            if constraint.name.is_empty() || package.entry.satisfies(&constraint, &self.version_schemes) {
+                continue;
+            }
+
+            // A pending upgrade that satisfies the dependency goes in first
+            if let Some(upgrade) = upgrades
+                .iter()
+                .find(|upgrade| {
+                    upgrade
+                        .entry
+                        .satisfies(&constraint, &self.version_schemes)
+                })
+            {
+                self.visit(upgrade.clone(), upgrades, resolver, visited, packages)?;
+                continue;
+            }
+
+            let satisfied = packages
+                .iter()
+                .any(|queued| queued.entry.satisfies(&constraint, &self.version_schemes))
+                || resolver.is_satisfied(&constraint)?;
+
+            if !satisfied {
+                let dependency = self.find(dependency).ok_or_else(|| {
+                    RepositoryError::NotFound(
+                        format!("{dependency} (needed by {})", package.entry.name).into(),
+                    )
+                })?;
+                self.visit(dependency, upgrades, resolver, visited, packages)?;
+            }
+        }
+
+        packages.push(package);
+        Ok(())
+    }
+
+    // Function to download a package and its signature, if the repository has one, into the cache.
+    // A cached file that still matches the index is reused
+    pub fn download(&self, package: &RemotePackage) -> RepositoryResult<PathBuf> {
+        let entry = &package.entry;
+        let path = self.cache_dir.join(PACKAGES_DIR).join(&entry.filename);
+
+        if !matches_entry(&path, entry)? {
+            let url = join_url(&package.repository, &entry.filename);
+            if !fetch(&url, &path)? {
+                return Err(RepositoryError::Network(
+                    format!("{url} does not exist").into(),
+                ));
+            }
+
+            if !matches_entry(&path, entry)? {
+                fs::remove_file(&path)?;
+                return Err(RepositoryError::Checksum(entry.filename.as_str().into()));
+            }
+        }
+
+        // The signature is checked against the policy when the package is extracted
+        let signature = signature_path(&path);
+        if signature.exists() {
+            fs::remove_file(&signature)?;
+        }
+        fetch(
+            &join_url(&package.repository, &format!("{}.minisig", entry.filename)),
+            &signature,
+        )?;
+
+        Ok(path)
+    }
+}
+
+// Function to check a file against the size and checksum of its index entry
+fn matches_entry(path: &Path, entry: &IndexEntry) -> RepositoryResult<bool> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() == entry.size => Ok(sha256_file(path)? == entry.sha256),
+        Ok(_) => Ok(false),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Function to get the cache directory of a repository index. The URL is shortened into a readable
+// prefix, its hash keeps URLs that shorten alike apart
+fn index_dir(cache_dir: &Path, url: &str) -> PathBuf {
+    let url = url.trim_end_matches('/');
+    let prefix: String = url
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() || char == '-' {
+                char
+            } else {
+                '_'
+            }
+        })
+        .take(INDEX_DIR_PREFIX_LEN)
+        .collect();
+    let hash = sha256_hex(url.as_bytes());
+
+    cache_dir
+        .join(INDEXES_DIR)
+        .join(format!("{prefix}-{}", &hash[..INDEX_DIR_HASH_LEN]))
+}
@@ -0,0 +1,17 @@
+// Imports
+use upac_types::{ExtractedPackage, Package};
+use upac_types::DatabaseResult;
+
+use crate::database::Database;
+
+// Mods
+pub mod resolver;
+
+pub use resolver::DependencyResolver;
+
+// Trait for checking package dependencies against the database
+pub(crate) trait Resolver {
+    fn missing_dependencies(&self, package: &ExtractedPackage) -> DatabaseResult<Vec<String>>;
+    fn dependents(&self, package_id: &str) -> DatabaseResult<Vec<String>>;
+    fn conflicting_packages(&self, package: &ExtractedPackage) -> DatabaseResult<Vec<String>>;
+}
@@ -0,0 +1,144 @@
+// Imports
+use super::{Database, DatabaseResult};
+use super::{ExtractedPackage, Package, Resolver};
+
+use crate::version::{Constraint, VersionSchemes};
+
+// Struct definition for dependency resolver
+pub struct DependencyResolver<'a> {
+    database: &'a dyn Database,
+    version_schemes: &'a VersionSchemes,
+}
+
+impl<'a> DependencyResolver<'a> {
+    pub fn new(database: &'a dyn Database, version_schemes: &'a VersionSchemes) -> Self {
+        Self {
+            database,
+            version_schemes,
+        }
+    }
+
+    // Function to check whether an installed package satisfies a constraint like `foo>=1.2`
+    pub(crate) fn is_satisfied(&self, constraint: &Constraint) -> DatabaseResult<bool> {
+        Ok(self
+            .database
+            .list_packages()?
+            .iter()
+            .any(|package| self.satisfies(package, constraint)))
+    }
+
+    fn satisfies(&self, package: &Package, constraint: &Constraint) -> bool {
+        constraint.satisfied_by(
+            &package.name,
+            &package.version,
+            package.provides.iter().map(String::as_str),
+            self.version_schemes.get(&package.format),
+        )
+    }
+
+    fn extracted_satisfies(&self, package: &ExtractedPackage, constraint: &Constraint) -> bool {
+        constraint.satisfied_by(
+            package.name.as_str(),
+            package.version.as_str(),
+            package.provides.iter().map(|provide| provide.as_str()),
+            self.version_schemes.get(package.format.as_str()),
+        )
+    }
+
+    // Function to list installed packages with a dependency the replaced version of a package
+    // satisfied and no installed package satisfies anymore, like `foo<2` once foo 2.0 replaced
+    // foo 1.0. Run after the new versions are recorded, so packages replaced together are checked
+    // against their new versions
+    pub(crate) fn broken_dependents(&self, replaced: &Package) -> DatabaseResult<Vec<String>> {
+        let packages = self.database.list_packages()?;
+
+        let broken = packages
+            .iter()
+            .filter(|installed| installed.name != replaced.name)
+            .filter(|installed| {
+                installed.dependencies.iter().any(|dependency| {
+                    let constraint = Constraint::parse(dependency);
+                    self.satisfies(replaced, &constraint)
+                        && !packages
+                            .iter()
+                            .any(|other| self.satisfies(other, &constraint))
+                })
+            })
+            .map(|installed| installed.name.clone())
+            .collect();
+
+        Ok(broken)
+    }
+}
+
+impl Resolver for DependencyResolver<'_> {
+    fn missing_dependencies(&self, package: &ExtractedPackage) -> DatabaseResult<Vec<String>> {
+        let installed = self.database.list_packages()?;
+        let mut missing = Vec::new();
+
+        for dependency in package.dependencies.iter() {
+            let constraint = Constraint::parse(dependency.as_str());
+
+            if constraint.name.is_empty() || self.extracted_satisfies(package, &constraint) {
+                continue;
+            }
+
+            // The installed version of the package itself is about to be replaced
+            let satisfied = installed.iter().any(|installed| {
+                installed.name != package.name.as_str() && self.satisfies(installed, &constraint)
+            });
+
+            if !satisfied {
+                missing.push(dependency.to_string());
+            }
+        }
+
+        Ok(missing)
+    }
+
+    fn dependents(&self, package_id: &str) -> DatabaseResult<Vec<String>> {
+        let packages = self.database.list_packages()?;
+        let Some(removed) = packages.iter().find(|package| package.name == package_id) else {
+            return Ok(Vec::new());
+        };
+
+        // A dependency the removed package satisfies, by name or by what it provides, only breaks
+        // when no other installed package satisfies it too
+        let dependents = packages
+            .iter()
+            .filter(|package| package.name != package_id)
+            .filter(|package| {
+                package.dependencies.iter().any(|dependency| {
+                    let constraint = Constraint::parse(dependency);
+                    self.satisfies(removed, &constraint)
+                        && !packages.iter().any(|other| {
+                            other.name != package_id && self.satisfies(other, &constraint)
+                        })
+                })
+            })
+            .map(|package| package.name.clone())
+            .collect();
+
+        Ok(dependents)
+    }
+
+    // A versioned conflict like `foo<2` only applies to the versions it names
+    fn conflicting_packages(&self, package: &ExtractedPackage) -> DatabaseResult<Vec<String>> {
+        let conflicting = self
+            .database
+            .list_packages()?
+            .into_iter()
+            .filter(|installed| installed.name != package.name.as_str())
+            .filter(|installed| {
+                package.conflicts.iter().any(|conflict| {
+                    self.satisfies(installed, &Constraint::parse(conflict.as_str()))
+                }) || installed.conflicts.iter().any(|conflict| {
+                    self.extracted_satisfies(package, &Constraint::parse(conflict))
+                })
+            })
+            .map(|installed| installed.name)
+            .collect();
+
+        Ok(conflicting)
+    }
+}
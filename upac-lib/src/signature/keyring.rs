@@ -0,0 +1,95 @@
+// Imports
+use super::minisign::{signature_path, PublicKey, SignatureFile};
+use super::{SignatureError, SignaturePolicy, SignatureResult};
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+// Trusted public keys, one minisign `.pub` file each
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: Vec<PublicKey>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Function to load every `*.pub` file of a directory; a missing directory is an empty keyring
+    pub fn load(keyring_dir: &Path) -> SignatureResult<Self> {
+        let entries = match fs::read_dir(keyring_dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut paths = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "pub"));
+        paths.sort();
+
+        let mut keyring = Self::new();
+        for path in paths {
+            keyring.add(PublicKey::load(&path)?);
+        }
+
+        Ok(keyring)
+    }
+
+    pub fn add(&mut self, key: PublicKey) {
+        self.keys.push(key);
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Function to verify a file against its detached signature with the key that made it
+    pub fn verify_file(&self, path: &Path, signature: &SignatureFile) -> SignatureResult<()> {
+        self.keys
+            .iter()
+            .find(|key| key.key_id == signature.key_id)
+            .ok_or_else(|| SignatureError::UntrustedKey(signature.key_id.to_string().into()))?
+            .verify_file(path, signature)
+    }
+}
+
+// Signature check run on every package before it is extracted
+#[derive(Debug, Clone, Default)]
+pub struct SignatureVerifier {
+    pub policy: SignaturePolicy,
+    pub keyring: Keyring,
+}
+
+impl SignatureVerifier {
+    pub fn new(policy: SignaturePolicy, keyring: Keyring) -> Self {
+        Self { policy, keyring }
+    }
+
+    // Function to apply the policy to a package and the `.minisig` file next to it
+    pub fn verify(&self, package: &Path) -> SignatureResult<()> {
+        if self.policy == SignaturePolicy::Never {
+            return Ok(());
+        }
+
+        let signature_path = signature_path(package);
+        if !signature_path.exists() {
+            return match self.policy {
+                SignaturePolicy::Required => Err(SignatureError::Missing(
+                    package.display().to_string().into(),
+                )),
+                _ => Ok(()),
+            };
+        }
+
+        self.keyring
+            .verify_file(package, &SignatureFile::load(&signature_path)?)
+    }
+}
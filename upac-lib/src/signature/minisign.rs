@@ -0,0 +1,476 @@
+// Imports
+use super::{SignatureError, SignatureResult};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Digest};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+
+const UNTRUSTED_PREFIX: &str = "untrusted comment:";
+const TRUSTED_PREFIX: &str = "trusted comment: ";
+
+// Signature algorithms: the file itself, or its BLAKE2b-512 hash
+const ALG_PURE: &[u8; 2] = b"Ed";
+const ALG_HASHED: &[u8; 2] = b"ED";
+const KDF_SCRYPT: &[u8; 2] = b"Sc";
+const KDF_NONE: &[u8; 2] = &[0, 0];
+const CHECKSUM_BLAKE2B: &[u8; 2] = b"B2";
+
+const KEY_ID_LEN: usize = 8;
+const PUBLIC_KEY_LEN: usize = 2 + KEY_ID_LEN + 32;
+const SIGNATURE_LEN: usize = 2 + KEY_ID_LEN + 64;
+// Algorithms, salt and limits, then the encrypted key id, keypair and checksum
+const SECRET_HEADER_LEN: usize = 6 + 32 + 16;
+const SECRET_KEY_LEN: usize = SECRET_HEADER_LEN + KEY_ID_LEN + 64 + 32;
+
+// Key id as minisign prints it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId([u8; KEY_ID_LEN]);
+
+impl Display for KeyId {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{:016X}", u64::from_le_bytes(self.0))
+    }
+}
+
+// Public key in the minisign format
+#[derive(Debug, Clone)]
+pub struct PublicKey {
+    pub key_id: KeyId,
+    key: VerifyingKey,
+}
+
+impl PublicKey {
+    pub fn load(path: &Path) -> SignatureResult<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|err| SignatureError::Invalid(format!("{}: {err}", path.display()).into()))
+    }
+
+    // Function to parse a key file, or the bare base64 line `minisign -P` takes
+    pub fn parse(content: &str) -> SignatureResult<Self> {
+        let line = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with(UNTRUSTED_PREFIX))
+            .ok_or_else(|| invalid("empty public key"))?;
+
+        let data = decode(line, PUBLIC_KEY_LEN, "public key")?;
+        if &data[..2] != ALG_PURE {
+            return Err(invalid("unsupported public key algorithm"));
+        }
+
+        let key = VerifyingKey::from_bytes(&data[10..].try_into().expect("length checked"))
+            .map_err(|_| invalid("public key is not a valid ed25519 point"))?;
+
+        Ok(Self {
+            key_id: key_id(&data[2..10]),
+            key,
+        })
+    }
+
+    // Function to check a detached signature of a file
+    pub fn verify_file(&self, path: &Path, signature: &SignatureFile) -> SignatureResult<()> {
+        if signature.key_id != self.key_id {
+            return Err(SignatureError::UntrustedKey(
+                signature.key_id.to_string().into(),
+            ));
+        }
+
+        let mismatch = || SignatureError::Mismatch(path.display().to_string().into());
+
+        let verified = if signature.hashed {
+            self.key.verify(&hash_file(path)?, &signature.signature)
+        } else {
+            self.key.verify(&fs::read(path)?, &signature.signature)
+        };
+        verified.map_err(|_| mismatch())?;
+
+        // The trusted comment is covered by its own signature
+        let mut global = signature.signature.to_bytes().to_vec();
+        global.extend_from_slice(signature.trusted_comment.as_bytes());
+        self.key
+            .verify(&global, &signature.global_signature)
+            .map_err(|_| mismatch())
+    }
+}
+
+// Secret key in the minisign format, decrypted
+pub struct SecretKey {
+    pub key_id: KeyId,
+    key: SigningKey,
+}
+
+impl SecretKey {
+    // Function to read a key file; encrypted keys need the password they were created with
+    pub fn load(path: &Path, password: Option<&str>) -> SignatureResult<Self> {
+        Self::parse(&fs::read_to_string(path)?, password)
+            .map_err(|err| SignatureError::Invalid(format!("{}: {err}", path.display()).into()))
+    }
+
+    pub fn parse(content: &str, password: Option<&str>) -> SignatureResult<Self> {
+        let line = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with(UNTRUSTED_PREFIX))
+            .ok_or_else(|| invalid("empty secret key"))?;
+
+        let mut data = decode(line, SECRET_KEY_LEN, "secret key")?;
+        if &data[..2] != ALG_PURE || &data[4..6] != CHECKSUM_BLAKE2B {
+            return Err(invalid("unsupported secret key algorithm"));
+        }
+
+        let (header, keys) = data.split_at_mut(SECRET_HEADER_LEN);
+        match &header[2..4] {
+            kdf if kdf == KDF_NONE => {}
+            kdf if kdf == KDF_SCRYPT => {
+                let password = password.ok_or_else(|| invalid("secret key is encrypted"))?;
+                let ops_limit = u64::from_le_bytes(header[38..46].try_into().expect("fixed slice"));
+                let mem_limit = u64::from_le_bytes(header[46..54].try_into().expect("fixed slice"));
+
+                let mut stream = vec![0u8; keys.len()];
+                scrypt::scrypt(
+                    password.as_bytes(),
+                    &header[6..38],
+                    &scrypt_params(ops_limit, mem_limit)?,
+                    &mut stream,
+                )
+                .map_err(|_| invalid("invalid key derivation parameters"))?;
+
+                for (byte, mask) in keys.iter_mut().zip(stream) {
+                    *byte ^= mask;
+                }
+            }
+            _ => return Err(invalid("unsupported key derivation")),
+        }
+
+        let (id, rest) = keys.split_at(KEY_ID_LEN);
+        let (keypair, checksum) = rest.split_at(64);
+
+        let mut hasher = Blake2b::<U32>::new();
+        hasher.update(ALG_PURE);
+        hasher.update(id);
+        hasher.update(keypair);
+        if hasher.finalize().as_slice() != checksum {
+            return Err(invalid("secret key checksum mismatch, wrong password?"));
+        }
+
+        let key = SigningKey::from_keypair_bytes(&keypair.try_into().expect("length checked"))
+            .map_err(|_| invalid("secret key does not match its public half"))?;
+
+        Ok(Self {
+            key_id: key_id(id),
+            key,
+        })
+    }
+
+    // Function to sign a file, writing `<file>.minisig` next to it; returns the signature path
+    pub fn sign_file(&self, path: &Path) -> SignatureResult<PathBuf> {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.sign_file_as(path, &file_name)
+    }
+
+    // Function to sign a file under the name it will be published as; the trusted comment
+    // carries `file_name` while the signature is still written next to `path`
+    pub fn sign_file_as(&self, path: &Path, file_name: &str) -> SignatureResult<PathBuf> {
+        let signature = self.key.sign(&hash_file(path)?);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let trusted_comment = format!("timestamp:{timestamp}\tfile:{file_name}\thashed");
+
+        let mut global = signature.to_bytes().to_vec();
+        global.extend_from_slice(trusted_comment.as_bytes());
+        let global_signature = self.key.sign(&global);
+
+        let mut data = Vec::with_capacity(SIGNATURE_LEN);
+        data.extend_from_slice(ALG_HASHED);
+        data.extend_from_slice(&self.key_id.0);
+        data.extend_from_slice(&signature.to_bytes());
+
+        let content = format!(
+            "{UNTRUSTED_PREFIX} signature from upac secret key {}\n{}\n{TRUSTED_PREFIX}{trusted_comment}\n{}\n",
+            self.key_id,
+            STANDARD.encode(data),
+            STANDARD.encode(global_signature.to_bytes()),
+        );
+
+        let signature_path = signature_path(path);
+        fs::write(&signature_path, content)?;
+        Ok(signature_path)
+    }
+}
+
+// Parsed `.minisig` file
+#[derive(Debug, Clone)]
+pub struct SignatureFile {
+    pub key_id: KeyId,
+    pub trusted_comment: String,
+    hashed: bool,
+    signature: Signature,
+    global_signature: Signature,
+}
+
+impl SignatureFile {
+    pub fn load(path: &Path) -> SignatureResult<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|err| SignatureError::Invalid(format!("{}: {err}", path.display()).into()))
+    }
+
+    pub fn parse(content: &str) -> SignatureResult<Self> {
+        let mut lines = content.lines().map(|line| line.trim_end_matches('\r'));
+
+        let (Some(untrusted), Some(signature), Some(trusted), Some(global)) =
+            (lines.next(), lines.next(), lines.next(), lines.next())
+        else {
+            return Err(invalid("truncated signature file"));
+        };
+
+        if !untrusted.starts_with(UNTRUSTED_PREFIX) {
+            return Err(invalid("missing untrusted comment"));
+        }
+        let trusted_comment = trusted
+            .strip_prefix(TRUSTED_PREFIX)
+            .ok_or_else(|| invalid("missing trusted comment"))?;
+
+        let data = decode(signature, SIGNATURE_LEN, "signature")?;
+        let hashed = match &data[..2] {
+            algorithm if algorithm == ALG_HASHED => true,
+            algorithm if algorithm == ALG_PURE => false,
+            _ => return Err(invalid("unsupported signature algorithm")),
+        };
+
+        let global = decode(global, 64, "global signature")?;
+
+        Ok(Self {
+            key_id: key_id(&data[2..10]),
+            trusted_comment: trusted_comment.to_string(),
+            hashed,
+            signature: Signature::from_slice(&data[10..]).map_err(|_| invalid("signature"))?,
+            global_signature: Signature::from_slice(&global).map_err(|_| invalid("signature"))?,
+        })
+    }
+}
+
+// Function to get the detached signature path of a file
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".");
+    signature_path.push(SIGNATURE_EXTENSION);
+    PathBuf::from(signature_path)
+}
+
+fn hash_file(path: &Path) -> SignatureResult<Vec<u8>> {
+    let mut hasher = Blake2b512::new();
+    let mut file = File::open(path)?;
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => hasher.update(&buffer[..read]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(hasher.finalize().to_vec())
+}
+
+// Function to turn libsodium's opslimit/memlimit pair into scrypt parameters, as minisign does
+fn scrypt_params(ops_limit: u64, mem_limit: u64) -> SignatureResult<scrypt::Params> {
+    let ops_limit = ops_limit.max(32768);
+    let r: u64 = 8;
+
+    let log_n_for = |max_n: u64| {
+        (1..63)
+            .find(|log_n| 1u64 << log_n > max_n / 2)
+            .unwrap_or(63)
+    };
+
+    let (log_n, p) = if ops_limit < mem_limit / 32 {
+        (log_n_for(ops_limit / (r * 4)), 1)
+    } else {
+        let log_n = log_n_for(mem_limit / (r * 128));
+        let max_rp = ((ops_limit / 4) >> log_n).min(0x3fff_ffff);
+        (log_n, max_rp / r)
+    };
+
+    scrypt::Params::new(
+        log_n as u8,
+        r as u32,
+        p.max(1) as u32,
+        scrypt::Params::RECOMMENDED_LEN,
+    )
+    .map_err(|_| invalid("invalid key derivation parameters"))
+}
+
+fn decode(line: &str, len: usize, what: &str) -> SignatureResult<Vec<u8>> {
+    match STANDARD.decode(line.trim()) {
+        Ok(data) if data.len() == len => Ok(data),
+        _ => Err(invalid(&format!("malformed {what}"))),
+    }
+}
+
+fn key_id(bytes: &[u8]) -> KeyId {
+    KeyId(bytes.try_into().expect("key id slice has a fixed length"))
+}
+
+fn invalid(msg: &str) -> SignatureError {
+    SignatureError::Invalid(msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Key and signatures of the file `test` made by minisign, from the minisign-verify test suite
+    const MINISIGN_PUBLIC_KEY: &str = "untrusted comment: minisign public key E7620F1842B4E81F\n\
+                                       RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n";
+    const MINISIGN_SIGNATURE: &str = "untrusted comment: signature from minisign secret key\n\
+        RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=\n\
+        trusted comment: timestamp:1556193335\tfile:test\n\
+        y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==\n";
+    const MINISIGN_LEGACY_SIGNATURE: &str = "untrusted comment: signature from minisign secret key\n\
+        RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=\n\
+        trusted comment: timestamp:1555779966\tfile:test\n\
+        QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==\n";
+
+    // Key pair in the layout `minisign -G` writes, encrypted with its default scrypt limits
+    const ENCRYPTED_SECRET_KEY: &str = "untrusted comment: minisign encrypted secret key\n\
+        RWRTY0IyFbYMOUZceiHQN9zzZVbGuncWtseq2NtIxNhh2kvemN8AAAACAAAAAAAAAEAAAAAAstSurCiWnFwcUBumUpjg2KxP+3laYqxlWa0DZm7jRl79p7DmguuL2XQL865Csn+fddBEa4uYKf/7PIOMQ5ChxzPD/bFJOLFVuIh22NXMxaZ+Jejiqa1mwJoif5nXxtsvRgkxMwtox6o=\n";
+    const ENCRYPTED_PUBLIC_KEY: &str = "RWRVcGFjVGVzdCmsuuFBvMrwsi4alNNNC8c2HlJtC/4SyJeUvJMilm3X";
+    const PASSWORD: &str = "upac test password";
+
+    fn key_pair(seed: u8) -> (SecretKey, PublicKey) {
+        let key = SigningKey::from_bytes(&[seed; 32]);
+        let key_id = KeyId([seed; KEY_ID_LEN]);
+        let public_key = PublicKey {
+            key_id,
+            key: key.verifying_key(),
+        };
+        (SecretKey { key_id, key }, public_key)
+    }
+
+    fn assert_mismatch(result: SignatureResult<()>) {
+        let err = result.expect_err("tampered signature was accepted");
+        assert!(err.to_string().starts_with("Signature mismatch"), "{err}");
+    }
+
+    #[test]
+    fn signed_file_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tool-1.0.upac");
+        fs::write(&path, "package").unwrap();
+
+        let (secret_key, public_key) = key_pair(1);
+        let signature = SignatureFile::load(&secret_key.sign_file(&path).unwrap()).unwrap();
+
+        assert_eq!(signature.key_id, secret_key.key_id);
+        assert!(signature
+            .trusted_comment
+            .ends_with("file:tool-1.0.upac\thashed"));
+        public_key.verify_file(&path, &signature).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_signature() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tool-1.0.upac");
+        fs::write(&path, "package").unwrap();
+
+        let (secret_key, public_key) = key_pair(1);
+        let signature_path = secret_key.sign_file(&path).unwrap();
+        let content = fs::read_to_string(&signature_path).unwrap();
+
+        // The signed file changed after signing
+        fs::write(&path, "package!").unwrap();
+        assert_mismatch(
+            public_key.verify_file(&path, &SignatureFile::load(&signature_path).unwrap()),
+        );
+        fs::write(&path, "package").unwrap();
+
+        // The trusted comment is edited without its global signature
+        let edited = content.replace("file:tool-1.0.upac", "file:other-1.0.upac");
+        assert_mismatch(public_key.verify_file(&path, &SignatureFile::parse(&edited).unwrap()));
+
+        // A bit of the signature itself is flipped
+        let lines: Vec<&str> = content.lines().collect();
+        let mut data = STANDARD.decode(lines[1]).unwrap();
+        data[SIGNATURE_LEN - 1] ^= 1;
+        let flipped = content.replace(lines[1], &STANDARD.encode(data));
+        assert_mismatch(public_key.verify_file(&path, &SignatureFile::parse(&flipped).unwrap()));
+
+        // A key with the same id but a different secret does not verify it either
+        let (_, other_key) = key_pair(2);
+        let impostor = PublicKey {
+            key_id: public_key.key_id,
+            ..other_key
+        };
+        assert_mismatch(impostor.verify_file(&path, &SignatureFile::parse(&content).unwrap()));
+    }
+
+    #[test]
+    fn verifies_signatures_made_by_minisign() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test");
+        fs::write(&path, "test").unwrap();
+
+        let public_key = PublicKey::parse(MINISIGN_PUBLIC_KEY).unwrap();
+        assert_eq!(public_key.key_id.to_string(), "E7620F1842B4E81F");
+
+        for content in [MINISIGN_SIGNATURE, MINISIGN_LEGACY_SIGNATURE] {
+            let signature = SignatureFile::parse(content).unwrap();
+            assert!(signature.trusted_comment.ends_with("\tfile:test"));
+            public_key.verify_file(&path, &signature).unwrap();
+        }
+
+        fs::write(&path, "tesT").unwrap();
+        assert_mismatch(
+            public_key.verify_file(&path, &SignatureFile::parse(MINISIGN_SIGNATURE).unwrap()),
+        );
+    }
+
+    #[test]
+    fn signs_with_minisign_secret_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.toml.part");
+        fs::write(&path, "[packages]\n").unwrap();
+
+        assert!(SecretKey::parse(ENCRYPTED_SECRET_KEY, None).is_err());
+        let secret_key = SecretKey::parse(ENCRYPTED_SECRET_KEY, Some(PASSWORD)).unwrap();
+        let public_key = PublicKey::parse(ENCRYPTED_PUBLIC_KEY).unwrap();
+        assert_eq!(secret_key.key_id, public_key.key_id);
+
+        let signature_path = secret_key.sign_file_as(&path, "index.toml").unwrap();
+        let content = fs::read_to_string(&signature_path).unwrap();
+        public_key
+            .verify_file(&path, &SignatureFile::parse(&content).unwrap())
+            .unwrap();
+
+        // minisign's own verifier accepts the signature without legacy support
+        let minisign_key = minisign_verify::PublicKey::from_base64(ENCRYPTED_PUBLIC_KEY).unwrap();
+        let minisign_signature = minisign_verify::Signature::decode(&content).unwrap();
+        assert!(minisign_signature
+            .trusted_comment()
+            .contains("\tfile:index.toml\t"));
+        minisign_key
+            .verify(&fs::read(&path).unwrap(), &minisign_signature, false)
+            .unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+// Imports
+use upac_types::{SignatureError, SignatureResult};
+
+// Mods
+pub mod keyring;
+pub mod minisign;
+
+pub use keyring::{Keyring, SignatureVerifier};
+pub use minisign::{
+    signature_path, KeyId, PublicKey, SecretKey, SignatureFile, SIGNATURE_EXTENSION,
+};
+
+// Default directory of trusted public keys
+pub const DEFAULT_KEYRING_DIR: &str = "/etc/upac/keys";
+
+// How strictly package signatures are enforced
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignaturePolicy {
+    // Every package needs a valid signature from the keyring
+    Required,
+    // Signatures are checked when present
+    #[default]
+    Optional,
+    // Signatures are ignored
+    Never,
+}
+
+impl SignaturePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Required => "required",
+            Self::Optional => "optional",
+            Self::Never => "never",
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// Imports
+use upac_types::{ExtractedPackage, FileEntry};
+
+use stabby::option::Option as StabOption;
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+use std::path::PathBuf;
+
+pub(crate) fn stab_vec(values: &[&str]) -> StabVec<StabString> {
+    values.iter().map(|value| StabString::from(*value)).collect()
+}
+
+// Function to describe a package without scripts or relations, the way a backend extracts it
+pub(crate) fn package(name: &str, version: &str, files: &[&str]) -> ExtractedPackage {
+    ExtractedPackage {
+        name: name.into(),
+        version: version.into(),
+        format: "upac".into(),
+        file_list: stab_vec(files),
+        dependencies: StabVec::new(),
+        provides: StabVec::new(),
+        conflicts: StabVec::new(),
+        config_files: StabVec::new(),
+        pre_install: StabOption::None(),
+        post_install: StabOption::None(),
+        pre_remove: StabOption::None(),
+        post_remove: StabOption::None(),
+    }
+}
+
+// Function to record files by path only, as the database does for entries without metadata
+pub(crate) fn file_entries(files: &[&str]) -> Vec<FileEntry> {
+    files
+        .iter()
+        .map(|file| FileEntry::from(PathBuf::from(file)))
+        .collect()
+}
@@ -0,0 +1,77 @@
+// Imports
+use upac_types::{FileEntry, FileType, LinkStrategy};
+use upac_types::{DatabaseError, InstallerError, InstallerResult};
+
+use crate::checksum::sha256_file;
+use crate::database::Database;
+use crate::installer::read_file_entry;
+
+use stabby::string::String as StabString;
+use stabby::vec::Vec as StabVec;
+
+// Mods
+pub mod verifier;
+
+pub use verifier::PackageVerifier;
+
+// Kind of difference between a recorded file and the filesystem
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileIssueKind {
+    Missing,
+    TypeChanged,
+    SizeMismatch,
+    ChecksumMismatch,
+    ModeMismatch,
+    OwnerMismatch,
+    LinkTargetMismatch,
+    // The copy under repo_path is gone
+    RepoMissing,
+    // The copy under repo_path no longer matches the record
+    RepoMismatch,
+    // The installed file is no longer a hard link of the repo copy
+    Unlinked,
+}
+
+impl FileIssueKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Missing => "missing",
+            Self::TypeChanged => "type_changed",
+            Self::SizeMismatch => "size_mismatch",
+            Self::ChecksumMismatch => "checksum_mismatch",
+            Self::ModeMismatch => "mode_mismatch",
+            Self::OwnerMismatch => "owner_mismatch",
+            Self::LinkTargetMismatch => "link_target_mismatch",
+            Self::RepoMissing => "repo_missing",
+            Self::RepoMismatch => "repo_mismatch",
+            Self::Unlinked => "unlinked",
+        }
+    }
+}
+
+// Single problem found for an installed file
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct FileIssue {
+    pub package: StabString,
+    pub path: StabString,
+    pub expected: StabString,
+    pub actual: StabString,
+    pub kind: FileIssueKind,
+}
+
+// Result of a verification pass
+#[stabby::stabby]
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub issues: StabVec<FileIssue>,
+    pub packages_checked: u64,
+    pub files_checked: u64,
+}
+
+// Trait for checking installed files against their database records
+pub(crate) trait Verifier {
+    fn verify(&self, package: Option<&str>) -> InstallerResult<VerifyReport>;
+}
@@ -0,0 +1,224 @@
+// Imports
+use super::{read_file_entry, sha256_file};
+use super::{Database, FileEntry, FileIssue, FileIssueKind, FileType, LinkStrategy};
+use super::{Verifier, VerifyReport};
+use super::{DatabaseError, InstallerError, InstallerResult};
+
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+// Struct definition for the integrity checker
+pub struct PackageVerifier<'a> {
+    database: &'a dyn Database,
+    root_path: &'a Path,
+    repo_path: &'a Path,
+}
+
+impl<'a> PackageVerifier<'a> {
+    pub fn new(database: &'a dyn Database, root_path: &'a Path, repo_path: &'a Path) -> Self {
+        Self {
+            database,
+            root_path,
+            repo_path,
+        }
+    }
+
+    // Function to compare one recorded file with the installed one and its repo copy
+    fn verify_file(
+        &self,
+        package: &str,
+        expected: &FileEntry,
+        issues: &mut Vec<FileIssue>,
+    ) -> InstallerResult<()> {
+        let mut report = |kind: FileIssueKind, expected_value: String, actual_value: String| {
+            issues.push(FileIssue {
+                package: package.into(),
+                path: expected.path.display().to_string().as_str().into(),
+                expected: expected_value.as_str().into(),
+                actual: actual_value.as_str().into(),
+                kind,
+            })
+        };
+
+        match fs::symlink_metadata(self.root_path.join(&expected.path)) {
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                report(
+                    FileIssueKind::Missing,
+                    expected.file_type.as_str().to_string(),
+                    String::new(),
+                );
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        let actual = read_file_entry(self.root_path, &expected.path)?;
+
+        // Records made before metadata was stored only prove the file exists
+        let recorded = expected.file_type != FileType::Unknown;
+
+        if recorded && actual.file_type != expected.file_type {
+            report(
+                FileIssueKind::TypeChanged,
+                expected.file_type.as_str().to_string(),
+                actual.file_type.as_str().to_string(),
+            );
+            return Ok(());
+        }
+
+        if recorded && actual.mode != expected.mode {
+            report(
+                FileIssueKind::ModeMismatch,
+                format!("{:o}", expected.mode),
+                format!("{:o}", actual.mode),
+            );
+        }
+
+        if recorded && (actual.uid, actual.gid) != (expected.uid, expected.gid) {
+            report(
+                FileIssueKind::OwnerMismatch,
+                format!("{}:{}", expected.uid, expected.gid),
+                format!("{}:{}", actual.uid, actual.gid),
+            );
+        }
+
+        match actual.file_type {
+            FileType::Regular => {
+                if recorded && actual.size != expected.size {
+                    report(
+                        FileIssueKind::SizeMismatch,
+                        expected.size.to_string(),
+                        actual.size.to_string(),
+                    );
+                }
+
+                if let (Some(expected_sum), Some(actual_sum)) = (&expected.sha256, &actual.sha256) {
+                    if expected_sum != actual_sum {
+                        report(
+                            FileIssueKind::ChecksumMismatch,
+                            expected_sum.clone(),
+                            actual_sum.clone(),
+                        );
+                    }
+                }
+            }
+            FileType::Symlink if recorded && actual.link_target != expected.link_target => {
+                let display = |target: &Option<std::path::PathBuf>| {
+                    target
+                        .as_ref()
+                        .map(|target| target.display().to_string())
+                        .unwrap_or_default()
+                };
+                report(
+                    FileIssueKind::LinkTargetMismatch,
+                    display(&expected.link_target),
+                    display(&actual.link_target),
+                );
+            }
+            _ => {}
+        }
+
+        if actual.file_type == FileType::Regular {
+            self.verify_repo_copy(expected, &actual, &mut report)?;
+        }
+
+        Ok(())
+    }
+
+    // Function to check the repo copy the installed file was hard-linked from
+    fn verify_repo_copy(
+        &self,
+        expected: &FileEntry,
+        actual: &FileEntry,
+        report: &mut impl FnMut(FileIssueKind, String, String),
+    ) -> InstallerResult<()> {
+        let repo_file_path = self.repo_path.join(&expected.path);
+
+        let repo_metadata = match fs::symlink_metadata(&repo_file_path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                report(
+                    FileIssueKind::RepoMissing,
+                    repo_file_path.display().to_string(),
+                    String::new(),
+                );
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // Only hard-linked files share the inode; cross-filesystem copies are checked by content alone
+        // and configuration files may be replaced by local edits
+        let strategy = expected.link_strategy.unwrap_or_default();
+        let installed_metadata = fs::symlink_metadata(self.root_path.join(&expected.path))?;
+        if strategy == LinkStrategy::HardLink
+            && !expected.config
+            && (repo_metadata.dev(), repo_metadata.ino())
+                != (installed_metadata.dev(), installed_metadata.ino())
+        {
+            report(
+                FileIssueKind::Unlinked,
+                repo_file_path.display().to_string(),
+                String::new(),
+            );
+        }
+
+        if !repo_metadata.is_file() {
+            report(
+                FileIssueKind::RepoMismatch,
+                FileType::Regular.as_str().to_string(),
+                String::new(),
+            );
+            return Ok(());
+        }
+
+        let repo_sum = sha256_file(&repo_file_path)?;
+        match (&expected.sha256, &actual.sha256) {
+            (Some(expected_sum), _) if *expected_sum != repo_sum => {
+                report(FileIssueKind::RepoMismatch, expected_sum.clone(), repo_sum);
+            }
+            // Without a recorded checksum the repo copy is the only reference
+            (None, Some(actual_sum)) if *actual_sum != repo_sum => {
+                report(
+                    FileIssueKind::ChecksumMismatch,
+                    repo_sum,
+                    actual_sum.clone(),
+                );
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Verifier for PackageVerifier<'_> {
+    fn verify(&self, package: Option<&str>) -> InstallerResult<VerifyReport> {
+        let packages = match package {
+            Some(package) => vec![self.database.get_package(package).map_err(|err| match err {
+                DatabaseError::NotFound => {
+                    InstallerError::Installer(format!("Package {package} is not installed").into())
+                }
+                err => err.into(),
+            })?],
+            None => self.database.list_packages()?,
+        };
+
+        let mut issues = Vec::new();
+        let mut report = VerifyReport::default();
+
+        for package in &packages {
+            for file in self.database.get_file_entries(&package.name)? {
+                self.verify_file(&package.name, &file, &mut issues)?;
+                report.files_checked += 1;
+            }
+            report.packages_checked += 1;
+        }
+
+        report.issues = issues.into_iter().collect();
+        Ok(report)
+    }
+}
@@ -0,0 +1,362 @@
+// Imports
+use super::VersionScheme;
+
+use std::cmp::Ordering;
+
+// Parts of an `epoch:version-release` string; a missing epoch is 0
+struct Evr<'a> {
+    epoch: &'a str,
+    version: &'a str,
+    release: Option<&'a str>,
+}
+
+impl<'a> Evr<'a> {
+    fn parse(full: &'a str) -> Self {
+        let (epoch, rest) = match full.split_once(':') {
+            Some((epoch, rest))
+                if !epoch.is_empty() && epoch.bytes().all(|byte| byte.is_ascii_digit()) =>
+            {
+                (epoch, rest)
+            }
+            _ => ("0", full),
+        };
+
+        let (version, release) = match rest.rsplit_once('-') {
+            Some((version, release)) => (version, Some(release)),
+            None => (rest, None),
+        };
+
+        Self {
+            epoch,
+            version,
+            release,
+        }
+    }
+}
+
+// Function to order two versions by the rules of a scheme
+pub fn compare_versions(scheme: VersionScheme, left: &str, right: &str) -> Ordering {
+    if left == right {
+        return Ordering::Equal;
+    }
+
+    let left = Evr::parse(left);
+    let right = Evr::parse(right);
+
+    match scheme {
+        // dpkg always compares the revision, a missing one counts as empty
+        VersionScheme::Dpkg => compare_numbers(left.epoch, right.epoch)
+            .then_with(|| dpkg_compare(left.version, right.version))
+            .then_with(|| {
+                dpkg_compare(
+                    left.release.unwrap_or_default(),
+                    right.release.unwrap_or_default(),
+                )
+            }),
+        // rpm and pacman compare the release only when both versions carry one
+        VersionScheme::Alpm | VersionScheme::Rpm => compare_numbers(left.epoch, right.epoch)
+            .then_with(|| rpm_compare(scheme, left.version, right.version))
+            .then_with(|| match (left.release, right.release) {
+                (Some(left), Some(right)) => rpm_compare(scheme, left, right),
+                _ => Ordering::Equal,
+            }),
+    }
+}
+
+fn compare_numbers(left: &str, right: &str) -> Ordering {
+    let left = left.trim_start_matches('0');
+    let right = right.trim_start_matches('0');
+
+    left.len().cmp(&right.len()).then_with(|| left.cmp(right))
+}
+
+// Function to compare version segments like rpmvercmp. rpm adds `~` (sorts before anything, even
+// the end) and `^` (sorts after the end but before anything else); pacman has neither and never
+// lets a trailing letter segment beat the end of the other version
+fn rpm_compare(scheme: VersionScheme, left: &str, right: &str) -> Ordering {
+    let extended = scheme == VersionScheme::Rpm;
+    let is_separator =
+        |byte: u8| !(byte.is_ascii_alphanumeric() || extended && matches!(byte, b'~' | b'^'));
+
+    let (left, right) = (left.as_bytes(), right.as_bytes());
+    let (mut left_index, mut right_index) = (0, 0);
+
+    loop {
+        // pacman stops before skipping separators once either version has ended
+        if !extended && (left_index == left.len() || right_index == right.len()) {
+            break;
+        }
+
+        let left_start = left_index;
+        let right_start = right_index;
+        while left_index < left.len() && is_separator(left[left_index]) {
+            left_index += 1;
+        }
+        while right_index < right.len() && is_separator(right[right_index]) {
+            right_index += 1;
+        }
+
+        let left_byte = left.get(left_index).copied();
+        let right_byte = right.get(right_index).copied();
+
+        if extended {
+            if left_byte == Some(b'~') || right_byte == Some(b'~') {
+                if left_byte != Some(b'~') {
+                    return Ordering::Greater;
+                }
+                if right_byte != Some(b'~') {
+                    return Ordering::Less;
+                }
+                left_index += 1;
+                right_index += 1;
+                continue;
+            }
+
+            if left_byte == Some(b'^') || right_byte == Some(b'^') {
+                return match (left_byte, right_byte) {
+                    (None, _) => Ordering::Less,
+                    (_, None) => Ordering::Greater,
+                    (Some(b'^'), Some(b'^')) => {
+                        left_index += 1;
+                        right_index += 1;
+                        continue;
+                    }
+                    (Some(b'^'), _) => Ordering::Less,
+                    _ => Ordering::Greater,
+                };
+            }
+        }
+
+        let (Some(left_byte), Some(_)) = (left_byte, right_byte) else {
+            break;
+        };
+
+        // pacman treats `1.0` and `1..0` as different versions
+        if !extended && left_index - left_start != right_index - right_start {
+            return (left_index - left_start).cmp(&(right_index - right_start));
+        }
+
+        let numeric = left_byte.is_ascii_digit();
+        let segment_end = |bytes: &[u8], start: usize| {
+            start
+                + bytes[start..]
+                    .iter()
+                    .take_while(|byte| {
+                        if numeric {
+                            byte.is_ascii_digit()
+                        } else {
+                            byte.is_ascii_alphabetic()
+                        }
+                    })
+                    .count()
+        };
+
+        let left_end = segment_end(left, left_index);
+        let right_end = segment_end(right, right_index);
+
+        // A number is newer than letters
+        if right_end == right_index {
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let left_segment = std::str::from_utf8(&left[left_index..left_end]).unwrap_or_default();
+        let right_segment = std::str::from_utf8(&right[right_index..right_end]).unwrap_or_default();
+
+        let ordering = if numeric {
+            compare_numbers(left_segment, right_segment)
+        } else {
+            left_segment.cmp(right_segment)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        left_index = left_end;
+        right_index = right_end;
+    }
+
+    let left_rest = left.get(left_index).copied();
+    let right_rest = right.get(right_index).copied();
+
+    match (left_rest, right_rest) {
+        (None, None) => Ordering::Equal,
+        _ if extended => {
+            if left_rest.is_none() {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }
+        (None, Some(right_byte)) if !right_byte.is_ascii_alphabetic() => Ordering::Less,
+        (Some(left_byte), _) if left_byte.is_ascii_alphabetic() => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+// Function to compare version parts like dpkg's verrevcmp: letters sort before other
+// characters, and `~` sorts before anything, even the end of the string
+fn dpkg_compare(left: &str, right: &str) -> Ordering {
+    let order = |byte: Option<u8>| -> i32 {
+        match byte {
+            None => 0,
+            Some(byte) if byte.is_ascii_digit() => 0,
+            Some(byte) if byte.is_ascii_alphabetic() => i32::from(byte),
+            Some(b'~') => -1,
+            Some(byte) => i32::from(byte) + 256,
+        }
+    };
+
+    let (left, right) = (left.as_bytes(), right.as_bytes());
+    let (mut left_index, mut right_index) = (0, 0);
+    let is_digit = |bytes: &[u8], index: usize| bytes.get(index).is_some_and(u8::is_ascii_digit);
+
+    while left_index < left.len() || right_index < right.len() {
+        while (left_index < left.len() && !is_digit(left, left_index))
+            || (right_index < right.len() && !is_digit(right, right_index))
+        {
+            let left_order = order(left.get(left_index).copied());
+            let right_order = order(right.get(right_index).copied());
+            if left_order != right_order {
+                return left_order.cmp(&right_order);
+            }
+            left_index += 1;
+            right_index += 1;
+        }
+
+        while left.get(left_index) == Some(&b'0') {
+            left_index += 1;
+        }
+        while right.get(right_index) == Some(&b'0') {
+            right_index += 1;
+        }
+
+        let mut first_difference = Ordering::Equal;
+        while is_digit(left, left_index) && is_digit(right, right_index) {
+            if first_difference == Ordering::Equal {
+                first_difference = left[left_index].cmp(&right[right_index]);
+            }
+            left_index += 1;
+            right_index += 1;
+        }
+
+        if is_digit(left, left_index) {
+            return Ordering::Greater;
+        }
+        if is_digit(right, right_index) {
+            return Ordering::Less;
+        }
+        if first_difference != Ordering::Equal {
+            return first_difference;
+        }
+    }
+
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cmp::Ordering::{Equal, Greater, Less};
+
+    // Function to check each pair both ways, so the ordering is also antisymmetric
+    fn check(scheme: VersionScheme, cases: &[(&str, &str, Ordering)]) {
+        for (left, right, expected) in cases {
+            assert_eq!(
+                compare_versions(scheme, left, right),
+                *expected,
+                "{left} vs {right}"
+            );
+            assert_eq!(
+                compare_versions(scheme, right, left),
+                expected.reverse(),
+                "{right} vs {left}"
+            );
+        }
+    }
+
+    // Vectors from pacman's vercmp tests
+    #[test]
+    fn alpm_versions() {
+        check(
+            VersionScheme::Alpm,
+            &[
+                ("1.5.0", "1.5.0", Equal),
+                ("1.5.1", "1.5.0", Greater),
+                ("1.5.1", "1.5", Greater),
+                ("1.5-1", "1.5", Equal),
+                ("1.5-1", "1.5-2", Less),
+                ("1.5-2", "1.5.1-1", Less),
+                ("1.0a", "1.0alpha", Less),
+                ("1.0alpha", "1.0b", Less),
+                ("1.0beta", "1.0pre", Less),
+                ("1.0pre", "1.0rc", Less),
+                ("1.0rc", "1.0", Less),
+                ("1.0", "1.0.a", Less),
+                ("1.0.a", "1.0.1", Less),
+                ("1.0.0", "1.0a", Greater),
+                ("1.1a", "1.1", Less),
+                ("1:1.0", "2.0", Greater),
+                ("0:1.0", "1.0", Equal),
+                ("1.0", "1..0", Less),
+                ("010", "10", Equal),
+            ],
+        );
+    }
+
+    // Vectors from dpkg's version tests
+    #[test]
+    fn dpkg_versions() {
+        check(
+            VersionScheme::Dpkg,
+            &[
+                ("1.0", "1.0", Equal),
+                ("1.0-1", "1.0", Greater),
+                ("1.0", "1.0-0", Equal),
+                ("1.0~rc1", "1.0", Less),
+                ("1.0~~", "1.0~", Less),
+                ("1.0a", "1.0", Greater),
+                ("1.0a", "1.0+", Less),
+                ("1.0+dfsg", "1.0", Greater),
+                ("1:0.1", "2.0", Greater),
+                ("2.30-1ubuntu1", "2.30-1", Greater),
+                ("1.2.3-1", "1.2.10-1", Less),
+                ("001", "1", Equal),
+                ("1.0-1~bpo1", "1.0-1", Less),
+                ("7.6p2-4", "7.6-0", Greater),
+                ("1.18.36:5.4", "1.18.36:5.5", Less),
+            ],
+        );
+    }
+
+    // Vectors from rpm's rpmvercmp tests
+    #[test]
+    fn rpm_versions() {
+        check(
+            VersionScheme::Rpm,
+            &[
+                ("1.0", "1.0", Equal),
+                ("2.0.1", "2.0", Greater),
+                ("2.0.1a", "2.0.1", Greater),
+                ("5.5p1", "5.5p2", Less),
+                ("5.5p10", "5.5p1", Greater),
+                ("10xyz", "10.1xyz", Less),
+                ("xyz10", "xyz10.1", Less),
+                ("1.0a", "1.0.a", Equal),
+                ("1.0~rc1", "1.0", Less),
+                ("1.0~rc1~git", "1.0~rc1", Less),
+                ("1.0^", "1.0", Greater),
+                ("1.0^git1", "1.0.1", Less),
+                ("1.0^git1", "1.0~rc1", Greater),
+                ("1:1.0-1", "2.0-1", Greater),
+                ("1.0-1", "1.0", Equal),
+                ("1.0-1.fc40", "1.0-1.fc39", Greater),
+                ("a", "1", Less),
+            ],
+        );
+    }
+}
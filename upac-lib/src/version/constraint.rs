@@ -0,0 +1,129 @@
+// Imports
+use super::{compare_versions, VersionScheme};
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+
+// Comparison of a dependency constraint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+}
+
+impl Operator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Equal => "=",
+            Self::GreaterEqual => ">=",
+            Self::Greater => ">",
+        }
+    }
+
+    // Function to check whether `version <op> required` holds for an ordering of the two
+    pub fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            Self::Less => ordering == Ordering::Less,
+            Self::LessEqual => ordering != Ordering::Greater,
+            Self::Equal => ordering == Ordering::Equal,
+            Self::GreaterEqual => ordering != Ordering::Less,
+            Self::Greater => ordering == Ordering::Greater,
+        }
+    }
+}
+
+// Dependency, provide or conflict like `foo`, `foo>=1.2`, `bar<2` or `baz=1.0-3`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub name: String,
+    pub requirement: Option<(Operator, String)>,
+}
+
+impl Constraint {
+    // Function to split a relation into its name and optional version requirement; an operator
+    // without a version is ignored
+    pub fn parse(relation: &str) -> Self {
+        let Some(start) = relation.find(['<', '>', '=']) else {
+            return Self {
+                name: relation.trim().to_string(),
+                requirement: None,
+            };
+        };
+
+        let name = relation[..start].trim().to_string();
+        let rest = &relation[start..];
+
+        let (operator, version) = [
+            (">=", Operator::GreaterEqual),
+            ("<=", Operator::LessEqual),
+            ("==", Operator::Equal),
+            ("=", Operator::Equal),
+            (">", Operator::Greater),
+            ("<", Operator::Less),
+        ]
+        .into_iter()
+        .find_map(|(symbol, operator)| {
+            rest.strip_prefix(symbol)
+                .map(|version| (operator, version.trim()))
+        })
+        .unwrap_or((Operator::Equal, ""));
+
+        Self {
+            name,
+            requirement: (!version.is_empty()).then(|| (operator, version.to_string())),
+        }
+    }
+
+    // Function to check a version of a package with this name against the requirement
+    pub fn matches(&self, version: &str, scheme: VersionScheme) -> bool {
+        match &self.requirement {
+            None => true,
+            Some((operator, required)) => {
+                operator.accepts(compare_versions(scheme, version, required))
+            }
+        }
+    }
+
+    // Function to check whether a package satisfies the constraint by its own name and version,
+    // or through one of its provides. An unversioned provide only satisfies unversioned constraints
+    pub fn satisfied_by<'a>(
+        &self,
+        name: &str,
+        version: &str,
+        provides: impl IntoIterator<Item = &'a str>,
+        scheme: VersionScheme,
+    ) -> bool {
+        if name == self.name && self.matches(version, scheme) {
+            return true;
+        }
+
+        provides.into_iter().any(|provide| {
+            let provide = Self::parse(provide);
+            if provide.name != self.name {
+                return false;
+            }
+
+            match (&self.requirement, &provide.requirement) {
+                (None, _) => true,
+                (Some(_), Some((Operator::Equal, provided))) => self.matches(provided, scheme),
+                (Some(_), _) => false,
+            }
+        })
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match &self.requirement {
+            Some((operator, version)) => {
+                write!(formatter, "{}{}{version}", self.name, operator.as_str())
+            }
+            None => write!(formatter, "{}", self.name),
+        }
+    }
+}
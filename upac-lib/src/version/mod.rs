@@ -0,0 +1,61 @@
+// Mods
+pub mod compare;
+pub mod constraint;
+
+pub use compare::compare_versions;
+pub use constraint::{Constraint, Operator};
+
+use std::collections::HashMap;
+
+// Ordering rules of `epoch:version-release` strings; every backend states the one its format uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VersionScheme {
+    // pacman's vercmp, also used by native packages
+    #[default]
+    Alpm,
+    Dpkg,
+    Rpm,
+}
+
+impl VersionScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Alpm => "alpm",
+            Self::Dpkg => "dpkg",
+            Self::Rpm => "rpm",
+        }
+    }
+
+    // Function to get the scheme of a package format recorded in the database or a repository index
+    pub fn for_format(format: &str) -> Self {
+        match format {
+            "deb" => Self::Dpkg,
+            "rpm" => Self::Rpm,
+            _ => Self::Alpm,
+        }
+    }
+}
+
+// Version ordering of each package format as the registered backends state it
+#[derive(Debug, Clone, Default)]
+pub struct VersionSchemes {
+    schemes: HashMap<String, VersionScheme>,
+}
+
+impl VersionSchemes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, format: &str, scheme: VersionScheme) {
+        self.schemes.insert(format.to_string(), scheme);
+    }
+
+    // Function to get the scheme of a format, guessed from its name when no backend states one
+    pub fn get(&self, format: &str) -> VersionScheme {
+        self.schemes
+            .get(format)
+            .copied()
+            .unwrap_or_else(|| VersionScheme::for_format(format))
+    }
+}
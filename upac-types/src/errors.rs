@@ -88,6 +88,7 @@ impl Display for ConfigError {
 pub enum DatabaseError {
     Io(IoError),
     Toml(String),
+    Sqlite(String),
     NotFound,
     Lock,
     Path(PathBuf),
@@ -113,6 +114,13 @@ impl From<toml::de::Error> for DatabaseError {
     }
 }
 
+#[cfg(feature = "sqlite-errors")]
+impl From<rusqlite::Error> for DatabaseError {
+    fn from(err: rusqlite::Error) -> Self {
+        DatabaseError::Sqlite(err.to_string())
+    }
+}
+
 impl From<LockError> for DatabaseError {
     fn from(_: LockError) -> Self {
         DatabaseError::Lock
@@ -124,6 +132,7 @@ impl Display for DatabaseError {
         match self {
             Self::Io(err) => write!(formatter, "IO error: {err}"),
             Self::Toml(err) => write!(formatter, "TOML error: {err}"),
+            Self::Sqlite(err) => write!(formatter, "SQLite error: {err}"),
             Self::NotFound => write!(formatter, "Not found"),
             Self::Lock => write!(formatter, "Lock error"),
             Self::Path(path) => write!(formatter, "Path error: {}", path.display()),
@@ -141,6 +150,8 @@ pub enum InstallerError {
     Database(StabString),
     Installer(StabString),
     Dependency(StabString),
+    Script(StabString),
+    Conflict(StabString),
 }
 
 impl From<IoError> for InstallerError {
@@ -186,6 +197,7 @@ impl From<DatabaseError> for InstallerError {
         let msg = match err {
             DatabaseError::Io(err) => format!("IO error: {err}"),
             DatabaseError::Toml(err) => format!("TOML error: {err}"),
+            DatabaseError::Sqlite(err) => format!("SQLite error: {err}"),
             DatabaseError::NotFound => "Not found".to_string(),
             DatabaseError::Lock => "Lock error".to_string(),
             DatabaseError::Path(path) => format!("Path error: {}", path.display()),
@@ -194,6 +206,34 @@ impl From<DatabaseError> for InstallerError {
     }
 }
 
+// Packages extracted while a transaction is applied fail as part of it
+impl From<BackendError> for InstallerError {
+    fn from(err: BackendError) -> Self {
+        Self::Installer(err.to_string().into())
+    }
+}
+
+impl Debug for InstallerError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{self}")
+    }
+}
+
+impl Display for InstallerError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = self.match_ref(
+            |msg| format!("IO error: {msg}"),
+            |msg| format!("Lock error: {msg}"),
+            |msg| format!("Database error: {msg}"),
+            |msg| format!("Installer error: {msg}"),
+            |msg| format!("Dependency error: {msg}"),
+            |msg| format!("Script error: {msg}"),
+            |msg| format!("File conflicts: {msg}"),
+        );
+        write!(formatter, "{msg}")
+    }
+}
+
 // ─── OSTreeError ─────────────────────────────────────────────────────────────
 
 #[repr(stabby)]
@@ -246,6 +286,161 @@ impl Display for OSTreeError {
     }
 }
 
+// ─── BackendError ────────────────────────────────────────────────────────────
+
+#[repr(stabby)]
+#[stabby::stabby]
+pub enum BackendError {
+    Io(StabString),
+    // No backend recognises the file
+    Unsupported(StabString),
+    // The archive or its metadata is malformed
+    Format(StabString),
+    // A plugin could not be loaded or was built against other types
+    Plugin(StabString),
+    // The package signature is missing, untrusted or does not match
+    Signature(StabString),
+}
+
+impl From<SignatureError> for BackendError {
+    fn from(err: SignatureError) -> Self {
+        Self::Signature(err.to_string().into())
+    }
+}
+
+impl From<IoError> for BackendError {
+    fn from(err: IoError) -> Self {
+        Self::Io(err.to_string().into())
+    }
+}
+
+impl Debug for BackendError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{self}")
+    }
+}
+
+impl Display for BackendError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = self.match_ref(
+            |msg| format!("IO error: {msg}"),
+            |msg| format!("Unsupported package format: {msg}"),
+            |msg| format!("Invalid package: {msg}"),
+            |msg| format!("Plugin error: {msg}"),
+            |msg| format!("Signature error: {msg}"),
+        );
+        write!(formatter, "{msg}")
+    }
+}
+
+// ─── SignatureError ──────────────────────────────────────────────────────────
+
+#[repr(stabby)]
+#[stabby::stabby]
+pub enum SignatureError {
+    Io(StabString),
+    // The policy requires a signature and the package has none
+    Missing(StabString),
+    // Signed by a key that is not in the keyring
+    UntrustedKey(StabString),
+    // Malformed signature or key file
+    Invalid(StabString),
+    // The signature does not match the package
+    Mismatch(StabString),
+}
+
+impl From<IoError> for SignatureError {
+    fn from(err: IoError) -> Self {
+        Self::Io(err.to_string().into())
+    }
+}
+
+impl Debug for SignatureError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{self}")
+    }
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = self.match_ref(
+            |msg| format!("IO error: {msg}"),
+            |msg| format!("Missing signature: {msg}"),
+            |msg| format!("Untrusted key: {msg}"),
+            |msg| format!("Invalid signature data: {msg}"),
+            |msg| format!("Signature mismatch: {msg}"),
+        );
+        write!(formatter, "{msg}")
+    }
+}
+
+// ─── RepositoryError ─────────────────────────────────────────────────────────
+
+#[repr(stabby)]
+#[stabby::stabby]
+pub enum RepositoryError {
+    Io(StabString),
+    // A repository URL could not be fetched
+    Network(StabString),
+    // The repository index is malformed
+    Index(StabString),
+    // No configured repository has the package
+    NotFound(StabString),
+    // A downloaded file does not match the size or checksum of its index entry
+    Checksum(StabString),
+    // The index signature is missing, untrusted or does not match
+    Signature(StabString),
+    Database(StabString),
+    // A package file could not be read by its backend
+    Backend(StabString),
+}
+
+impl From<IoError> for RepositoryError {
+    fn from(err: IoError) -> Self {
+        Self::Io(err.to_string().into())
+    }
+}
+
+impl From<SignatureError> for RepositoryError {
+    fn from(err: SignatureError) -> Self {
+        Self::Signature(err.to_string().into())
+    }
+}
+
+impl From<BackendError> for RepositoryError {
+    fn from(err: BackendError) -> Self {
+        Self::Backend(err.to_string().into())
+    }
+}
+
+impl From<DatabaseError> for RepositoryError {
+    fn from(err: DatabaseError) -> Self {
+        Self::Database(err.to_string().into())
+    }
+}
+
+impl Debug for RepositoryError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{self}")
+    }
+}
+
+impl Display for RepositoryError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = self.match_ref(
+            |msg| format!("IO error: {msg}"),
+            |msg| format!("Download failed: {msg}"),
+            |msg| format!("Invalid repository index: {msg}"),
+            |msg| format!("Package not found in any repository: {msg}"),
+            |msg| format!("Checksum mismatch: {msg}"),
+            |msg| format!("Index signature error: {msg}"),
+            |msg| format!("Database error: {msg}"),
+            |msg| format!("Package error: {msg}"),
+        );
+        write!(formatter, "{msg}")
+    }
+}
+
 // ─── Алиасы ──────────────────────────────────────────────────────────────────
 
 pub type LockResult<T> = Result<T, LockError>;
@@ -257,3 +452,10 @@ pub type OSTreeStabbyResult<T> = StabbyResult<T, OSTreeError>;
 
 pub type InstallerResult<T> = Result<T, InstallerError>;
 pub type InstallerStabbyResult<T> = StabbyResult<T, InstallerError>;
+
+pub type BackendResult<T> = Result<T, BackendError>;
+pub type BackendStabbyResult<T> = StabbyResult<T, BackendError>;
+
+pub type SignatureResult<T> = Result<T, SignatureError>;
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
@@ -1,10 +1,15 @@
+// stabby generates matchers taking one closure per enum variant
+#[allow(clippy::too_many_arguments)]
 mod errors;
 mod types;
 
-pub use errors::{ConfigError, DatabaseError, InstallerError, LockError, OSTreeError};
 pub use errors::{
-    ConfigResult, DatabaseResult, InstallerResult, InstallerStabbyResult, LockResult, OSTreeResult,
-    OSTreeStabbyResult,
+    BackendError, ConfigError, DatabaseError, InstallerError, LockError, OSTreeError, RepositoryError, SignatureError,
+};
+pub use errors::{
+    BackendResult, BackendStabbyResult, ConfigResult, DatabaseResult, InstallerResult, InstallerStabbyResult, LockResult, OSTreeResult,
+    OSTreeStabbyResult, RepositoryResult, SignatureResult,
 };
 
-pub use types::{ExtractedPackage, OSTreeOperation, Package};
+pub use types::{ExtractedPackage, FileEntry, FileType, InstallReason, LinkStrategy};
+pub use types::{OSTreeOperation, Package, PackageMetadata, PackageScripts};
@@ -5,6 +5,8 @@ use stabby::option::Option as StabOption;
 use stabby::string::String as StabString;
 use stabby::vec::Vec as StabVec;
 
+use std::path::PathBuf;
+
 // Types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Package {
@@ -12,6 +14,131 @@ pub struct Package {
     pub version: String,
     pub format: String,
     pub install_date: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    // Virtual names the package also satisfies dependencies on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provides: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+    #[serde(default)]
+    pub reason: InstallReason,
+}
+
+// Why a package was installed
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallReason {
+    #[default]
+    Explicit,
+    Dependency,
+}
+
+impl InstallReason {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Explicit => "explicit",
+            Self::Dependency => "dependency",
+        }
+    }
+}
+
+// Kind of filesystem entry a package file was installed as
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileType {
+    // Recorded before metadata was stored
+    #[default]
+    Unknown,
+    Regular,
+    Directory,
+    Symlink,
+    Other,
+}
+
+impl FileType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Regular => "regular",
+            Self::Directory => "directory",
+            Self::Symlink => "symlink",
+            Self::Other => "other",
+        }
+    }
+}
+
+// How an installed regular file was placed from its repo copy
+#[repr(u8)]
+#[stabby::stabby]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStrategy {
+    // Shares the inode of the repo copy
+    #[default]
+    HardLink,
+    // Shares data extents with the repo copy (FICLONE)
+    Reflink,
+    CopyFileRange,
+    Copy,
+}
+
+impl LinkStrategy {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::HardLink => "hard_link",
+            Self::Reflink => "reflink",
+            Self::CopyFileRange => "copy_file_range",
+            Self::Copy => "copy",
+        }
+    }
+}
+
+// Installed file with the metadata it had at install time; the path is relative to the root
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub file_type: FileType,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub mode: u32,
+    #[serde(default)]
+    pub uid: u32,
+    #[serde(default)]
+    pub gid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<PathBuf>,
+    // Set for regular files only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_strategy: Option<LinkStrategy>,
+    // Protected configuration file, kept when modified locally
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub config: bool,
+}
+
+impl From<PathBuf> for FileEntry {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageScripts {
+    pub pre_install: Option<String>,
+    pub post_install: Option<String>,
+    pub pre_remove: Option<String>,
+    pub post_remove: Option<String>,
 }
 
 #[stabby::stabby]
@@ -21,6 +148,10 @@ pub struct ExtractedPackage {
     pub format: StabString,
     pub file_list: StabVec<StabString>,
     pub dependencies: StabVec<StabString>,
+    pub provides: StabVec<StabString>,
+    pub conflicts: StabVec<StabString>,
+    // Entries of file_list that are configuration files
+    pub config_files: StabVec<StabString>,
 
     pub pre_install: StabOption<StabString>,
     pub post_install: StabOption<StabString>,
@@ -28,6 +159,42 @@ pub struct ExtractedPackage {
     pub post_remove: StabOption<StabString>,
 }
 
+impl ExtractedPackage {
+    pub fn scripts(&self) -> PackageScripts {
+        let to_string = |script: &StabOption<StabString>| script.as_ref().map(|s| s.to_string());
+
+        PackageScripts {
+            pre_install: to_string(&self.pre_install),
+            post_install: to_string(&self.post_install),
+            pre_remove: to_string(&self.pre_remove),
+            post_remove: to_string(&self.post_remove),
+        }
+    }
+
+    pub fn metadata(&self) -> PackageMetadata {
+        PackageMetadata {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            format: self.format.clone(),
+            dependencies: self.dependencies.clone(),
+            provides: self.provides.clone(),
+            conflicts: self.conflicts.clone(),
+        }
+    }
+}
+
+// Package description read from an archive without extracting its files
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct PackageMetadata {
+    pub name: StabString,
+    pub version: StabString,
+    pub format: StabString,
+    pub dependencies: StabVec<StabString>,
+    pub provides: StabVec<StabString>,
+    pub conflicts: StabVec<StabString>,
+}
+
 pub enum OSTreeOperation {
     Install,
     Remove,